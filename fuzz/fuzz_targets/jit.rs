@@ -0,0 +1,55 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! The generated code must behave like the interpreter for every program the interpreter finishes
+//! or stops by leaving the memory within the step limit.
+#![no_main]
+
+use brainfuck_fuzz::Case;
+use libfuzzer_sys::fuzz_target;
+
+#[cfg(target_arch = "aarch64")]
+fuzz_target!(|case: Case| {
+    use brainfuck::commands::config::Config;
+    use brainfuck::commands::verify::{VerifyError, verify_outcomes};
+
+    const MEMORY_SIZE: usize = 64;
+    const STEP_LIMIT: usize = 100_000;
+
+    let config = Config::new(case.program.0.clone(), MEMORY_SIZE, false, None);
+    if let Err(VerifyError::Divergence(divergence)) =
+        verify_outcomes(&config, &case.input, Some(STEP_LIMIT))
+    {
+        panic!("{:?}: {}", case.program.0, divergence);
+    }
+});
+
+// There is no JIT for other architectures
+#[cfg(not(target_arch = "aarch64"))]
+fuzz_target!(|_case: Case| {});
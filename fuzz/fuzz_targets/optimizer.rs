@@ -0,0 +1,101 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! The optimizations must not change the behaviour of a program: if the unoptimized AST finishes
+//! within the step limit, the optimized AST must finish with the same memory, data pointer and
+//! output. Programs that fail must fail after optimizing too. The data pointer must stay in the
+//! range of the static analysis, and programs without bounds checks must not leave the memory.
+//! Minified programs that finish must produce the same output.
+#![no_main]
+
+use brainfuck::commands::config::Config;
+use brainfuck::commands::minify::minify;
+use brainfuck::jit::analysis::{Interval, analyze};
+use brainfuck::jit::ast::{AST, MAX_OPT_LEVEL};
+use brainfuck::jit::evaluator::evaluate;
+use brainfuck::vm::program::Program;
+use brainfuck_fuzz::Case;
+use libfuzzer_sys::fuzz_target;
+
+const MEMORY_SIZE: usize = 64;
+const STEP_LIMIT: usize = 100_000;
+
+fuzz_target!(|case: Case| {
+    let code = &case.program.0;
+    for rle in [false, true] {
+        let ast =
+            |opt_level| AST::new(Program::new(code, rle).unwrap(), opt_level, MEMORY_SIZE).unwrap();
+        let reference = evaluate(&ast(0), &case.input, MEMORY_SIZE, STEP_LIMIT);
+        for opt_level in 1..=MAX_OPT_LEVEL {
+            let optimized_ast = ast(opt_level);
+            let optimized = evaluate(&optimized_ast, &case.input, MEMORY_SIZE, STEP_LIMIT);
+            let analysis = analyze(&optimized_ast, MEMORY_SIZE);
+            match &optimized {
+                Ok(optimized) => assert!(
+                    analysis
+                        .data_ptr
+                        .contains(&Interval::single(optimized.data_ptr as isize)),
+                    "data pointer {} outside of {} for {code:?}",
+                    optimized.data_ptr,
+                    analysis.data_ptr
+                ),
+                Err(_) => assert_ne!(analysis.checks().1, 0, "unchecked fault for {code:?}"),
+            }
+            match (&reference, &optimized) {
+                (Ok(reference), _) if !reference.finished => (),
+                (Ok(reference), Ok(optimized)) => {
+                    assert!(optimized.finished, "O{opt_level} did not finish {code:?}");
+                    assert_eq!(
+                        (&reference.memory, reference.data_ptr, &reference.output),
+                        (&optimized.memory, optimized.data_ptr, &optimized.output),
+                        "O{opt_level} differs for {code:?}"
+                    );
+                }
+                (Err(_), Err(_)) => (),
+                _ => panic!(
+                    "O{opt_level} fails differently for {code:?}: {reference:?} {optimized:?}"
+                ),
+            }
+        }
+    }
+
+    let source = minify(Config::new(code.to_string(), MEMORY_SIZE, false, None)).unwrap();
+    let ast = |code| AST::new(Program::new(code, false).unwrap(), 0, MEMORY_SIZE).unwrap();
+    if let (Ok(reference), Ok(minified)) = (
+        evaluate(&ast(code), &case.input, MEMORY_SIZE, STEP_LIMIT),
+        evaluate(&ast(&source), &case.input, MEMORY_SIZE, STEP_LIMIT),
+    ) && reference.finished
+        && minified.finished
+    {
+        assert_eq!(
+            reference.output, minified.output,
+            "minified {source:?} differs for {code:?}"
+        );
+    }
+});
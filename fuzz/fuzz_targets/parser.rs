@@ -0,0 +1,70 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Parse arbitrary text: the parser must never panic and every valid program must convert into an
+//! AST, be analyzed and generate code at every optimization level. Formatting must keep the
+//! instructions of every valid program.
+#![no_main]
+
+use brainfuck::commands::fmt::{FmtOptions, format_source};
+use brainfuck::jit::analysis::analyze;
+use brainfuck::jit::ast::{AST, MAX_OPT_LEVEL};
+use brainfuck::vm::program::Program;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let code = String::from_utf8_lossy(data);
+    if Program::new(&code, false).is_ok() {
+        for group in [false, true] {
+            let options = FmtOptions {
+                width: 20,
+                group,
+                ..Default::default()
+            };
+            format_source(&code, &options).unwrap();
+        }
+    }
+    for rle in [false, true] {
+        let Ok(program) = Program::new(&code, rle) else {
+            continue;
+        };
+        assert_eq!(program.code.len(), program.locations.len());
+        for (start, end) in &program.jump_table {
+            assert_eq!(program.jump_table.get(end), Some(start));
+        }
+
+        for opt_level in 0..=MAX_OPT_LEVEL {
+            let program = Program::new(&code, rle).unwrap();
+            let ast = AST::new(program, opt_level, 64).unwrap();
+            ast.pretty_print();
+            let analysis = analyze(&ast, 64);
+            ast.generate(true, Some(&analysis));
+        }
+    }
+});
@@ -0,0 +1,69 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Inputs shared by the fuzz targets.
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// Building blocks of the generated programs. Besides the plain instructions there are the loops
+/// the optimizer looks for and some comment characters.
+const TOKENS: &[&str] = &[
+    "+", "-", "<", ">", ".", ",", "[", "]", "+", "-", "<", ">", "[-]", "[+]", "[--]", "[->+<]",
+    "[-<+>]", "[>+<-]", "[<+>-]", "\n", " x ",
+];
+
+/// A brainfuck program with balanced brackets.
+#[derive(Debug)]
+pub struct BalancedProgram(pub String);
+
+impl<'a> Arbitrary<'a> for BalancedProgram {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut code = String::new();
+        let mut depth = 0usize;
+        for _ in 0..u.arbitrary_len::<u8>()? {
+            let token = *u.choose(TOKENS)?;
+            match token {
+                "[" => depth += 1,
+                // Skip closing brackets without an opening one
+                "]" if depth == 0 => continue,
+                "]" => depth -= 1,
+                _ => (),
+            }
+            code.push_str(token);
+        }
+        code.push_str(&"]".repeat(depth));
+        Ok(BalancedProgram(code))
+    }
+}
+
+/// A program together with its input.
+#[derive(Debug, Arbitrary)]
+pub struct Case {
+    pub program: BalancedProgram,
+    pub input: String,
+}
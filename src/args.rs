@@ -29,11 +29,17 @@
 //
 use std::{fs, path::PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use clap::Parser;
 use log::info;
 
-use super::brainfuck::commands::config::Config;
+use brainfuck::commands::config::Config;
+use brainfuck::commands::lint::LintFormat;
+use brainfuck::commands::profile::ProfileFormat;
+use brainfuck::commands::test::Engine;
+#[cfg(target_arch = "aarch64")]
+use brainfuck::jit::ast::MAX_OPT_LEVEL;
+use brainfuck::vm::preprocessor::preprocess;
 
 #[derive(Debug, Clone, Parser)]
 #[command(version, about, long_about = None)]
@@ -46,6 +52,20 @@ pub struct CLIArgs {
     #[arg(short, long, default_value_t = false)]
     pub rle: bool,
 
+    /// Use the extended syntax: `#` dumps the state of the interpreter or breaks in the debugger
+    /// and `!` separates the program from its input.
+    #[arg(short = 'x', long, default_value_t = false)]
+    pub extended: bool,
+
+    /// Expand the macros, includes and conditionals of the preprocessor before running the
+    /// program.
+    #[arg(short = 'P', long, default_value_t = false)]
+    pub preprocess: bool,
+
+    /// Define a name for the `@ifdef` conditionals of the preprocessor.
+    #[arg(short = 'D', long, value_name = "NAME")]
+    pub define: Vec<String>,
+
     /// Set memory size of the interpreter
     #[arg(short, long, default_value_t=1<<12)]
     pub memory_size: usize,
@@ -65,29 +85,190 @@ pub struct CLIArgs {
 #[derive(Debug, Clone, Parser)]
 pub enum Commands {
     /// Use interpreter for execution (slower)
-    INT {
+    Int {
         #[command(subcommand)]
         command: IntCommand,
     },
 
     /// Use Just In Time Compiler (JIT) to execute program (faster)
     #[cfg(target_arch = "aarch64")]
-    JIT {
+    Jit {
+        /// Optimization level of the generated code
+        #[arg(short = 'O', long, default_value_t = MAX_OPT_LEVEL, value_parser = clap::value_parser!(u8).range(0..=MAX_OPT_LEVEL as i64))]
+        opt_level: u8,
+
         #[command(subcommand)]
         command: JITCommand,
     },
+
+    /// Execute the program with all engines and report the first difference in output, memory,
+    /// data pointer or error
+    Verify {
+        /// File with the input of the program. Without input the program reads nothing.
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+
+        /// Stop the interpreters after this number of instructions
+        #[arg(long)]
+        step_limit: Option<usize>,
+
+        /// Path to the file to verify
+        filename: PathBuf,
+    },
+
+    /// Run the programs with expected output (sibling `.out` file or `{ expected: ... }` header
+    /// comment) and compare their output. Exits with an error if any test fails.
+    Test {
+        /// Engine that executes the programs
+        #[arg(short, long, value_enum, default_value_t = Engine::Int)]
+        engine: Engine,
+
+        /// Stop the interpreter after this number of instructions and fail the test
+        #[arg(long)]
+        step_limit: Option<usize>,
+
+        /// Programs and directories to search for `*.bf` files
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Reformat the program with one indentation level per loop. The formatted program has exactly
+    /// the same instructions.
+    Fmt {
+        /// Spaces per loop level
+        #[arg(long, default_value_t = 4)]
+        indent: usize,
+
+        /// Length of the lines. Comments and long runs may exceed it.
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+
+        /// Separate runs of the same instruction with spaces and never split them
+        #[arg(long)]
+        group: bool,
+
+        /// Remove comments and blank lines
+        #[arg(long)]
+        strip_comments: bool,
+
+        /// Overwrite the file instead of printing the formatted program
+        #[arg(short, long)]
+        write: bool,
+
+        /// Path to the file to format
+        filename: PathBuf,
+    },
+
+    /// Print the program as short brainfuck source without comments, dead loops and cancelling
+    /// instructions
+    Minify {
+        /// Path to the file to minify
+        filename: PathBuf,
+    },
+
+    /// Print the program with the macros, includes and conditionals of the preprocessor expanded
+    Expand {
+        /// Path to the file to expand
+        filename: PathBuf,
+    },
+
+    /// Compute the data pointer range of the program without running it, suggest the minimal
+    /// memory size and warn about instructions that always leave the memory
+    Analyze {
+        /// Path to the file to analyze
+        filename: PathBuf,
+    },
+
+    /// Check the program for common mistakes like dead loops or cancelling instructions. Exits
+    /// with an error if there are findings.
+    Lint {
+        /// Output format of the findings
+        #[arg(long, value_enum, default_value_t = LintFormat::Text)]
+        format: LintFormat,
+
+        /// Path to the file to check
+        filename: PathBuf,
+    },
+
+    /// Generate a program that prints the text. The program is checked with the interpreter.
+    Generate {
+        /// Text to print
+        #[arg(required_unless_present = "file")]
+        text: Option<String>,
+
+        /// Print the content of the file instead
+        #[arg(short, long, value_name = "FILE", conflicts_with = "text")]
+        file: Option<PathBuf>,
+
+        /// Number of cells the program may use
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+        cells: u16,
+
+        /// Do not rely on cells wrapping around from 0 to 255 and back
+        #[arg(long)]
+        no_wrapping: bool,
+    },
+
+    /// Compare the execution times of all engines
+    Bench {
+        /// Number of runs per engine
+        #[arg(short = 'n', long, default_value_t = 10)]
+        runs: usize,
+
+        /// File with the input of the program. Without input the program reads nothing.
+        #[arg(short, long, value_name = "FILE")]
+        input: Option<PathBuf>,
+
+        /// Path to the file to benchmark
+        filename: PathBuf,
+    },
 }
 
 #[derive(Debug, Clone, Parser)]
 pub enum IntCommand {
     /// Start the debugger
-    Debug { filename: PathBuf },
+    Debug {
+        /// Speak the Debug Adapter Protocol (DAP) over stdin/stdout instead of starting the REPL
+        #[clap(long)]
+        dap: bool,
+
+        /// Act as GDB remote target listening on the given TCP port instead of starting the REPL
+        #[clap(long, value_name = "PORT", conflicts_with = "dap")]
+        gdb: Option<u16>,
+
+        /// Start the full screen terminal UI instead of the REPL
+        #[clap(long, conflicts_with_all = ["dap", "gdb"])]
+        tui: bool,
+
+        /// Execute the debugger commands in the file ('-' for stdin) instead of starting the REPL.
+        /// Exits with an error if an `expect` command fails.
+        #[clap(long, value_name = "FILE", conflicts_with_all = ["dap", "gdb", "tui"])]
+        script: Option<PathBuf>,
+
+        /// Path to the file to debug
+        filename: PathBuf,
+    },
 
     /// Run the program in the interpreter (slower)
     Run {
-        /// Dump profiling data. This will create/override the 'profile.txt' file.
-        #[clap(short, long)]
-        profile: bool,
+        /// Dump profiling data into the file (default 'profile.txt') and print a report of the
+        /// hottest loops to stderr.
+        #[clap(short, long, value_name = "FILE", num_args = 0..=1, default_missing_value = "profile.txt")]
+        profile: Option<PathBuf>,
+
+        /// Format of the profiling data.
+        #[clap(long, value_enum, default_value_t = ProfileFormat::Raw, requires = "profile")]
+        profile_format: ProfileFormat,
+
+        /// Write the data pointer range and the read/write statistics of every memory cell into
+        /// the file (default 'memory.txt').
+        #[clap(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "memory.txt")]
+        memory_profile: Option<PathBuf>,
+
+        /// Write a heatmap of the memory accesses into the image file (PNG for '.png' files,
+        /// PPM otherwise).
+        #[clap(long, value_name = "FILE")]
+        heatmap: Option<PathBuf>,
 
         /// Path to the file to execute
         filename: PathBuf,
@@ -116,19 +297,50 @@ pub enum JITCommand {
     Dump { filename: PathBuf, output: PathBuf },
 
     /// Run the program
-    Run { filename: PathBuf },
+    Run {
+        /// Count the entries and iterations of every loop and print a report of the hottest
+        /// loops to stderr.
+        #[clap(short, long)]
+        profile: bool,
+
+        /// Write /tmp/perf-<pid>.map so `perf report` attributes samples to brainfuck loops.
+        #[clap(long)]
+        perf_map: bool,
+
+        /// Write /tmp/jit-<pid>.dump for `perf inject --jit`.
+        #[clap(long)]
+        jitdump: bool,
+
+        filename: PathBuf,
+    },
 }
 
 impl CLIArgs {
-    pub fn config(&self) -> Result<Config> {
-        let memory_size = self.memory_size;
-        let rle = self.rle;
-        let memory_dump = self.dump_memory.clone();
+    /// The program file of the command. Commands working on several programs have none.
+    pub fn filename(&self) -> Option<&PathBuf> {
         let filename = match &self.command {
-            Commands::INT {
-                command: IntCommand::Debug { filename },
+            Commands::Test {
+                engine: _,
+                step_limit: _,
+                paths: _,
+            } => return None,
+            Commands::Generate {
+                text: _,
+                file: _,
+                cells: _,
+                no_wrapping: _,
+            } => return None,
+            Commands::Int {
+                command:
+                    IntCommand::Debug {
+                        dap: _,
+                        gdb: _,
+                        tui: _,
+                        script: _,
+                        filename,
+                    },
             } => filename,
-            Commands::INT {
+            Commands::Int {
                 command:
                     IntCommand::Disassemble {
                         filename,
@@ -136,33 +348,109 @@ impl CLIArgs {
                         end: _,
                     },
             } => filename,
-            Commands::INT {
+            Commands::Int {
                 command:
                     IntCommand::Run {
                         profile: _,
+                        profile_format: _,
+                        memory_profile: _,
+                        heatmap: _,
                         filename,
                     },
             } => filename,
             #[cfg(target_arch = "aarch64")]
-            Commands::JIT {
-                command: JITCommand::Run { filename },
+            Commands::Jit {
+                opt_level: _,
+                command:
+                    JITCommand::Run {
+                        profile: _,
+                        perf_map: _,
+                        jitdump: _,
+                        filename,
+                    },
             } => filename,
             #[cfg(target_arch = "aarch64")]
-            Commands::JIT {
+            Commands::Jit {
+                opt_level: _,
                 command: JITCommand::Ast { filename },
             } => filename,
             #[cfg(target_arch = "aarch64")]
-            Commands::JIT {
+            Commands::Jit {
+                opt_level: _,
                 command:
                     JITCommand::Dump {
                         filename,
                         output: _,
                     },
             } => filename,
+            Commands::Bench {
+                runs: _,
+                input: _,
+                filename,
+            } => filename,
+            Commands::Verify {
+                input: _,
+                step_limit: _,
+                filename,
+            } => filename,
+            Commands::Lint {
+                format: _,
+                filename,
+            } => filename,
+            Commands::Analyze { filename } => filename,
+            Commands::Minify { filename } => filename,
+            Commands::Expand { filename } => filename,
+            Commands::Fmt {
+                indent: _,
+                width: _,
+                group: _,
+                strip_comments: _,
+                write: _,
+                filename,
+            } => filename,
+        };
+        Some(filename)
+    }
+
+    pub fn config(&self) -> Result<Config> {
+        let memory_size = self.memory_size;
+        let rle = self.rle;
+        let extended = self.extended;
+        let memory_dump = self.dump_memory.clone();
+        let test = matches!(
+            self.command,
+            Commands::Test {
+                engine: _,
+                step_limit: _,
+                paths: _,
+            }
+        );
+        if extended && test {
+            bail!("test reads the input from the test headers and does not support --extended");
+        }
+        // Every test reads its own program and `generate` has no program
+        let Some(filename) = self.filename() else {
+            return Ok(Config::new(String::new(), memory_size, rle, memory_dump)
+                .with_extended_syntax(extended));
         };
         info!("Debuging file: {}", filename.to_str().unwrap());
         let code = CLIArgs::read_file(filename)?;
-        Ok(Config::new(code, memory_size, rle, memory_dump))
+        let expand = matches!(self.command, Commands::Expand { filename: _ });
+        if !self.preprocess && !expand {
+            return Ok(
+                Config::new(code, memory_size, rle, memory_dump).with_extended_syntax(extended)
+            );
+        }
+        let expansion = preprocess(&code, filename, &self.define)?;
+        let config = Config::new(expansion.code, memory_size, rle, memory_dump)
+            .with_extended_syntax(extended)
+            .with_source_map(expansion.source_map);
+        // Report unbalanced brackets with the locations in the macro source before any command
+        // parses the expanded code
+        if !expand {
+            config.program()?;
+        }
+        Ok(config)
     }
 
     fn read_file(filename: &PathBuf) -> Result<String> {
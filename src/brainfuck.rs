@@ -29,6 +29,5 @@
 //
 pub mod commands;
 mod io;
-#[cfg(target_arch = "aarch64")]
 pub mod jit;
 pub mod vm;
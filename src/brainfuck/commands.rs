@@ -27,10 +27,19 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
-#[cfg(target_arch = "aarch64")]
-pub mod jit;
+pub mod analyze;
+pub mod bench;
 pub mod common;
 pub mod config;
 pub mod debug;
 pub mod disassemble;
+pub mod fmt;
+pub mod generate;
+#[cfg(target_arch = "aarch64")]
+pub mod jit;
+pub mod lint;
+pub mod minify;
+pub mod profile;
 pub mod run;
+pub mod test;
+pub mod verify;
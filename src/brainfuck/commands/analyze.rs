@@ -0,0 +1,101 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Static analysis of the data pointer range of a program.
+use super::super::commands::config::Config;
+use super::super::jit::analysis;
+use super::super::jit::ast::{AST, MAX_OPT_LEVEL};
+use super::super::vm::program::ProgramError;
+
+/// Analyze the optimized program of the {config} and describe the data pointer range, the
+/// minimal memory size and the instructions that leave the memory whenever they are reached.
+pub fn analyze(config: Config) -> Result<String, ProgramError> {
+    let program = config.program_with_rle(true)?;
+    let ast = AST::new(program, MAX_OPT_LEVEL, config.memory_size)?;
+    let analysis = analysis::analyze(&ast, config.memory_size);
+
+    let mut report = format!("data pointer range: {}\n", analysis.data_ptr);
+    match analysis.minimal_memory_size() {
+        Some(size) => report.push_str(&format!("minimal memory size: {}\n", size)),
+        None => report.push_str(&format!(
+            "minimal memory size: unknown, the data pointer might reach the end of the memory ({} cells)\n",
+            config.memory_size
+        )),
+    }
+    let (accesses, checked) = analysis.checks();
+    report.push_str(&format!(
+        "bounds checks: {} of {} pointer movements\n",
+        checked, accesses
+    ));
+    for warning in analysis.warnings() {
+        report.push_str(&format!("warning: {}\n", warning));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::super::super::vm::preprocessor::preprocess;
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let config = Config::new("+[->>+<<]>>.<<<".to_string(), 16, false, None);
+        assert_eq!(
+            analyze(config).unwrap(),
+            "data pointer range: 0..=2\n\
+             minimal memory size: 3\n\
+             bounds checks: 1 of 3 pointer movements\n\
+             warning: the data pointer moves in front of the first cell outside of loops\n"
+        );
+
+        let config = Config::new("+[>+]".to_string(), 16, false, None);
+        assert!(
+            analyze(config)
+                .unwrap()
+                .contains("minimal memory size: unknown")
+        );
+    }
+
+    #[test]
+    fn test_macro_source() {
+        let expansion =
+            preprocess("@define open [\n>,@open<<]\n", Path::new("test.bf"), &[]).unwrap();
+        let config =
+            Config::new(expansion.code, 16, false, None).with_source_map(expansion.source_map);
+        // The loop starts in the macro body
+        assert!(
+            analyze(config)
+                .unwrap()
+                .contains("in the loop at line 1, column 14\n")
+        );
+    }
+}
@@ -0,0 +1,194 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+use std::time::{Duration, Instant};
+
+#[cfg(target_arch = "aarch64")]
+use super::super::jit::{ast::MAX_OPT_LEVEL, jit::JIT};
+
+use super::super::commands::config::Config;
+use super::super::io::string::{StdinString, StdoutString};
+use super::super::vm::vm::{EvalError, Normal, VM};
+
+/// Timings of all runs of one engine.
+#[derive(Debug)]
+struct Measurement {
+    engine: String,
+    times: Vec<Duration>,
+    // Number of executed uncompressed instructions, only known for the interpreter
+    steps: Option<usize>,
+    // Size of the program: opcodes for the interpreter, machine instructions for the JIT
+    code_size: usize,
+}
+
+impl Measurement {
+    /// Returns (min, median, standard deviation) of the run times.
+    fn statistics(&self) -> (Duration, Duration, Duration) {
+        let mut times = self.times.clone();
+        times.sort();
+        let median = match times.len() {
+            0 => Duration::ZERO,
+            len if len % 2 == 0 => (times[len / 2 - 1] + times[len / 2]) / 2,
+            len => times[len / 2],
+        };
+        let seconds = times.iter().map(Duration::as_secs_f64).collect::<Vec<_>>();
+        let mean = seconds.iter().sum::<f64>() / seconds.len().max(1) as f64;
+        let variance = seconds
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / seconds.len().max(1) as f64;
+        let min = times.first().copied().unwrap_or_default();
+        (min, median, Duration::from_secs_f64(variance.sqrt()))
+    }
+}
+
+fn interpreter(
+    config: &Config,
+    rle: bool,
+    runs: usize,
+    input: &str,
+) -> Result<Measurement, EvalError> {
+    let new_vm = || -> Result<_, EvalError> {
+        Ok(VM::<Normal>::with_program(
+            config.program_with_rle(rle)?,
+            Box::new(StdinString::new(input)),
+            Box::new(StdoutString::new()),
+            config.memory_size,
+        ))
+    };
+    let mut steps = None;
+    let times = (0..runs)
+        .map(|_| {
+            let mut vm = new_vm()?;
+            let now = Instant::now();
+            vm.run()?;
+            let elapsed = now.elapsed();
+            steps = Some(vm.steps());
+            Ok(elapsed)
+        })
+        .collect::<Result<Vec<_>, EvalError>>()?;
+    Ok(Measurement {
+        engine: format!("interpreter{}", if rle { " (RLE)" } else { "" }),
+        times,
+        steps,
+        code_size: new_vm()?.program().code.len(),
+    })
+}
+
+#[cfg(target_arch = "aarch64")]
+fn jit(config: &Config, opt_level: u8, runs: usize, input: &str) -> Result<Measurement, EvalError> {
+    let program = config.program_with_rle(true)?;
+    let jit = JIT::with_program(program, config.memory_size, opt_level)?;
+    let times = (0..runs)
+        .map(|_| {
+            let now = Instant::now();
+            jit.run(Some(input))?;
+            Ok(now.elapsed())
+        })
+        .collect::<Result<Vec<_>, EvalError>>()?;
+    Ok(Measurement {
+        engine: format!("jit -O{}", opt_level),
+        times,
+        steps: None,
+        // Every AArch64 instruction has 4 bytes
+        code_size: jit.generate_code().len() / 4,
+    })
+}
+
+/// Run the program {runs} times with every engine and report the timings. The program reads the
+/// provided {input} and its output is discarded. The JIT timings include the code generation.
+pub fn bench(config: Config, runs: usize, input: &str) -> Result<String, EvalError> {
+    let interpreters = [false, true]
+        .iter()
+        .map(|rle| interpreter(&config, *rle, runs, input));
+    #[cfg(target_arch = "aarch64")]
+    let jits = (0..=MAX_OPT_LEVEL).map(|opt_level| jit(&config, opt_level, runs, input));
+    #[cfg(not(target_arch = "aarch64"))]
+    let jits = std::iter::empty();
+    let measurements = interpreters.chain(jits).collect::<Result<Vec<_>, _>>()?;
+
+    let mut report = format!(
+        "{:<20}  {:>5}  {:>12}  {:>12}  {:>12}  {:>14}  {:>10}\n",
+        "ENGINE", "RUNS", "MIN", "MEDIAN", "STDDEV", "STEPS", "CODE SIZE"
+    );
+    measurements.iter().for_each(|measurement| {
+        let (min, median, stddev) = measurement.statistics();
+        report.push_str(&format!(
+            "{:<20}  {:>5}  {:>12}  {:>12}  {:>12}  {:>14}  {:>10}\n",
+            measurement.engine,
+            measurement.times.len(),
+            format!("{:.2?}", min),
+            format!("{:.2?}", median),
+            format!("{:.2?}", stddev),
+            measurement
+                .steps
+                .map_or("-".to_string(), |steps| steps.to_string()),
+            measurement.code_size,
+        ));
+    });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics() {
+        let measurement = Measurement {
+            engine: "test".to_string(),
+            times: [4, 1, 3, 2].map(Duration::from_millis).to_vec(),
+            steps: None,
+            code_size: 0,
+        };
+        let (min, median, stddev) = measurement.statistics();
+        assert_eq!(min, Duration::from_millis(1));
+        assert_eq!(median, Duration::from_micros(2500));
+        assert_eq!(stddev.as_micros(), 1118);
+    }
+
+    #[test]
+    fn test_bench() -> Result<(), EvalError> {
+        let code = include_str!("../../../examples/helloworld.bf").to_string();
+        let report = bench(Config::new(code, 1 << 10, false, None), 3, "")?;
+        let lines = report.lines().collect::<Vec<_>>();
+        assert!(lines[1].starts_with("interpreter "));
+        assert!(lines[2].starts_with("interpreter (RLE)"));
+        // Both interpreters execute the same number of uncompressed instructions
+        let steps = |line: &str| {
+            line.split_whitespace()
+                .nth_back(1)
+                .unwrap()
+                .parse::<usize>()
+        };
+        assert_eq!(steps(lines[2]).unwrap(), steps(lines[1]).unwrap());
+        Ok(())
+    }
+}
@@ -27,17 +27,15 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
-use std::{
-    fs::File,
-    io::{Error, Write},
-};
-
 use super::super::commands::config::Config;
+use super::super::io::base::Stdin;
+use super::super::io::console::StdinConsole;
+use super::super::io::string::StdinString;
 
-pub(crate) fn dump_memory(config: Config, memory: &Vec<u8>) -> Result<(), Error> {
-    if let Some(mem_dump) = config.memory_dump {
-        let mut file = File::create(mem_dump)?;
-        file.write_all(memory)?;
+/// The input embedded after the `!` of the extended syntax or the console.
+pub(crate) fn stdin(config: &Config, prompt: Option<String>) -> Box<dyn Stdin> {
+    match config.split_input() {
+        (_, Some(input)) => Box::new(StdinString::new(input)),
+        (_, None) => Box::new(StdinConsole::new(prompt)),
     }
-    Ok(())
 }
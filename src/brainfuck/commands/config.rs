@@ -29,12 +29,19 @@
 //
 use std::path::PathBuf;
 
+use super::super::vm::preprocessor::SourceMap;
+use super::super::vm::program::{Program, ProgramError, split_input};
+
 #[derive(Debug)]
 pub struct Config {
     pub code: String,
     pub memory_size: usize,
     pub memory_dump: Option<PathBuf>,
     pub rle: bool,
+    /// Extended syntax: `#` dumps the state and `!` separates the program from its input
+    pub extended: bool,
+    /// Locations in the macro source if the code was preprocessed
+    pub source_map: Option<SourceMap>,
 }
 
 impl Config {
@@ -44,6 +51,51 @@ impl Config {
             memory_size,
             rle,
             memory_dump,
+            extended: false,
+            source_map: None,
+        }
+    }
+
+    /// Enable or disable the extended syntax.
+    pub fn with_extended_syntax(mut self, extended: bool) -> Self {
+        self.extended = extended;
+        self
+    }
+
+    /// Use the {source_map} for the locations of the preprocessed code.
+    pub fn with_source_map(mut self, source_map: SourceMap) -> Self {
+        self.source_map = Some(source_map);
+        self
+    }
+
+    /// The program code and, for the extended syntax, the input after the first `!`.
+    pub fn split_input(&self) -> (&str, Option<&str>) {
+        if self.extended {
+            split_input(&self.code)
+        } else {
+            (&self.code, None)
+        }
+    }
+
+    /// Parse the program using the syntax of the config. The locations of preprocessed programs
+    /// point into the macro source.
+    pub fn program(&self) -> Result<Program, ProgramError> {
+        self.program_with_rle(self.rle)
+    }
+
+    /// Parse the program like `program`, but with the provided {rle} setting.
+    pub fn program_with_rle(&self, rle: bool) -> Result<Program, ProgramError> {
+        let (code, _) = self.split_input();
+        let program = if self.extended {
+            Program::new_extended(code, rle)
+        } else {
+            Program::new(code, rle)
+        };
+        match &self.source_map {
+            Some(source_map) => program
+                .map(|program| source_map.map_program(program))
+                .map_err(|error| source_map.map_error(error)),
+            None => program,
         }
     }
 }
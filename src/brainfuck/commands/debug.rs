@@ -27,10 +27,18 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
+pub mod dap;
+pub mod gdb;
+pub mod script;
+pub mod tui;
+
 use std::num::ParseIntError;
+use std::path::PathBuf;
 use std::process;
 
+use super::super::commands::common::stdin;
 use super::super::commands::config::Config;
+use super::super::io::console::StdoutConsole;
 use super::super::vm::debugger::Debugger;
 use super::super::vm::vm::EvalError;
 
@@ -50,6 +58,26 @@ enum DebuggerCommand {
     /// Run the code until the next break point is hit or the program ends
     Continue,
 
+    #[command(subcommand, visible_alias = "e")]
+    /// Check the state of the VM. Failed expectations make a script fail.
+    Expect(ExpectCommand),
+
+    #[command(visible_alias = "f")]
+    /// Fill a memory range with a single value
+    Fill {
+        #[arg(value_parser = parse_usize_hex)]
+        /// The start address of the range
+        start: usize,
+
+        #[arg(value_parser = parse_usize_hex)]
+        /// The number of cells to fill
+        len: usize,
+
+        #[arg(value_parser = parse_byte)]
+        /// The value to store (decimal, 0x-prefixed hexadecimal or a single character)
+        value: u8,
+    },
+
     #[command(visible_alias = "l")]
     /// List the instructions around the current instruction
     List {
@@ -78,6 +106,14 @@ enum DebuggerCommand {
     /// Show registers of the VM
     Registers,
 
+    #[command(subcommand)]
+    /// Modify the memory or the registers of the VM
+    Set(SetCommand),
+
+    #[command(subcommand)]
+    /// Save or restore the complete state of the program
+    Snapshot(SnapshotCommand),
+
     #[command(visible_alias = "s")]
     /// Execute the next instruction
     Step,
@@ -110,6 +146,142 @@ pub enum BreakpointCommand {
     },
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum SetCommand {
+    #[command(visible_alias = "m")]
+    /// Write a value, a character ('c') or a string ("text") into memory
+    Mem {
+        #[arg(value_parser = parse_usize_hex)]
+        /// The address of the first memory cell to write
+        address: usize,
+
+        #[arg(value_parser = parse_bytes)]
+        /// The value to write
+        value: Bytes,
+    },
+
+    /// Set the data pointer
+    Dp {
+        #[arg(value_parser = parse_usize_hex)]
+        /// The new data pointer
+        value: usize,
+    },
+
+    /// Set the instruction pointer
+    Ip {
+        #[arg(value_parser = parse_usize_hex)]
+        /// The new instruction pointer
+        value: usize,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SnapshotCommand {
+    #[command(visible_alias = "s")]
+    /// Save registers, memory, input, output and breakpoints into a file
+    Save {
+        /// The snapshot file to create
+        name: PathBuf,
+    },
+
+    #[command(visible_alias = "l")]
+    /// Restore the state saved in a snapshot file
+    Load {
+        /// The snapshot file to load
+        name: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExpectCommand {
+    #[command(visible_alias = "m")]
+    /// Compare a memory cell, e.g., `expect mem 5 == 72`
+    Mem {
+        #[arg(value_parser = parse_usize_hex)]
+        /// The address of the memory cell
+        address: usize,
+
+        #[arg(value_parser = parse_comparison)]
+        /// One of ==, !=, <, <=, >, >=
+        comparison: Comparison,
+
+        #[arg(value_parser = parse_byte)]
+        /// The expected value (decimal, 0x-prefixed hexadecimal or a single character)
+        value: u8,
+    },
+
+    /// Compare the data pointer
+    Dp {
+        #[arg(value_parser = parse_comparison)]
+        /// One of ==, !=, <, <=, >, >=
+        comparison: Comparison,
+
+        #[arg(value_parser = parse_usize_hex)]
+        /// The expected value
+        value: usize,
+    },
+
+    /// Compare the instruction pointer
+    Ip {
+        #[arg(value_parser = parse_comparison)]
+        /// One of ==, !=, <, <=, >, >=
+        comparison: Comparison,
+
+        #[arg(value_parser = parse_usize_hex)]
+        /// The expected value
+        value: usize,
+    },
+
+    #[command(visible_alias = "o")]
+    /// Compare the generated output. Supports \n, \t and \\ escapes.
+    Output {
+        #[arg(short, long)]
+        /// Only require the output to contain the text
+        contains: bool,
+
+        /// The expected output
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Comparison {
+    fn matches<T: PartialOrd>(self, actual: T, expected: T) -> bool {
+        match self {
+            Comparison::Equal => actual == expected,
+            Comparison::NotEqual => actual != expected,
+            Comparison::Less => actual < expected,
+            Comparison::LessEqual => actual <= expected,
+            Comparison::Greater => actual > expected,
+            Comparison::GreaterEqual => actual >= expected,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterEqual => ">=",
+        }
+    }
+}
+
+/// Wrapper so clap does not treat the value as a list of arguments.
+#[derive(Debug, Clone)]
+pub struct Bytes(Vec<u8>);
+
 pub fn debug(config: Config) -> Result<(), EvalError> {
     println!("Welcome to the brainfuck debugger. Use Ctrl+D to exit the debugger.");
     let prompt = DefaultPrompt {
@@ -126,20 +298,42 @@ pub fn debug(config: Config) -> Result<(), EvalError> {
         })
         .build();
 
-    let mut debugger = Debugger::new(&config.code, config.rle, config.memory_size)?;
-    rl.repl(move |command| match command {
-        DebuggerCommand::Breakpoint(cmd) => handle_breakpoint(&mut debugger, cmd),
-        DebuggerCommand::Continue => handle_run(&mut debugger),
-        DebuggerCommand::List { index } => handle_list(&mut debugger, index),
-        DebuggerCommand::Memory { start, len } => handle_memory(&debugger, start, len),
-        DebuggerCommand::Output => handle_output(&debugger),
-        DebuggerCommand::Registers => handle_registers(&debugger),
-        DebuggerCommand::Step => handle_step(&mut debugger),
-        DebuggerCommand::Quit => process::exit(0),
+    let mut debugger = Debugger::with_program(
+        config.program()?,
+        config.memory_size,
+        stdin(&config, Some("INPUT: ".to_string())),
+        Box::new(StdoutConsole::new()),
+    );
+    rl.repl(move |command| {
+        execute(&mut debugger, command);
     });
     Ok(())
 }
 
+/// Execute a single debugger command.
+///
+/// # Returns
+///     false if the command failed, e.g., the program left the memory or an expectation failed
+fn execute(debugger: &mut Debugger, command: DebuggerCommand) -> bool {
+    match command {
+        DebuggerCommand::Breakpoint(cmd) => handle_breakpoint(debugger, cmd),
+        DebuggerCommand::Continue => return handle_run(debugger),
+        DebuggerCommand::Expect(cmd) => return handle_expect(debugger, cmd),
+        DebuggerCommand::Fill { start, len, value } => {
+            return handle_fill(debugger, start, len, value);
+        }
+        DebuggerCommand::List { index } => handle_list(debugger, index),
+        DebuggerCommand::Memory { start, len } => handle_memory(debugger, start, len),
+        DebuggerCommand::Output => handle_output(debugger),
+        DebuggerCommand::Registers => handle_registers(debugger),
+        DebuggerCommand::Set(cmd) => return handle_set(debugger, cmd),
+        DebuggerCommand::Snapshot(cmd) => return handle_snapshot(debugger, cmd),
+        DebuggerCommand::Step => return handle_step(debugger),
+        DebuggerCommand::Quit => process::exit(0),
+    }
+    true
+}
+
 fn handle_output(debugger: &Debugger) {
     println!("{}", debugger.output())
 }
@@ -155,22 +349,120 @@ fn handle_memory(debugger: &Debugger, start: usize, len: usize) {
     println!("{}", dump)
 }
 
+fn handle_fill(debugger: &mut Debugger, start: usize, len: usize, value: u8) -> bool {
+    if let Err(error) = debugger.memory_fill(start, len, value) {
+        println!("{}", error);
+        return false;
+    }
+    true
+}
+
+fn handle_set(debugger: &mut Debugger, command: SetCommand) -> bool {
+    let result = match command {
+        SetCommand::Mem { address, value } => debugger.memory_write(address, &value.0),
+        SetCommand::Dp { value } => debugger.set_data_ptr(value),
+        SetCommand::Ip { value } => debugger.set_ip(value),
+    };
+    if let Err(error) = result {
+        println!("{}", error);
+        return false;
+    }
+    true
+}
+
+fn handle_expect(debugger: &Debugger, command: ExpectCommand) -> bool {
+    let (ip, data_ptr) = debugger.registers();
+    let (passed, description) = match command {
+        ExpectCommand::Mem {
+            address,
+            comparison,
+            value,
+        } => match debugger.memory(address) {
+            Some(actual) => (
+                comparison.matches(actual, value),
+                format!(
+                    "mem[{}] = {}, expected {} {}",
+                    address,
+                    actual,
+                    comparison.symbol(),
+                    value
+                ),
+            ),
+            None => (false, format!("mem[{}] is out of bounds", address)),
+        },
+        ExpectCommand::Dp { comparison, value } => (
+            comparison.matches(data_ptr, value),
+            format!(
+                "dp = {}, expected {} {}",
+                data_ptr,
+                comparison.symbol(),
+                value
+            ),
+        ),
+        ExpectCommand::Ip { comparison, value } => (
+            comparison.matches(ip, value),
+            format!("ip = {}, expected {} {}", ip, comparison.symbol(), value),
+        ),
+        ExpectCommand::Output { contains, text } => {
+            let expected = unescape(&text);
+            let output = debugger.output();
+            let passed = if contains {
+                output.contains(&expected)
+            } else {
+                output == expected
+            };
+            (
+                passed,
+                format!(
+                    "output = {:?}, expected {} {:?}",
+                    output,
+                    if contains { "to contain" } else { "==" },
+                    expected
+                ),
+            )
+        }
+    };
+    println!("{}: {}", if passed { "PASS" } else { "FAIL" }, description);
+    passed
+}
+
+fn handle_snapshot(debugger: &mut Debugger, command: SnapshotCommand) -> bool {
+    let result = match &command {
+        SnapshotCommand::Save { name } => debugger.snapshot_save(name),
+        SnapshotCommand::Load { name } => debugger.snapshot_load(name),
+    };
+    match (result, command) {
+        (Ok(()), SnapshotCommand::Save { name }) => println!("Saved snapshot {:?}", name),
+        (Ok(()), SnapshotCommand::Load { name }) => {
+            let (ip, data_ptr) = debugger.registers();
+            println!("Loaded snapshot {:?} (ip={}, dp={})", name, ip, data_ptr);
+        }
+        (Err(error), _) => {
+            println!("{}", error);
+            return false;
+        }
+    }
+    true
+}
+
 fn handle_list(debugger: &mut Debugger, index: Option<usize>) {
     let output = debugger.program_list(index);
     println!("{}", output)
 }
 
-fn handle_step(debugger: &mut Debugger) {
+fn handle_step(debugger: &mut Debugger) -> bool {
     match debugger.step() {
         Ok(true) => println!("Finished execution"),
         Ok(false) => {}
         Err(error) => {
-            println!("{:?}", error)
+            print_error(&error);
+            return false;
         }
     }
+    true
 }
 
-fn handle_run(debugger: &mut Debugger) {
+fn handle_run(debugger: &mut Debugger) -> bool {
     match debugger.run() {
         Ok(true) => println!("Finished execution"),
         Ok(false) => {
@@ -178,9 +470,19 @@ fn handle_run(debugger: &mut Debugger) {
             println!("Run until breakpoint 0x{:04} ({})", ip, ip);
         }
         Err(error) => {
-            println!("{:?}", error)
+            print_error(&error);
+            return false;
         }
     }
+    true
+}
+
+/// Print the {error} of the program with the details of a fault.
+fn print_error(error: &EvalError) {
+    match error {
+        EvalError::DataPointerOutOfBounds(fault) => println!("{}: {}", error, fault),
+        _ => println!("{}", error),
+    }
 }
 
 fn handle_breakpoint(debugger: &mut Debugger, command: BreakpointCommand) {
@@ -215,3 +517,73 @@ fn parse_usize_hex(s: &str) -> Result<usize, ParseIntError> {
         s.parse::<usize>()
     }
 }
+
+/// Custom parser for a single byte. Accepts decimal, 0x-prefixed hexadecimal or a single character.
+fn parse_byte(s: &str) -> Result<u8, String> {
+    match parse_bytes(s)?.0[..] {
+        [value] => Ok(value),
+        _ => Err(format!("'{}' is not a single byte", s)),
+    }
+}
+
+/// Custom parser for memory values.
+///
+/// The shell style splitting of the REPL already removes one level of quotes, so a bare word that
+/// is not a number is taken as a string as well. Quotes that survive (e.g., `"'7'"`) force the
+/// character/string interpretation.
+fn parse_bytes(s: &str) -> Result<Bytes, String> {
+    let unquoted = s
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+        .or_else(|| s.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')));
+    let bytes = if let Some(text) = unquoted {
+        text.as_bytes().to_vec()
+    } else if let Ok(value) = parse_usize_hex(s) {
+        let byte =
+            u8::try_from(value).map_err(|_| format!("{} does not fit into a cell", value))?;
+        vec![byte]
+    } else {
+        s.as_bytes().to_vec()
+    };
+    if bytes.is_empty() {
+        return Err("empty value".to_string());
+    }
+    Ok(Bytes(bytes))
+}
+
+/// Custom parser for the comparison operator of expectations.
+fn parse_comparison(s: &str) -> Result<Comparison, String> {
+    match s {
+        "==" => Ok(Comparison::Equal),
+        "!=" => Ok(Comparison::NotEqual),
+        "<" => Ok(Comparison::Less),
+        "<=" => Ok(Comparison::LessEqual),
+        ">" => Ok(Comparison::Greater),
+        ">=" => Ok(Comparison::GreaterEqual),
+        _ => Err(format!("'{}' is not one of ==, !=, <, <=, >, >=", s)),
+    }
+}
+
+/// Replace the escape sequences \n, \t and \\ in {s}.
+fn unescape(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => {
+                if other != '\\' {
+                    result.push('\\');
+                }
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
@@ -0,0 +1,637 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Debug Adapter Protocol (DAP) front end for the debugger.
+//!
+//! The adapter speaks DAP over stdin/stdout so that editors like VS Code can drive the
+//! `Debugger`. Breakpoints are set by source line, the registers and the tape around the data
+//! pointer are exposed as variables, and the whole tape is available as memory reference.
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use serde_json::{Value, json};
+
+use super::super::super::commands::config::Config;
+use super::super::super::io::string::{StdinString, StdoutString};
+use super::super::super::vm::debugger::Debugger;
+use super::super::super::vm::vm::EvalError;
+
+// brainfuck programs only have a single thread
+const THREAD_ID: u64 = 1;
+const FRAME_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+const TAPE_REFERENCE: u64 = 2;
+// Number of cells shown on each side of the data pointer in the tape scope
+const TAPE_WINDOW: usize = 8;
+// Number of steps between checks for a pause request while the program runs
+const RUN_SLICE: usize = 1 << 16;
+// Largest accepted message body
+const MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+/// Serve DAP requests on stdin/stdout until the client disconnects.
+pub fn dap(config: Config, filename: &Path) -> Result<(), EvalError> {
+    let mut server = DapServer::new(config, filename, io::stdout().lock())?;
+    server.serve(&mut Requests::spawn(BufReader::new(io::stdin())))?;
+    Ok(())
+}
+
+/// Requests of the client read by a background thread, so that they can be checked for a pause
+/// request while the program runs.
+pub struct Requests {
+    receiver: Receiver<Result<Value, io::Error>>,
+    // Requests that arrived while the program was running
+    pending: VecDeque<Value>,
+}
+
+impl Requests {
+    pub fn spawn<R: BufRead + Send + 'static>(mut reader: R) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            while let Some(message) = read_message(&mut reader).transpose() {
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Requests {
+            receiver,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The next request. Returns None at the end of the stream.
+    fn next(&mut self) -> Result<Option<Value>, io::Error> {
+        if let Some(request) = self.pending.pop_front() {
+            return Ok(Some(request));
+        }
+        match self.receiver.recv() {
+            Ok(message) => message.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Check without blocking whether the client asked to pause. Other requests are kept until
+    /// the program stopped.
+    fn pause_request(&mut self) -> Result<Option<Value>, io::Error> {
+        while let Ok(message) = self.receiver.try_recv() {
+            let request = message?;
+            if request["command"] == "pause" {
+                return Ok(Some(request));
+            }
+            self.pending.push_back(request);
+        }
+        Ok(None)
+    }
+}
+
+pub struct DapServer<W: Write> {
+    config: Config,
+    source: PathBuf,
+    writer: W,
+    seq: u64,
+    debugger: Debugger,
+    // Instruction indexes of the breakpoints set via source lines
+    line_breakpoints: Vec<usize>,
+    stop_on_entry: bool,
+    // Number of output bytes already forwarded to the client
+    output_sent: usize,
+    terminated: bool,
+}
+
+impl<W: Write> DapServer<W> {
+    pub fn new(config: Config, source: &Path, writer: W) -> Result<Self, EvalError> {
+        let debugger = DapServer::<W>::create_debugger(&config, "")?;
+        Ok(DapServer {
+            config,
+            source: source.to_path_buf(),
+            writer,
+            seq: 0,
+            debugger,
+            line_breakpoints: vec![],
+            stop_on_entry: false,
+            output_sent: 0,
+            terminated: false,
+        })
+    }
+
+    fn create_debugger(config: &Config, input: &str) -> Result<Debugger, EvalError> {
+        // The input of the launch request replaces the embedded input
+        let input = match config.split_input() {
+            (_, Some(embedded)) if input.is_empty() => embedded,
+            _ => input,
+        };
+        Ok(Debugger::with_program(
+            config.program()?,
+            config.memory_size,
+            Box::new(StdinString::new(input)),
+            Box::new(StdoutString::new()),
+        ))
+    }
+
+    /// Handle requests until the client disconnects or closes the stream.
+    pub fn serve(&mut self, requests: &mut Requests) -> Result<(), io::Error> {
+        while let Some(request) = requests.next()? {
+            if !self.handle(&request, requests)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a single request. Returns false if the session ended.
+    fn handle(&mut self, request: &Value, requests: &mut Requests) -> Result<bool, io::Error> {
+        let arguments = &request["arguments"];
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsReadMemoryRequest": true,
+                    "supportsWriteMemoryRequest": true,
+                });
+                self.respond(request, Ok(capabilities))?;
+                self.event("initialized", json!({}))?;
+            }
+            "launch" => {
+                let result = self.launch(arguments);
+                self.respond(request, result)?;
+            }
+            "setBreakpoints" => {
+                let result = self.set_breakpoints(arguments);
+                self.respond(request, result)?;
+            }
+            "configurationDone" => {
+                self.respond(request, Ok(json!({})))?;
+                if self.stop_on_entry {
+                    self.stopped("entry", None)?;
+                } else {
+                    self.run(requests)?;
+                }
+            }
+            "threads" => {
+                let threads = json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] });
+                self.respond(request, Ok(threads))?;
+            }
+            "stackTrace" => {
+                let result = self.stack_trace();
+                self.respond(request, result)?;
+            }
+            "scopes" => {
+                let scopes = json!({ "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                    { "name": "Tape", "variablesReference": TAPE_REFERENCE, "expensive": false },
+                ]});
+                self.respond(request, Ok(scopes))?;
+            }
+            "variables" => {
+                let result = self.variables(arguments);
+                self.respond(request, result)?;
+            }
+            "readMemory" => {
+                let result = self.read_memory(arguments);
+                self.respond(request, result)?;
+            }
+            "writeMemory" => {
+                let result = self.write_memory(arguments);
+                self.respond(request, result)?;
+            }
+            "continue" => {
+                self.respond(request, Ok(json!({ "allThreadsContinued": true })))?;
+                self.run(requests)?;
+            }
+            // The program is not running, otherwise `run` would have handled the request
+            "pause" => self.respond(request, Ok(json!({})))?,
+            "next" | "stepIn" => {
+                self.respond(request, Ok(json!({})))?;
+                self.resume(Debugger::step, "step")?;
+            }
+            "stepOut" => {
+                self.respond(request, Ok(json!({})))?;
+                self.resume(Debugger::step_out, "step")?;
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})))?;
+                return Ok(false);
+            }
+            command => {
+                let message = format!("Unsupported request '{}'", command);
+                self.respond(request, Err(message))?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        if let Some(input) = arguments["input"].as_str() {
+            let mut debugger =
+                DapServer::<W>::create_debugger(&self.config, input).map_err(|e| e.to_string())?;
+            self.debugger
+                .breakpoints_list()
+                .into_iter()
+                .for_each(|index| debugger.breakpoints_add(index));
+            self.debugger = debugger;
+        }
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        // The request always contains the full list of breakpoints of the source
+        self.line_breakpoints
+            .drain(..)
+            .for_each(|index| self.debugger.breakpoints_delete(index));
+        let lines = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let breakpoints: Vec<Value> = lines
+            .iter()
+            .enumerate()
+            .map(|(id, breakpoint)| {
+                let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+                let index = self.debugger.program().ip_for_line(line);
+                if let Some(index) = index {
+                    self.debugger.breakpoints_add(index);
+                    self.line_breakpoints.push(index);
+                }
+                json!({ "id": id + 1, "verified": index.is_some(), "line": line })
+            })
+            .collect();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let (ip, _) = self.debugger.registers();
+        let (line, column) = self
+            .debugger
+            .program()
+            .location(ip)
+            .map(|location| (location.line, location.column))
+            .unwrap_or((0, 0));
+        let name = self
+            .source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let path = self
+            .source
+            .canonicalize()
+            .unwrap_or(self.source.clone())
+            .to_string_lossy()
+            .to_string();
+        Ok(json!({
+            "stackFrames": [{
+                "id": FRAME_ID,
+                "name": "main",
+                "line": line,
+                "column": column,
+                "source": { "name": name, "path": path },
+                "instructionPointerReference": format!("{}", ip),
+            }],
+            "totalFrames": 1,
+        }))
+    }
+
+    fn variables(&self, arguments: &Value) -> Result<Value, String> {
+        let (ip, data_ptr) = self.debugger.registers();
+        let variables = match arguments["variablesReference"].as_u64() {
+            Some(REGISTERS_REFERENCE) => vec![
+                json!({
+                    "name": "ip",
+                    "value": format!("0x{:04x} ({})", ip, ip),
+                    "variablesReference": 0,
+                }),
+                json!({
+                    "name": "dp",
+                    "value": format!("0x{:04x} ({})", data_ptr, data_ptr),
+                    "variablesReference": 0,
+                    "memoryReference": format!("0x{:x}", data_ptr),
+                }),
+            ],
+            Some(TAPE_REFERENCE) => {
+                let start = data_ptr.saturating_sub(TAPE_WINDOW);
+                let end = (data_ptr + TAPE_WINDOW).min(self.debugger.memory_size() - 1);
+                (start..=end)
+                    .filter_map(|address| {
+                        let value = self.debugger.memory(address)?;
+                        Some(json!({
+                            "name": format!("[{}]", address),
+                            "value": format_cell(value),
+                            "variablesReference": 0,
+                            "memoryReference": format!("0x{:x}", address),
+                        }))
+                    })
+                    .collect()
+            }
+            _ => return Err("Unknown variables reference".to_string()),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Resolve the memory reference and offset of a memory request.
+    fn memory_address(arguments: &Value) -> Result<i64, String> {
+        let reference = arguments["memoryReference"].as_str().unwrap_or_default();
+        let base = match reference.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => reference.parse::<i64>(),
+        }
+        .map_err(|_| format!("Invalid memory reference '{}'", reference))?;
+        base.checked_add(arguments["offset"].as_i64().unwrap_or(0))
+            .ok_or_else(|| format!("Invalid memory offset for '{}'", reference))
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value, String> {
+        let address = DapServer::<W>::memory_address(arguments)?;
+        let count = arguments["count"].as_u64().unwrap_or(0) as usize;
+        let memory_size = self.debugger.memory_size();
+        if address < 0 || memory_size as i64 <= address {
+            return Ok(json!({ "address": format!("0x{:x}", address), "unreadableBytes": count }));
+        }
+        let start = address as usize;
+        let end = start.saturating_add(count).min(memory_size);
+        let data: Vec<u8> = (start..end)
+            .filter_map(|index| self.debugger.memory(index))
+            .collect();
+        Ok(json!({
+            "address": format!("0x{:x}", start),
+            "data": base64_encode(&data),
+            "unreadableBytes": count - data.len(),
+        }))
+    }
+
+    fn write_memory(&mut self, arguments: &Value) -> Result<Value, String> {
+        let address = DapServer::<W>::memory_address(arguments)?;
+        let data = base64_decode(arguments["data"].as_str().unwrap_or_default())?;
+        let start = usize::try_from(address).map_err(|_| "Negative address".to_string())?;
+        self.debugger
+            .memory_write(start, &data)
+            .map_err(|e| e.to_string())?;
+        Ok(json!({ "bytesWritten": data.len() }))
+    }
+
+    /// Execute {action} and report the new state of the program to the client.
+    fn resume(
+        &mut self,
+        action: fn(&mut Debugger) -> Result<bool, EvalError>,
+        reason: &str,
+    ) -> Result<(), io::Error> {
+        if self.terminated {
+            return self.event("terminated", json!({}));
+        }
+        let result = action(&mut self.debugger);
+        self.report(result, reason)
+    }
+
+    /// Continue in slices of steps until a breakpoint, the end of the program or a pause request
+    /// of the client.
+    fn run(&mut self, requests: &mut Requests) -> Result<(), io::Error> {
+        if self.terminated {
+            return self.event("terminated", json!({}));
+        }
+        loop {
+            let result = match self.debugger.run_for(RUN_SLICE) {
+                Ok(None) => {
+                    self.flush_output()?;
+                    if let Some(pause) = requests.pause_request()? {
+                        self.respond(&pause, Ok(json!({})))?;
+                        return self.stopped("pause", None);
+                    }
+                    continue;
+                }
+                Ok(Some(finished)) => Ok(finished),
+                Err(error) => Err(error),
+            };
+            return self.report(result, "breakpoint");
+        }
+    }
+
+    /// Report the {result} of resuming the program to the client.
+    fn report(&mut self, result: Result<bool, EvalError>, reason: &str) -> Result<(), io::Error> {
+        self.flush_output()?;
+        match result {
+            Ok(true) => {
+                self.terminated = true;
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                self.event("terminated", json!({}))
+            }
+            Ok(false) => {
+                let (ip, _) = self.debugger.registers();
+                if self.debugger.breakpoints_list().contains(&ip) {
+                    self.stopped("breakpoint", None)
+                } else {
+                    self.stopped(reason, None)
+                }
+            }
+            Err(error) => {
+                let output = json!({ "category": "stderr", "output": format!("{}\n", error) });
+                self.event("output", output)?;
+                self.stopped("exception", Some(error.to_string()))
+            }
+        }
+    }
+
+    /// Forward the program output produced since the last call as output event.
+    fn flush_output(&mut self) -> Result<(), io::Error> {
+        let output = self.debugger.output();
+        if self.output_sent < output.len() {
+            let body = json!({ "category": "stdout", "output": &output[self.output_sent..] });
+            self.output_sent = output.len();
+            self.event("output", body)?;
+        }
+        Ok(())
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) -> Result<(), io::Error> {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["text"] = json!(text);
+        }
+        self.event("stopped", body)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> Result<(), io::Error> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<(), io::Error> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<(), io::Error> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let content = message.to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )?;
+        self.writer.flush()
+    }
+}
+
+/// Read the next message. Returns None at the end of the stream.
+fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>, io::Error> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() && content_length.is_some() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let content_length = content_length.unwrap_or_default();
+    if MAX_MESSAGE_SIZE < content_length {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Message of {} bytes exceeds the limit of {} bytes",
+                content_length, MAX_MESSAGE_SIZE
+            ),
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn format_cell(value: u8) -> String {
+    let c = value as char;
+    if c.is_ascii_graphic() {
+        format!("{} (0x{:02x} '{}')", value, value, c)
+    } else {
+        format!("{} (0x{:02x})", value, value)
+    }
+}
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    data.chunks(3)
+        .flat_map(|chunk| {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            (0..4).map(move |index| {
+                if chunk.len() < index {
+                    '='
+                } else {
+                    BASE64_ALPHABET[(triple >> (18 - 6 * index) & 0x3f) as usize] as char
+                }
+            })
+        })
+        .collect()
+}
+
+fn base64_decode(data: &str) -> Result<Vec<u8>, String> {
+    let mut output = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in data.bytes().filter(|c| *c != b'=') {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|x| *x == c)
+            .ok_or(format!("Invalid base64 character '{}'", c as char))?;
+        buffer = ((buffer << 6) | value as u32) & 0xffff;
+        bits += 6;
+        if 8 <= bits {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        for (data, encoded) in [
+            ("", ""),
+            ("H", "SA=="),
+            ("Hi", "SGk="),
+            ("Hi!", "SGkh"),
+            ("Hello World!\n", "SGVsbG8gV29ybGQhCg=="),
+        ] {
+            assert_eq!(base64_encode(data.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded), Ok(data.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_read_message() {
+        let message = |content: &str| read_message(&mut content.as_bytes());
+        let request = message("Content-Length: 2\r\n\r\n{}").unwrap();
+        assert_eq!(request, Some(json!({})));
+        assert!(message("").unwrap().is_none());
+        let error = message("Content-Length: 1073741824\r\n\r\n{}").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_memory_address() {
+        let address = |arguments| DapServer::<Vec<u8>>::memory_address(&arguments);
+        assert_eq!(
+            address(json!({"memoryReference": "0x10", "offset": -2})),
+            Ok(14)
+        );
+        assert_eq!(address(json!({"memoryReference": "7"})), Ok(7));
+        assert!(address(json!({"memoryReference": "x"})).is_err());
+        assert!(address(json!({"memoryReference": "1", "offset": i64::MAX})).is_err());
+    }
+}
@@ -0,0 +1,442 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! GDB remote serial protocol (RSP) stub for the debugger.
+//!
+//! The stub exposes the tape as target memory (address 0 is the first cell) and the `ip` and
+//! `dp` registers as two 64-bit registers. Breakpoint addresses are instruction indexes, the same
+//! indexes the `list` command of the REPL shows.
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+
+use super::super::super::commands::common::stdin;
+use super::super::super::commands::config::Config;
+use super::super::super::io::console::StdoutConsole;
+use super::super::super::vm::debugger::Debugger;
+use super::super::super::vm::vm::EvalError;
+
+// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// Byte gdb sends to interrupt a running target
+const INTERRUPT: u8 = 0x03;
+
+// Number of steps between checks for an interrupt while the target runs
+const RUN_SLICE: usize = 1 << 16;
+
+const REGISTER_NAMES: [&str; 2] = ["ip", "dp"];
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.brainfuck.core">
+    <reg name="ip" bitsize="64" type="code_ptr" regnum="0"/>
+    <reg name="dp" bitsize="64" type="data_ptr" regnum="1"/>
+  </feature>
+</target>
+"#;
+
+/// Wait for a single gdb connection on the given port and serve it until it detaches.
+pub fn gdb(config: Config, port: u16) -> Result<(), EvalError> {
+    let debugger = Debugger::with_program(
+        config.program()?,
+        config.memory_size,
+        stdin(&config, Some("INPUT: ".to_string())),
+        Box::new(StdoutConsole::new()),
+    );
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!(
+        "Waiting for gdb on port {}. Connect with: target remote localhost:{}",
+        port, port
+    );
+    let (stream, address) = listener.accept()?;
+    println!("Connection from {}", address);
+    let mut reader = BufReader::new(stream.try_clone()?);
+    GdbStub::new(debugger, stream).serve(&mut reader)?;
+    Ok(())
+}
+
+/// Connection to the client that can be checked for an interrupt while the target runs.
+pub trait Connection: BufRead {
+    /// Check without blocking whether the client sent an interrupt and consume it.
+    fn interrupted(&mut self) -> Result<bool, io::Error>;
+}
+
+impl Connection for BufReader<TcpStream> {
+    fn interrupted(&mut self) -> Result<bool, io::Error> {
+        self.get_ref().set_nonblocking(true)?;
+        let position = match self.fill_buf() {
+            Ok(buffer) => Ok(buffer.iter().position(|byte| *byte == INTERRUPT)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        };
+        self.get_ref().set_nonblocking(false)?;
+        match position? {
+            Some(position) => {
+                self.consume(position + 1);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+pub struct GdbStub<W: Write> {
+    debugger: Debugger,
+    writer: W,
+    finished: bool,
+}
+
+impl<W: Write> GdbStub<W> {
+    pub fn new(debugger: Debugger, writer: W) -> Self {
+        GdbStub {
+            debugger,
+            writer,
+            finished: false,
+        }
+    }
+
+    /// Handle packets until the client detaches, kills the target or closes the connection.
+    pub fn serve<R: Connection>(&mut self, reader: &mut R) -> Result<(), io::Error> {
+        while let Some(packet) = read_packet(reader)? {
+            self.writer.write_all(b"+")?;
+            match self.handle(&packet, reader)? {
+                Some(reply) => self.send(&reply)?,
+                None => {
+                    self.send("OK")?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compute the reply for a packet. Returns None if the session should end.
+    fn handle<R: Connection>(
+        &mut self,
+        packet: &str,
+        reader: &mut R,
+    ) -> Result<Option<String>, io::Error> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => self.stop_reply(SIGTRAP),
+            Some(b'g') => {
+                let (ip, data_ptr) = self.debugger.registers();
+                format!("{}{}", encode_register(ip), encode_register(data_ptr))
+            }
+            Some(b'G') => self.write_registers(&packet[1..]),
+            Some(b'p') => self.read_register(&packet[1..]),
+            Some(b'P') => self.write_register(&packet[1..]),
+            Some(b'm') => self.read_memory(&packet[1..]),
+            Some(b'M') => self.write_memory(&packet[1..]),
+            Some(b'Z') => self.breakpoint(&packet[1..], true),
+            Some(b'z') => self.breakpoint(&packet[1..], false),
+            Some(b's') => self.resume(Debugger::step),
+            Some(b'c') => self.run(reader)?,
+            Some(b'H') => "OK".to_string(),
+            Some(b'D') | Some(b'k') => return Ok(None),
+            _ if packet == "vCont?" => "vCont;c;s".to_string(),
+            _ if packet.starts_with("vCont;s") => self.resume(Debugger::step),
+            _ if packet.starts_with("vCont;c") => self.run(reader)?,
+            _ if packet.starts_with("qSupported") => {
+                "PacketSize=4000;qXfer:features:read+;swbreak+".to_string()
+            }
+            _ if packet.starts_with("qXfer:features:read:target.xml:") => read_chunk(
+                TARGET_XML,
+                &packet["qXfer:features:read:target.xml:".len()..],
+            ),
+            _ if packet.starts_with("qRegisterInfo") => {
+                self.register_info(&packet["qRegisterInfo".len()..])
+            }
+            _ if packet == "qAttached" => "1".to_string(),
+            _ if packet == "qC" => "QC1".to_string(),
+            _ if packet == "qfThreadInfo" => "m1".to_string(),
+            _ if packet == "qsThreadInfo" => "l".to_string(),
+            // Empty reply tells the client that the packet is not supported
+            _ => String::new(),
+        };
+        Ok(Some(reply))
+    }
+
+    fn stop_reply(&self, signal: u8) -> String {
+        if self.finished {
+            "W00".to_string()
+        } else {
+            format!("S{:02x}", signal)
+        }
+    }
+
+    fn resume(&mut self, action: fn(&mut Debugger) -> Result<bool, EvalError>) -> String {
+        if self.finished {
+            return self.stop_reply(SIGTRAP);
+        }
+        match action(&mut self.debugger) {
+            Ok(finished) => {
+                self.finished = finished;
+                self.stop_reply(SIGTRAP)
+            }
+            Err(_) => self.stop_reply(SIGSEGV),
+        }
+    }
+
+    /// Continue in slices of steps until a breakpoint, the end of the program or an interrupt of
+    /// the client.
+    fn run<R: Connection>(&mut self, reader: &mut R) -> Result<String, io::Error> {
+        if self.finished {
+            return Ok(self.stop_reply(SIGTRAP));
+        }
+        loop {
+            match self.debugger.run_for(RUN_SLICE) {
+                Ok(Some(finished)) => {
+                    self.finished = finished;
+                    return Ok(self.stop_reply(SIGTRAP));
+                }
+                Ok(None) if reader.interrupted()? => return Ok(self.stop_reply(SIGINT)),
+                Ok(None) => {}
+                Err(_) => return Ok(self.stop_reply(SIGSEGV)),
+            }
+        }
+    }
+
+    fn read_register(&self, args: &str) -> String {
+        let (ip, data_ptr) = self.debugger.registers();
+        match usize::from_str_radix(args, 16) {
+            Ok(0) => encode_register(ip),
+            Ok(1) => encode_register(data_ptr),
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let Some((number, value)) = args.split_once('=') else {
+            return "E01".to_string();
+        };
+        let result = match (usize::from_str_radix(number, 16), decode_register(value)) {
+            (Ok(0), Some(value)) => self.debugger.set_ip(value),
+            (Ok(1), Some(value)) => self.debugger.set_data_ptr(value),
+            _ => return "E01".to_string(),
+        };
+        reply_ok(result)
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        if args.len() < 32 {
+            return "E01".to_string();
+        }
+        match (decode_register(&args[..16]), decode_register(&args[16..32])) {
+            (Some(ip), Some(data_ptr)) => {
+                let result = self
+                    .debugger
+                    .set_ip(ip)
+                    .and_then(|_| self.debugger.set_data_ptr(data_ptr));
+                reply_ok(result)
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let Some((address, len)) = parse_address_length(args) else {
+            return "E01".to_string();
+        };
+        let data: Option<String> = (address..address.saturating_add(len))
+            .map(|index| {
+                self.debugger
+                    .memory(index)
+                    .map(|value| format!("{:02x}", value))
+            })
+            .collect();
+        data.unwrap_or("E01".to_string())
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((range, data)) = args.split_once(':') else {
+            return "E01".to_string();
+        };
+        let (Some((address, len)), Some(bytes)) = (parse_address_length(range), decode_hex(data))
+        else {
+            return "E01".to_string();
+        };
+        if bytes.len() != len {
+            return "E01".to_string();
+        }
+        reply_ok(self.debugger.memory_write(address, &bytes))
+    }
+
+    fn breakpoint(&mut self, args: &str, insert: bool) -> String {
+        // Software and hardware breakpoints behave the same. Watchpoints are not supported.
+        let mut parts = args.split(',');
+        let kind = parts.next();
+        let address = parts
+            .next()
+            .and_then(|address| usize::from_str_radix(address, 16).ok());
+        match (kind, address) {
+            (Some("0") | Some("1"), Some(address)) => {
+                if insert {
+                    self.debugger.breakpoints_add(address);
+                } else {
+                    self.debugger.breakpoints_delete(address);
+                }
+                "OK".to_string()
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Register description for lldb, which does not use the target description.
+    fn register_info(&self, args: &str) -> String {
+        match usize::from_str_radix(args, 16) {
+            Ok(number) if number < REGISTER_NAMES.len() => format!(
+                "name:{};bitsize:64;offset:{};encoding:uint;format:hex;set:General Purpose Registers;{}",
+                REGISTER_NAMES[number],
+                number * 8,
+                if number == 0 { "generic:pc;" } else { "" }
+            ),
+            _ => "E45".to_string(),
+        }
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), io::Error> {
+        let checksum = data.bytes().fold(0u8, |acc, value| acc.wrapping_add(value));
+        write!(self.writer, "${}#{:02x}", data, checksum)?;
+        self.writer.flush()
+    }
+}
+
+/// Read the next packet. Acknowledgements and interrupts of a stopped target are skipped. Returns None at the end of
+/// the stream.
+fn read_packet<R: BufRead>(reader: &mut R) -> Result<Option<String>, io::Error> {
+    let mut byte = [0];
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        if byte[0] == b'$' {
+            break;
+        }
+    }
+    let mut data = vec![];
+    reader.read_until(b'#', &mut data)?;
+    data.pop();
+    // We rely on TCP for reliability and do not verify the checksum
+    let mut checksum = [0; 2];
+    reader.read_exact(&mut checksum)?;
+    Ok(Some(String::from_utf8_lossy(&data).to_string()))
+}
+
+/// Serve a slice of a qXfer object. {args} has the form `offset,length`.
+fn read_chunk(data: &str, args: &str) -> String {
+    let Some((offset, length)) = parse_address_length(args) else {
+        return "E01".to_string();
+    };
+    if data.len() <= offset {
+        return "l".to_string();
+    }
+    let end = offset.saturating_add(length).min(data.len());
+    let prefix = if end == data.len() { 'l' } else { 'm' };
+    format!("{}{}", prefix, &data[offset..end])
+}
+
+fn reply_ok(result: Result<(), EvalError>) -> String {
+    match result {
+        Ok(()) => "OK".to_string(),
+        Err(_) => "E01".to_string(),
+    }
+}
+
+fn parse_address_length(args: &str) -> Option<(usize, usize)> {
+    let (address, length) = args.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+/// Registers are transferred as target (little endian) byte order.
+fn encode_register(value: usize) -> String {
+    (value as u64)
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode_register(value: &str) -> Option<usize> {
+    let bytes: [u8; 8] = decode_hex(value)?.try_into().ok()?;
+    usize::try_from(u64::from_le_bytes(bytes)).ok()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::super::io::string::{StdinString, StdoutString};
+    use super::*;
+
+    impl Connection for &[u8] {
+        fn interrupted(&mut self) -> Result<bool, io::Error> {
+            match self.split_first() {
+                Some((&INTERRUPT, rest)) => {
+                    *self = rest;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        }
+    }
+
+    fn serve(code: &str, input: &[u8]) -> Result<String, EvalError> {
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let debugger = Debugger::with_io(code, false, 4, Box::new(stdin), Box::new(stdout))?;
+        let mut output = vec![];
+        GdbStub::new(debugger, &mut output).serve(&mut &input[..])?;
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+
+    #[test]
+    fn test_continue() -> Result<(), EvalError> {
+        assert_eq!(serve("+.", b"$c#63")?, "+$W00#b7");
+        // An interrupt stops a program that never ends
+        assert_eq!(
+            serve("+[]", b"$c#63\x03$g#67")?,
+            "+$S02#b5+$02000000000000000000000000000000#02"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Non-interactive execution of debugger commands.
+//!
+//! A script contains one debugger command per line, exactly as it would be typed into the REPL.
+//! Empty lines and lines starting with `#` are ignored. Combined with the `expect` command this
+//! turns the debugger into a test harness.
+use std::fs;
+use std::io::{self, Read};
+use std::iter;
+use std::path::Path;
+
+use clap::Parser;
+use thiserror::Error;
+
+use super::super::super::commands::common::stdin;
+use super::super::super::commands::config::Config;
+use super::super::super::io::console::StdoutConsole;
+use super::super::super::vm::debugger::Debugger;
+use super::super::super::vm::opcode::OpCode;
+use super::super::super::vm::vm::EvalError;
+use super::{DebuggerCommand, execute};
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("{0}")]
+    EvalError(#[from] EvalError),
+    #[error("Cannot read script")]
+    IOError(#[from] io::Error),
+    #[error("Invalid command in line {0}:\n{1}")]
+    InvalidCommand(usize, String),
+    #[error("{0} command(s) or expectation(s) failed")]
+    CommandsFailed(usize),
+    #[error("Program and script cannot both read stdin, embed the input with -x instead")]
+    StdinConflict,
+}
+
+/// Execute the debugger commands of the script at {path}. A path of `-` reads the script from
+/// stdin, which requires that the program does not read from stdin as well.
+pub fn script(config: Config, path: &Path) -> Result<(), ScriptError> {
+    let program = config.program().map_err(EvalError::from)?;
+    let content = if path == Path::new("-") {
+        if program.code.contains(&OpCode::Input) && config.split_input().1.is_none() {
+            return Err(ScriptError::StdinConflict);
+        }
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        fs::read_to_string(path)?
+    };
+    // No prompt for input so that the output of a script stays clean
+    let mut debugger = Debugger::with_program(
+        program,
+        config.memory_size,
+        stdin(&config, None),
+        Box::new(StdoutConsole::new()),
+    );
+    run_script(&mut debugger, &content)
+}
+
+fn run_script(debugger: &mut Debugger, content: &str) -> Result<(), ScriptError> {
+    let mut failures = 0;
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: String| ScriptError::InvalidCommand(index + 1, message);
+        let words = shlex::split(line).ok_or(invalid("unbalanced quotes".to_string()))?;
+        let command =
+            DebuggerCommand::try_parse_from(iter::once("").chain(words.iter().map(String::as_str)))
+                .map_err(|error| invalid(error.to_string()))?;
+        println!("> {}", line);
+        if let DebuggerCommand::Quit = command {
+            break;
+        }
+        if !execute(debugger, command) {
+            failures += 1;
+        }
+    }
+    if 0 < failures {
+        return Err(ScriptError::CommandsFailed(failures));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::super::io::string::{StdinString, StdoutString};
+    use super::*;
+
+    fn debugger(code: &str) -> Debugger {
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        Debugger::with_io(code, true, 16, Box::new(stdin), Box::new(stdout)).unwrap()
+    }
+
+    #[test]
+    fn test_script() {
+        let hw = include_str!("../../../../examples/helloworld.bf");
+        let content = r#"
+            # Run to the first output instruction
+            breakpoint create 0x1f
+            continue
+            expect mem 2 == 72
+            expect mem 2 == 'H'
+            expect dp < 3
+            set mem 2 "J"
+            continue
+            expect output --contains "World!\n"
+            expect output "Jello World!\n"
+            expect ip == 58
+        "#;
+        assert!(run_script(&mut debugger(hw), content).is_ok());
+    }
+
+    #[test]
+    fn test_script_failures() {
+        let content = "step\nexpect mem 0 == 2\nexpect output \"a\"\nexpect ip == 1";
+        assert!(matches!(
+            run_script(&mut debugger("++"), content),
+            Err(ScriptError::CommandsFailed(2))
+        ));
+        // Runtime errors and rejected commands fail the script as well
+        assert!(matches!(
+            run_script(&mut debugger("+<"), "continue\nexpect dp == 0"),
+            Err(ScriptError::CommandsFailed(1))
+        ));
+        assert!(matches!(
+            run_script(&mut debugger("++"), "set dp 16\nfill 8 9 0\nset ip 0"),
+            Err(ScriptError::CommandsFailed(2))
+        ));
+        assert!(matches!(
+            run_script(&mut debugger("++"), "step\nfrobnicate"),
+            Err(ScriptError::InvalidCommand(2, _))
+        ));
+    }
+}
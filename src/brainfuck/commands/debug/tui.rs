@@ -0,0 +1,395 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Full screen terminal UI for the debugger.
+use std::io;
+use std::time::Duration;
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph, Wrap},
+};
+
+use super::super::super::commands::config::Config;
+use super::super::super::io::base::{InputHistory, Stdin};
+use super::super::super::io::string::{StdinString, StdoutString};
+use super::super::super::vm::debugger::Debugger;
+use super::super::super::vm::vm::EvalError;
+
+// Number of steps a continue runs before checking for a key that interrupts it
+const RUN_SLICE: usize = 1 << 16;
+
+const HELP: &str =
+    "s: step  c: continue  r: reverse step  b: toggle breakpoint  ↑/↓: move cursor  q: quit";
+
+/// Reads program input directly from the keyboard while the terminal is in raw mode.
+#[derive(Debug, Default)]
+struct KeyboardStdin {
+    history: InputHistory,
+}
+
+impl Stdin for KeyboardStdin {
+    fn read(&mut self) -> Result<Option<char>, io::Error> {
+        if let Some(value) = self.history.replay() {
+            return Ok(Some(value));
+        }
+        loop {
+            if let Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) = event::read()?
+            {
+                let value = match code {
+                    // Same convention as the console input
+                    KeyCode::Enter => 0x0 as char,
+                    KeyCode::Char(c) => c,
+                    _ => continue,
+                };
+                self.history.record(value);
+                return Ok(Some(value));
+            }
+        }
+    }
+
+    fn consumed(&self) -> String {
+        self.history.consumed()
+    }
+
+    fn consumed_len(&self) -> usize {
+        self.history.consumed_len()
+    }
+
+    fn rewind(&mut self, consumed: &str) {
+        self.history.rewind(consumed)
+    }
+}
+
+pub fn tui(config: Config) -> Result<(), EvalError> {
+    let stdin: Box<dyn Stdin> = match config.split_input() {
+        (_, Some(input)) => Box::new(StdinString::new(input)),
+        (_, None) => Box::new(KeyboardStdin::default()),
+    };
+    let debugger = Debugger::with_program(
+        config.program()?,
+        config.memory_size,
+        stdin,
+        Box::new(StdoutString::new()),
+    );
+    let mut terminal = ratatui::try_init()?;
+    let result = Tui::new(debugger).run(&mut terminal);
+    ratatui::restore();
+    Ok(result?)
+}
+
+struct Tui {
+    debugger: Debugger,
+    // Instruction selected in the listing. Breakpoints are toggled at the cursor.
+    cursor: usize,
+    status: String,
+    // A continue is in progress and runs in slices between the key checks
+    running: bool,
+    quit: bool,
+}
+
+impl Tui {
+    fn new(debugger: Debugger) -> Self {
+        Tui {
+            debugger,
+            cursor: 0,
+            status: "Program input is read from the keyboard when ',' executes".to_string(),
+            running: false,
+            quit: false,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), io::Error> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if self.running {
+                self.continue_slice();
+                if !event::poll(Duration::ZERO)? {
+                    continue;
+                }
+            }
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key.code);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        if self.running {
+            // Any key interrupts a running program
+            self.running = false;
+            self.follow_ip("Interrupted");
+            return;
+        }
+        let last = self.debugger.program().code.len().saturating_sub(1);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('s') => self.execute(Debugger::step),
+            KeyCode::Char('c') => {
+                self.running = true;
+                self.status = "Running, press any key to interrupt".to_string();
+            }
+            KeyCode::Char('r') => match self.debugger.step_back() {
+                Ok(true) => self.follow_ip("Reversed one step"),
+                Ok(false) => self.status = "No history left".to_string(),
+                Err(error) => self.status = error.to_string(),
+            },
+            KeyCode::Char('b') => {
+                if self.debugger.breakpoints_list().contains(&self.cursor) {
+                    self.debugger.breakpoints_delete(self.cursor);
+                } else {
+                    self.debugger.breakpoints_add(self.cursor);
+                }
+            }
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(16),
+            KeyCode::PageDown => self.cursor = (self.cursor + 16).min(last),
+            _ => {}
+        }
+    }
+
+    fn execute(&mut self, action: fn(&mut Debugger) -> Result<bool, EvalError>) {
+        let result = action(&mut self.debugger);
+        self.stopped(result);
+    }
+
+    /// Run the next slice of a continue.
+    fn continue_slice(&mut self) {
+        let result = match self.debugger.run_for(RUN_SLICE) {
+            Ok(None) => return,
+            Ok(Some(finished)) => Ok(finished),
+            Err(error) => Err(error),
+        };
+        self.running = false;
+        self.stopped(result);
+    }
+
+    /// Show why the execution stopped.
+    fn stopped(&mut self, result: Result<bool, EvalError>) {
+        match result {
+            Ok(true) => self.follow_ip("Finished execution"),
+            Ok(false) => {
+                let (ip, _) = self.debugger.registers();
+                if self.debugger.breakpoints_list().contains(&ip) {
+                    self.follow_ip(&format!("Hit breakpoint {}", ip));
+                } else {
+                    self.follow_ip("");
+                }
+            }
+            Err(error) => self.follow_ip(&error.to_string()),
+        }
+    }
+
+    fn follow_ip(&mut self, status: &str) {
+        (self.cursor, _) = self.debugger.registers();
+        self.status = status.to_string();
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(2)]).areas(frame.area());
+        let [top, bottom] =
+            Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(main);
+        let [listing, tape] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(top);
+        let [output, breakpoints] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .areas(bottom);
+
+        self.draw_listing(frame, listing);
+        self.draw_tape(frame, tape);
+        self.draw_output(frame, output);
+        self.draw_breakpoints(frame, breakpoints);
+
+        let (ip, data_ptr) = self.debugger.registers();
+        let footer_text = vec![
+            Line::from(format!("ip={} dp={}  {}", ip, data_ptr, self.status)),
+            Line::from(HELP).style(Style::default().fg(Color::DarkGray)),
+        ];
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn draw_listing(&self, frame: &mut Frame, area: Rect) {
+        let (ip, _) = self.debugger.registers();
+        let breakpoints = self.debugger.breakpoints_list();
+        let height = area.height.saturating_sub(2) as usize;
+        let start = self.cursor.saturating_sub(height / 2);
+        let end = start + height.saturating_sub(1);
+        let listing = self
+            .debugger
+            .program()
+            .listing(Some(start), Some(end))
+            .unwrap_or_default();
+        let lines: Vec<Line> = listing
+            .lines()
+            .zip(start..)
+            .map(|(text, index)| {
+                let marker = match (index == ip, breakpoints.contains(&index)) {
+                    (true, true) => "●▶",
+                    (true, false) => " ▶",
+                    (false, true) => "● ",
+                    (false, false) => "  ",
+                };
+                let mut style = Style::default();
+                if breakpoints.contains(&index) {
+                    style = style.fg(Color::Red);
+                }
+                if index == ip {
+                    style = style.bg(Color::Blue).fg(Color::White);
+                }
+                if index == self.cursor {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Line::styled(format!("{}{}", marker, text), style)
+            })
+            .collect();
+        let block = Block::bordered().title(" Program ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_tape(&self, frame: &mut Frame, area: Rect) {
+        let (_, data_ptr) = self.debugger.registers();
+        let height = area.height.saturating_sub(2) as usize;
+        let start = data_ptr.saturating_sub(height / 2);
+        let lines: Vec<Line> = (start..start + height)
+            .filter_map(|address| {
+                let value = self.debugger.memory(address)?;
+                let c = value as char;
+                let printable = if c.is_ascii_graphic() { c } else { '.' };
+                let text = format!(
+                    " {:04x} │ {:3} 0x{:02x} {}",
+                    address, value, value, printable
+                );
+                let style = if address == data_ptr {
+                    Style::default().bg(Color::Yellow).fg(Color::Black)
+                } else if value != 0 {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                Some(Line::styled(text, style))
+            })
+            .collect();
+        let block = Block::bordered().title(" Tape ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_output(&self, frame: &mut Frame, area: Rect) {
+        let output = self.debugger.output();
+        let height = area.height.saturating_sub(2) as usize;
+        // Keep the end of the output visible
+        let scroll = output.lines().count().saturating_sub(height) as u16;
+        let block = Block::bordered().title(" Output ");
+        let paragraph = Paragraph::new(output)
+            .block(block)
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn draw_breakpoints(&self, frame: &mut Frame, area: Rect) {
+        let program = self.debugger.program();
+        let lines: Vec<Line> = self
+            .debugger
+            .breakpoints_list()
+            .iter()
+            .map(|index| match program.location(*index) {
+                Some(location) => Line::from(format!(
+                    " {:04} (line {}, column {})",
+                    index, location.line, location.column
+                )),
+                None => Line::from(format!(" {:04}", index)),
+            })
+            .collect();
+        let block = Block::bordered().title(" Breakpoints ");
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tui(code: &str) -> Tui {
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let debugger = Debugger::with_io(code, false, 4, Box::new(stdin), Box::new(stdout));
+        Tui::new(debugger.unwrap())
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut tui = tui("+>+.<-");
+        tui.handle_key(KeyCode::Char('s'));
+        tui.handle_key(KeyCode::Char('s'));
+        assert_eq!(tui.debugger.registers(), (2, 1));
+        assert_eq!(tui.cursor, 2);
+        tui.handle_key(KeyCode::Char('r'));
+        assert_eq!(tui.debugger.registers(), (1, 0));
+
+        tui.handle_key(KeyCode::Down);
+        tui.handle_key(KeyCode::Down);
+        tui.handle_key(KeyCode::Char('b'));
+        assert_eq!(tui.debugger.breakpoints_list(), vec![3]);
+        tui.handle_key(KeyCode::Char('c'));
+        assert!(tui.running);
+        tui.continue_slice();
+        assert!(!tui.running);
+        assert_eq!(tui.status, "Hit breakpoint 3");
+        tui.handle_key(KeyCode::Char('b'));
+        assert!(tui.debugger.breakpoints_list().is_empty());
+
+        tui.handle_key(KeyCode::Char('q'));
+        assert!(tui.quit);
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut tui = tui("+[]");
+        tui.handle_key(KeyCode::Char('c'));
+        tui.continue_slice();
+        assert!(tui.running);
+        tui.handle_key(KeyCode::Char('x'));
+        assert!(!tui.running);
+        assert!(!tui.quit);
+        assert_eq!(tui.status, "Interrupted");
+    }
+}
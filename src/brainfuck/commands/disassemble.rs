@@ -28,7 +28,6 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
 use super::super::commands::config::Config;
-use super::super::vm::program::Program;
 use super::super::vm::vm::EvalError;
 
 pub fn disassemble(
@@ -36,5 +35,5 @@ pub fn disassemble(
     start: Option<usize>,
     end: Option<usize>,
 ) -> Result<String, EvalError> {
-    Program::new(&config.code, config.rle)?.listing(start, end)
+    config.program()?.listing(start, end)
 }
@@ -0,0 +1,375 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Source formatter for brainfuck programs.
+use thiserror::Error;
+
+use super::super::commands::config::Config;
+use super::super::vm::opcode::OpCode;
+use super::super::vm::program::{Program, ProgramError};
+
+#[derive(Debug, Error)]
+pub enum FmtError {
+    #[error("Invalid program")]
+    ProgramError(#[from] ProgramError),
+    #[error("The formatted program differs from the original program")]
+    Changed,
+}
+
+/// Layout of the formatted program.
+#[derive(Debug, Clone)]
+pub struct FmtOptions {
+    /// Spaces per loop level
+    pub indent: usize,
+    /// Length of the lines. Comments and long runs may exceed it.
+    pub width: usize,
+    /// Separate runs of the same instruction with spaces and never split them
+    pub group: bool,
+    /// Keep the comments and blank lines
+    pub comments: bool,
+}
+
+impl Default for FmtOptions {
+    fn default() -> Self {
+        FmtOptions {
+            indent: 4,
+            width: 80,
+            group: false,
+            comments: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    /// Repeated instruction
+    Run(char, usize),
+    Loop(Vec<Node>),
+    /// A comment at the end of a line with code is trailing
+    Comment {
+        text: String,
+        trailing: bool,
+    },
+    /// Empty line between paragraphs
+    Blank,
+}
+
+/// Add the comments of the text between two instructions to the {nodes}.
+fn add_comments(text: &str, after_code: bool, nodes: &mut Vec<Node>) {
+    let lines = text.split('\n').collect::<Vec<_>>();
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if !line.is_empty() {
+            nodes.push(Node::Comment {
+                text: line.to_string(),
+                trailing: index == 0 && after_code,
+            });
+        } else if 0 < index && index < lines.len() - 1 {
+            nodes.push(Node::Blank);
+        }
+    }
+}
+
+/// Split the {source} of a valid program into nodes. {convert} decides which characters are
+/// instructions.
+fn parse(source: &str, comments: bool, convert: fn(char) -> Option<OpCode>) -> Vec<Node> {
+    let mut stack: Vec<Vec<Node>> = vec![vec![]];
+    let mut comment = String::new();
+    let mut after_code = false;
+    for character in source.chars() {
+        if convert(character).is_none() {
+            comment.push(character);
+            continue;
+        }
+        let nodes = stack.last_mut().unwrap();
+        if comments {
+            add_comments(&comment, after_code, nodes);
+        }
+        comment.clear();
+        after_code = true;
+        match (character, nodes.last_mut()) {
+            ('[', _) => stack.push(vec![]),
+            (']', _) => {
+                let body = stack.pop().unwrap();
+                stack.last_mut().unwrap().push(Node::Loop(body));
+            }
+            (_, Some(Node::Run(previous, count))) if *previous == character => *count += 1,
+            _ => nodes.push(Node::Run(character, 1)),
+        }
+    }
+    let mut nodes = stack.pop().unwrap();
+    if comments {
+        add_comments(&comment, after_code, &mut nodes);
+    }
+    nodes
+}
+
+struct Writer<'a> {
+    options: &'a FmtOptions,
+    lines: Vec<String>,
+    /// Current line without indentation
+    line: String,
+    level: usize,
+}
+
+impl Writer<'_> {
+    fn indentation(&self) -> String {
+        " ".repeat(self.level * self.options.indent)
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            let line = format!("{}{}", self.indentation(), self.line);
+            self.lines.push(line);
+            self.line.clear();
+        }
+    }
+
+    /// Append the {text} to the current line or start a new line if it does not fit.
+    fn word(&mut self, text: &str) {
+        if !self.line.is_empty() {
+            let separator = if self.options.group { 1 } else { 0 };
+            let length = self.indentation().len() + self.line.len() + separator + text.len();
+            if self.options.width < length {
+                self.flush();
+            } else if self.options.group {
+                self.line.push(' ');
+            }
+        }
+        self.line.push_str(text);
+    }
+
+    /// The loop on a single line if it has no nested loops or comments and fits into a line.
+    fn inline_loop(&self, body: &[Node]) -> Option<String> {
+        let runs = body
+            .iter()
+            .map(|node| match node {
+                Node::Run(character, count) => Some(character.to_string().repeat(*count)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let text = format!("[{}]", runs.join(if self.options.group { " " } else { "" }));
+        (self.indentation().len() + text.len() <= self.options.width).then_some(text)
+    }
+
+    fn write(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Run(character, count) if self.options.group => {
+                    self.word(&character.to_string().repeat(*count))
+                }
+                Node::Run(character, count) => {
+                    for _ in 0..*count {
+                        self.word(&character.to_string());
+                    }
+                }
+                Node::Loop(body) => match self.inline_loop(body) {
+                    Some(text) => self.word(&text),
+                    None => {
+                        self.flush();
+                        self.lines.push(format!("{}[", self.indentation()));
+                        self.level += 1;
+                        self.write(body);
+                        self.flush();
+                        self.level -= 1;
+                        self.lines.push(format!("{}]", self.indentation()));
+                    }
+                },
+                Node::Comment { text, trailing } => {
+                    if !self.line.is_empty() && *trailing {
+                        self.line.push(' ');
+                        self.line.push_str(text);
+                        self.flush();
+                    } else if let Some(last) = self.lines.last_mut()
+                        && *trailing
+                        && !last.is_empty()
+                    {
+                        last.push(' ');
+                        last.push_str(text);
+                    } else {
+                        self.flush();
+                        self.lines.push(format!("{}{}", self.indentation(), text));
+                    }
+                }
+                Node::Blank => {
+                    self.flush();
+                    if self.lines.last().is_some_and(|last| !last.is_empty()) {
+                        self.lines.push(String::new());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Format the {source} of a program.
+pub fn format_source(source: &str, options: &FmtOptions) -> Result<String, FmtError> {
+    format_code(source, options, false)
+}
+
+/// Format the {source} of a program. The extended syntax keeps `#` as an instruction, the input
+/// after the first `!` has to be removed before.
+fn format_code(source: &str, options: &FmtOptions, extended: bool) -> Result<String, FmtError> {
+    let parse_program = |code: &str| {
+        if extended {
+            Program::new_extended(code, false)
+        } else {
+            Program::new(code, false)
+        }
+    };
+    let convert = if extended {
+        OpCode::convert_extended
+    } else {
+        OpCode::convert
+    };
+    let original = parse_program(source)?;
+    let mut writer = Writer {
+        options,
+        lines: vec![],
+        line: String::new(),
+        level: 0,
+    };
+    writer.write(&parse(source, options.comments, convert));
+    writer.flush();
+    while writer.lines.last().is_some_and(|last| last.is_empty()) {
+        writer.lines.pop();
+    }
+    let formatted = writer
+        .lines
+        .iter()
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect::<String>();
+
+    // The formatting must not change a single instruction
+    if parse_program(&formatted)?.to_string() != original.to_string() {
+        return Err(FmtError::Changed);
+    }
+    Ok(formatted)
+}
+
+/// Format the program of the {config}. The input embedded after the `!` of the extended syntax
+/// is kept unchanged.
+pub fn fmt(config: Config, options: &FmtOptions) -> Result<String, FmtError> {
+    let (code, input) = config.split_input();
+    let mut formatted = format_code(code, options, config.extended)?;
+    if let Some(input) = input {
+        formatted.push('!');
+        formatted.push_str(input);
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let source = "Clear  the cell\n[-]+++++ +++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>. print H\n\n\nEnd";
+        assert_eq!(
+            format_source(source, &FmtOptions::default()).unwrap(),
+            "Clear  the cell\n\
+             [-]++++++++\n\
+             [\n    \
+                 >++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-\n\
+             ]\n\
+             >>. print H\n\
+             \n\
+             End\n"
+        );
+
+        let options = FmtOptions {
+            indent: 2,
+            width: 14,
+            group: true,
+            comments: false,
+        };
+        assert_eq!(
+            format_source(source, &options).unwrap(),
+            "[-] ++++++++\n\
+             [\n  \
+               > ++++\n  \
+               [\n    \
+                 > ++ > +++\n    \
+                 > +++ > +\n    \
+                 <<<< -\n  \
+               ]\n  \
+               > + > + > -\n  \
+               >> + [<] < -\n\
+             ]\n\
+             >> .\n"
+        );
+    }
+
+    #[test]
+    fn test_extended_syntax() {
+        let options = FmtOptions {
+            comments: false,
+            ..Default::default()
+        };
+        let config =
+            Config::new("+++#.!ab+ c".to_string(), 16, false, None).with_extended_syntax(true);
+        assert_eq!(fmt(config, &options).unwrap(), "+++#.\n!ab+ c");
+
+        let config = Config::new("[#\n-]!".to_string(), 16, false, None).with_extended_syntax(true);
+        assert_eq!(fmt(config, &FmtOptions::default()).unwrap(), "[#-]\n!");
+    }
+
+    #[test]
+    fn test_examples() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "bf") {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            for options in [
+                FmtOptions::default(),
+                FmtOptions {
+                    group: true,
+                    comments: false,
+                    ..Default::default()
+                },
+            ] {
+                let formatted = format_source(&source, &options).unwrap();
+                // Formatting is idempotent
+                assert_eq!(
+                    format_source(&formatted, &options).unwrap(),
+                    formatted,
+                    "{:?}",
+                    path
+                );
+            }
+        }
+    }
+}
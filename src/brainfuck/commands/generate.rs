@@ -0,0 +1,340 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Generator of brainfuck programs that print a text.
+//!
+//! The program starts with one multiplication loop that sets up cells close to the characters of
+//! the text, e.g., `++++++++[>+++++++++>++++++++++++<<-]` for cells with 72 and 96. Every
+//! character is then printed from the cell that needs the fewest moves and updates. The values of
+//! the cells are the weighted medians of the characters grouped into one range per cell.
+use thiserror::Error;
+
+use super::super::io::string::{StdinString, StdoutString};
+use super::super::vm::vm::{EvalError, Normal, VM};
+
+// Largest number of iterations of the multiplication loop that is tried
+const MAX_ITERATIONS: usize = 24;
+// Number of loops with the best estimates that print the whole text
+const CANDIDATES: usize = 4;
+
+#[derive(Debug, Error)]
+pub enum GenerateError {
+    #[error("Character {0:?} cannot be printed, only characters up to U+00FF are supported")]
+    UnsupportedCharacter(char),
+    #[error("{0}")]
+    EvalError(#[from] EvalError),
+    #[error("The generated program prints {0:?} instead")]
+    Mismatch(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GenerateOptions {
+    /// Number of cells the program may use
+    pub cells: usize,
+    /// Whether cells wrap around, so that e.g. 255 is one `-` away from 0
+    pub wrapping: bool,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            cells: 8,
+            wrapping: true,
+        }
+    }
+}
+
+/// Generate the shortest program found that prints the {text} and check it with the interpreter.
+pub fn generate(text: &str, options: &GenerateOptions) -> Result<String, GenerateError> {
+    let values = text
+        .chars()
+        .map(|value| u8::try_from(value).map_err(|_| GenerateError::UnsupportedCharacter(value)))
+        .collect::<Result<Vec<u8>, _>>()?;
+    let generator = Generator::new(&values, options.wrapping);
+
+    // A single cell needs no loop, the loop counter takes one of the other cells
+    let mut best = generator.print(&mut [0], 0, String::new());
+    for bases in generator.bases(options.cells.saturating_sub(1)) {
+        // Printing the whole text for every loop is too slow for long texts
+        let mut iterations = (1..=MAX_ITERATIONS).collect::<Vec<_>>();
+        iterations.sort_by_cached_key(|iterations| generator.estimate(&bases, *iterations));
+        for iterations in iterations.into_iter().take(CANDIDATES) {
+            let candidate = generator.with_loop(&bases, iterations);
+            if candidate.len() < best.len() {
+                best = candidate;
+            }
+        }
+    }
+
+    let stdin = StdinString::new("");
+    let stdout = StdoutString::new();
+    let mut vm = VM::<Normal>::new(
+        &best,
+        Box::new(stdin),
+        Box::new(stdout),
+        false,
+        options.cells,
+    )?;
+    vm.run()?;
+    let output = vm.stdout().to_string();
+    if output != text {
+        return Err(GenerateError::Mismatch(output));
+    }
+    Ok(best)
+}
+
+struct Generator<'a> {
+    values: &'a [u8],
+    wrapping: bool,
+    /// Distinct characters in ascending order with their number of occurrences
+    distinct: Vec<(u8, usize)>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(values: &'a [u8], wrapping: bool) -> Self {
+        let mut weights = [0usize; 256];
+        for value in values {
+            weights[*value as usize] += 1;
+        }
+        let distinct = (0..=255u8)
+            .filter(|value| 0 < weights[*value as usize])
+            .map(|value| (value, weights[value as usize]))
+            .collect();
+        Generator {
+            values,
+            wrapping,
+            distinct,
+        }
+    }
+
+    /// Number of instructions that change {from} into {to}.
+    fn update_length(&self, from: u8, to: u8) -> usize {
+        if self.wrapping {
+            to.wrapping_sub(from).min(from.wrapping_sub(to)) as usize
+        } else {
+            from.abs_diff(to) as usize
+        }
+    }
+
+    /// Instructions that change {from} into {to}.
+    fn update(&self, from: u8, to: u8) -> String {
+        let up = to.wrapping_sub(from) as usize;
+        let down = from.wrapping_sub(to) as usize;
+        let increment = if self.wrapping {
+            up <= down
+        } else {
+            from <= to
+        };
+        if increment {
+            "+".repeat(up)
+        } else {
+            "-".repeat(down)
+        }
+    }
+
+    /// Instructions that move the data pointer from {from} to {to}.
+    fn moves(from: usize, to: usize) -> String {
+        if from < to {
+            ">".repeat(to - from)
+        } else {
+            "<".repeat(from - to)
+        }
+    }
+
+    /// Append the instructions that print the text from the {cells} to the {code}. Every
+    /// character uses the cell with the fewest instructions.
+    fn print(&self, cells: &mut [u8], mut data_ptr: usize, mut code: String) -> String {
+        for value in self.values {
+            let cell = (0..cells.len())
+                .min_by_key(|cell| {
+                    data_ptr.abs_diff(*cell) + self.update_length(cells[*cell], *value)
+                })
+                .unwrap();
+            code.push_str(&Generator::moves(data_ptr, cell));
+            code.push_str(&self.update(cells[cell], *value));
+            code.push('.');
+            cells[cell] = *value;
+            data_ptr = cell;
+        }
+        code
+    }
+
+    /// Increments per iteration that bring the cells closest to the {bases}.
+    fn factors(bases: &[u8], iterations: usize) -> Vec<usize> {
+        bases
+            .iter()
+            .map(|base| ((*base as usize + iterations / 2) / iterations).min(255 / iterations))
+            .collect()
+    }
+
+    /// Length of the loop with {iterations} for the {bases} and of the updates of the characters
+    /// from the closest cell after the loop, ignoring moves and the changed cells.
+    fn estimate(&self, bases: &[u8], iterations: usize) -> usize {
+        let factors = Generator::factors(bases, iterations);
+        let setup = iterations + factors.iter().sum::<usize>() + 2 * factors.len() + 3;
+        let updates = self
+            .distinct
+            .iter()
+            .map(|(value, weight)| {
+                let update = factors
+                    .iter()
+                    .map(|factor| self.update_length((factor * iterations) as u8, *value))
+                    .min()
+                    .unwrap_or_default();
+                update * weight
+            })
+            .sum::<usize>();
+        setup + updates
+    }
+
+    /// Set up the {bases} behind the counter cell with a loop of {iterations} and print the text.
+    fn with_loop(&self, bases: &[u8], iterations: usize) -> String {
+        let factors = Generator::factors(bases, iterations);
+        let mut code = "+".repeat(iterations) + "[";
+        for factor in &factors {
+            code.push('>');
+            code.push_str(&"+".repeat(*factor));
+        }
+        code.push_str(&"<".repeat(factors.len()));
+        code.push_str("-]");
+        // The counter is zero after the loop and can be used as well
+        let mut cells = vec![0];
+        cells.extend(factors.iter().map(|factor| (factor * iterations) as u8));
+        self.print(&mut cells, 0, code)
+    }
+
+    /// Split the distinct characters into ranges with the smallest total distance to their
+    /// weighted medians and return the medians for every number of ranges up to {max_count}.
+    fn bases(&self, max_count: usize) -> Vec<Vec<u8>> {
+        let distinct = &self.distinct;
+        let size = distinct.len();
+        let max_count = max_count.min(size);
+
+        // Weighted median and distance of every range [start, end)
+        let mut ranges = vec![vec![(0, 0); size + 1]; size];
+        for (start, row) in ranges.iter_mut().enumerate() {
+            for end in start + 1..=size {
+                let group = &distinct[start..end];
+                let total = group.iter().map(|(_, weight)| weight).sum::<usize>();
+                let mut seen = 0;
+                let median = group
+                    .iter()
+                    .find(|(_, weight)| {
+                        seen += weight;
+                        total <= 2 * seen
+                    })
+                    .unwrap()
+                    .0;
+                let distance = group
+                    .iter()
+                    .map(|(value, weight)| value.abs_diff(median) as usize * weight)
+                    .sum::<usize>();
+                row[end] = (median, distance);
+            }
+        }
+
+        // costs[groups][end]: smallest distance of the first {end} values in {groups} ranges
+        let mut costs = vec![vec![usize::MAX; size + 1]; max_count + 1];
+        let mut splits = vec![vec![0; size + 1]; max_count + 1];
+        costs[0][0] = 0;
+        for groups in 1..=max_count {
+            for end in groups..=size {
+                for start in groups - 1..end {
+                    if costs[groups - 1][start] == usize::MAX {
+                        continue;
+                    }
+                    let cost = costs[groups - 1][start] + ranges[start][end].1;
+                    if cost < costs[groups][end] {
+                        costs[groups][end] = cost;
+                        splits[groups][end] = start;
+                    }
+                }
+            }
+        }
+        (1..=max_count)
+            .map(|count| {
+                let mut bases = vec![];
+                let mut end = size;
+                for groups in (1..=count).rev() {
+                    let start = splits[groups][end];
+                    bases.push(ranges[start][end].0);
+                    end = start;
+                }
+                bases.reverse();
+                bases
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let text = "Hello World!\n";
+        let code = generate(text, &GenerateOptions::default()).unwrap();
+        assert!(code.contains('['));
+        assert!(code.len() < 120, "{}", code);
+
+        // A single cell cannot hold a loop counter
+        let options = GenerateOptions {
+            cells: 1,
+            ..Default::default()
+        };
+        let code = generate(text, &options).unwrap();
+        assert!(!code.contains('>'));
+
+        let options = GenerateOptions {
+            cells: 1,
+            wrapping: false,
+        };
+        assert_eq!(generate("\u{ff}", &options).unwrap(), "+".repeat(255) + ".");
+        assert_eq!(
+            generate("\u{ff}", &GenerateOptions::default()).unwrap(),
+            "-."
+        );
+
+        assert!(matches!(
+            generate("€", &options),
+            Err(GenerateError::UnsupportedCharacter('€'))
+        ));
+    }
+
+    #[test]
+    fn test_bases() {
+        let generator = Generator::new(b"aab zzy", true);
+        let bases = generator.bases(3);
+        assert_eq!(bases[0], vec![b'b']);
+        assert_eq!(bases[2], vec![b' ', b'a', b'z']);
+        // There are no more ranges than distinct characters
+        assert_eq!(generator.bases(8).len(), 5);
+    }
+}
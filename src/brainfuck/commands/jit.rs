@@ -27,35 +27,88 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
 
 use super::super::{
-    jit::jit::JIT,
-    commands::{common::dump_memory, config::Config},
+    commands::config::Config,
+    jit::jit::{JIT, LoopCounter},
+    jit::perf::PerfOptions,
+    vm::profiler::{HOT_LOOPS, LoopRow, LoopWeight, loop_report},
     vm::vm::EvalError,
 };
 
-pub fn jit_disassemble(config: Config) -> Result<(), EvalError> {
-    let jit = JIT::new(&config.code, config.rle, config.memory_size)?;
+pub fn jit_disassemble(config: Config, opt_level: u8) -> Result<(), EvalError> {
+    let jit = JIT::with_program(config.program()?, config.memory_size, opt_level)?;
     let root = jit.disassemble();
     print!("{}", root);
     Ok(())
 }
 
-pub fn jit_dump(config: Config, output: PathBuf) -> Result<(), EvalError> {
-    let jit = JIT::new(&config.code, config.rle, config.memory_size)?;
+pub fn jit_dump(config: Config, opt_level: u8, output: PathBuf) -> Result<(), EvalError> {
+    let jit = JIT::with_program(config.program()?, config.memory_size, opt_level)?;
     let code = jit.generate_code();
     let mut output_file = File::create(&output)?;
     output_file.write_all(&code)?;
     println!("Wrote {} bytes to {:?}", code.len(), output);
     println!("\nThe assembly code is for a function of the following signature:");
-    println!("\nvoid run(\n\tvoid (*output)(char),\n\tchar (*input)(),\n\tchar *memory\n);");
+    println!(
+        "\nchar *run(\n\tvoid (*output)(char),\n\tchar (*input)(char),\n\tchar *memory,\n\tuint64_t *counters,\n\tsize_t memory_size\n);"
+    );
     Ok(())
 }
 
-pub fn jit_run(config: Config) -> Result<(), EvalError> {
-    let jit = JIT::new(&config.code, config.rle, config.memory_size)?;
-    let memory = jit.run();
-    dump_memory(config, &memory)?;
+fn dump_memory(config: Config, memory: &[u8]) -> Result<(), io::Error> {
+    if let Some(mem_dump) = config.memory_dump {
+        let mut file = File::create(mem_dump)?;
+        file.write_all(memory)?;
+    }
     Ok(())
 }
+
+pub fn jit_run(
+    config: Config,
+    opt_level: u8,
+    profile: bool,
+    perf: PerfOptions,
+) -> Result<(), EvalError> {
+    let mut jit = JIT::with_program(config.program()?, config.memory_size, opt_level)?;
+    let (_, input) = config.split_input();
+    jit.enable_perf(perf);
+    let execution = if profile {
+        jit.run_profiled(input)?
+    } else {
+        jit.run(input)?
+    };
+    // The output is only captured for embedded input
+    print!("{}", execution.output);
+    if profile {
+        eprint!("{}", counter_report(&execution.counters));
+    }
+    dump_memory(config, &execution.memory)?;
+    match execution.fault {
+        Some(fault) => Err(EvalError::DataPointerOutOfBounds(fault)),
+        None => Ok(()),
+    }
+}
+
+/// Generate a report of the loops with the most iterations.
+fn counter_report(counters: &[LoopCounter]) -> String {
+    let total = counters
+        .iter()
+        .map(|counter| counter.iterations)
+        .sum::<u64>();
+    let rows = counters
+        .iter()
+        .map(|counter| LoopRow {
+            location: Some(counter.location),
+            entries: counter.entries,
+            iterations: counter.iterations,
+            details: None,
+        })
+        .collect();
+    loop_report(rows, LoopWeight::Iterations(total), HOT_LOOPS)
+}
@@ -0,0 +1,397 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Static checks for common mistakes in brainfuck programs.
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use clap::ValueEnum;
+use serde_json::json;
+
+use super::super::commands::config::Config;
+use super::super::vm::opcode::OpCode;
+use super::super::vm::program::{Program, ProgramError, SourceLocation};
+
+/// Output format of the findings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum LintFormat {
+    /// One `file:line:column: kind: message` line per finding.
+    #[default]
+    Text,
+    /// JSON array of the findings.
+    Json,
+}
+
+/// Kind of mistake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintKind {
+    /// `+-`, `-+`, `<>` or `><`
+    CancellingPair,
+    /// A loop that is reached with a zero cell
+    DeadLoop,
+    /// `[]` never terminates for a non-zero cell
+    EmptyLoop,
+    /// Code after a loop that never terminates
+    UnreachableCode,
+    /// The data pointer moves in front of the first cell
+    PointerUnderflow,
+    /// An instruction that is probably part of a comment
+    SuspiciousComment,
+}
+
+impl Display for LintKind {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(match self {
+            LintKind::CancellingPair => "cancelling-pair",
+            LintKind::DeadLoop => "dead-loop",
+            LintKind::EmptyLoop => "empty-loop",
+            LintKind::UnreachableCode => "unreachable-code",
+            LintKind::PointerUnderflow => "pointer-underflow",
+            LintKind::SuspiciousComment => "suspicious-comment",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub kind: LintKind,
+    pub location: SourceLocation,
+    pub message: String,
+}
+
+/// What is known about the program state at an instruction.
+#[derive(Debug, Clone)]
+struct State {
+    /// Offset of the data pointer from the first cell. Without it nothing is known about the cells.
+    data_ptr: Option<isize>,
+    /// Values of the changed cells by offset (None if unknown)
+    cells: HashMap<isize, Option<u8>>,
+    /// Value of the cells that are not in {cells}
+    default: Option<u8>,
+}
+
+impl State {
+    fn current(&self) -> Option<u8> {
+        let data_ptr = self.data_ptr?;
+        self.cells.get(&data_ptr).copied().unwrap_or(self.default)
+    }
+
+    fn set_current(&mut self, value: Option<u8>) {
+        if let Some(data_ptr) = self.data_ptr {
+            self.cells.insert(data_ptr, value);
+        }
+    }
+
+    fn move_data_ptr(&mut self, amount: isize) {
+        self.data_ptr = self.data_ptr.map(|data_ptr| data_ptr + amount);
+    }
+
+    /// Forget everything about the data pointer and the cells.
+    fn forget(&mut self) {
+        self.data_ptr = None;
+        self.cells.clear();
+        self.default = None;
+    }
+}
+
+struct Linter<'a> {
+    program: &'a Program,
+    findings: Vec<Finding>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, kind: LintKind, index: usize, message: String) {
+        self.findings.push(Finding {
+            kind,
+            location: self.program.locations[index],
+            message,
+        });
+    }
+
+    /// Follow the instructions from {start} to {end} (exclusive) and update the {state}.
+    fn lint_block(&mut self, start: usize, end: usize, state: &mut State) {
+        let mut index = start;
+        while index < end {
+            match self.program.code[index] {
+                OpCode::IncValue(count) => {
+                    let value = state.current().map(|value| value.wrapping_add(count as u8));
+                    state.set_current(value);
+                }
+                OpCode::DecValue(count) => {
+                    let value = state.current().map(|value| value.wrapping_sub(count as u8));
+                    state.set_current(value);
+                }
+                OpCode::Input => state.set_current(None),
+                OpCode::Output | OpCode::Dump => (),
+                OpCode::IncDataPtr(count) => state.move_data_ptr(count as isize),
+                OpCode::DecDataPtr(count) => {
+                    state.move_data_ptr(-(count as isize));
+                    if state.data_ptr.is_some_and(|data_ptr| data_ptr < 0) {
+                        self.report(
+                            LintKind::PointerUnderflow,
+                            index,
+                            "the data pointer moves in front of the first cell".to_string(),
+                        );
+                        state.forget();
+                    }
+                }
+                OpCode::LoopStart => {
+                    let loop_end = self.program.jump_table[&index];
+                    let current = state.current();
+                    if current == Some(0) {
+                        let all_zero = state.default == Some(0)
+                            && state.cells.values().all(|value| *value == Some(0));
+                        let reason = if all_zero {
+                            "all cells are zero"
+                        } else {
+                            "the current cell is zero"
+                        };
+                        self.report(
+                            LintKind::DeadLoop,
+                            index,
+                            format!("the loop never executes because {}", reason),
+                        );
+                    } else if loop_end == index + 1 {
+                        self.report(
+                            LintKind::EmptyLoop,
+                            index,
+                            "the empty loop never terminates if the current cell is not zero"
+                                .to_string(),
+                        );
+                        if current.is_some() && loop_end + 1 < end {
+                            self.report(
+                                LintKind::UnreachableCode,
+                                loop_end + 1,
+                                "the code is unreachable because the loop before never terminates"
+                                    .to_string(),
+                            );
+                            return;
+                        }
+                    } else {
+                        // Every iteration starts with unknown cells. The cells changed by the body
+                        // are unknown after the loop.
+                        let mut body = State {
+                            data_ptr: state.data_ptr,
+                            cells: HashMap::new(),
+                            default: None,
+                        };
+                        self.lint_block(index + 1, loop_end, &mut body);
+                        if body.data_ptr.is_some() && body.data_ptr == state.data_ptr {
+                            for offset in body.cells.keys() {
+                                state.cells.insert(*offset, None);
+                            }
+                        } else {
+                            state.forget();
+                        }
+                    }
+                    // Every loop ends with a zero cell
+                    state.set_current(Some(0));
+                    index = loop_end;
+                }
+                OpCode::LoopEnd => (),
+            }
+            index += 1;
+        }
+    }
+
+    /// Adjacent instructions that undo each other.
+    fn cancelling_pairs(&mut self) {
+        let code = &self.program.code;
+        let mut index = 0;
+        while index + 1 < code.len() {
+            let cancelling = matches!(
+                (code[index], code[index + 1]),
+                (OpCode::IncValue(_), OpCode::DecValue(_))
+                    | (OpCode::DecValue(_), OpCode::IncValue(_))
+                    | (OpCode::IncDataPtr(_), OpCode::DecDataPtr(_))
+                    | (OpCode::DecDataPtr(_), OpCode::IncDataPtr(_))
+            );
+            if cancelling {
+                let pair = format!("{}{}", code[index].to_char(), code[index + 1].to_char());
+                self.report(
+                    LintKind::CancellingPair,
+                    index,
+                    format!("'{}' has no effect", pair),
+                );
+                index += 1;
+            }
+            index += 1;
+        }
+    }
+
+    /// Instructions directly following a letter are probably punctuation of a comment, e.g.
+    /// "Hello, world." or "well-known".
+    fn suspicious_comments(&mut self, source: &str) {
+        let mut previous = ' ';
+        let mut index = 0;
+        for (value, _) in SourceLocation::annotate(source) {
+            if OpCode::convert(value).is_some() {
+                if previous.is_ascii_alphabetic() && matches!(value, '.' | ',' | '+' | '-') {
+                    self.report(
+                        LintKind::SuspiciousComment,
+                        index,
+                        format!("'{}' after a word is executed as an instruction", value),
+                    );
+                }
+                index += 1;
+            }
+            previous = value;
+        }
+    }
+}
+
+/// Check the {source} for common mistakes. The findings are sorted by their location.
+pub fn lint_source(source: &str) -> Result<Vec<Finding>, ProgramError> {
+    let program = Program::new(source, false)?;
+    let mut linter = Linter {
+        program: &program,
+        findings: vec![],
+    };
+    let mut state = State {
+        data_ptr: Some(0),
+        cells: HashMap::new(),
+        default: Some(0),
+    };
+    linter.lint_block(0, program.code.len(), &mut state);
+    linter.cancelling_pairs();
+    linter.suspicious_comments(source);
+    let mut findings = linter.findings;
+    findings.sort_by_key(|finding| finding.location.offset);
+    Ok(findings)
+}
+
+/// Format the {findings} in the file {filename}.
+pub fn format_findings(filename: &str, findings: &[Finding], format: LintFormat) -> String {
+    match format {
+        LintFormat::Text => findings
+            .iter()
+            .map(|finding| {
+                format!(
+                    "{}:{}:{}: {}: {}\n",
+                    filename,
+                    finding.location.line,
+                    finding.location.column,
+                    finding.kind,
+                    finding.message
+                )
+            })
+            .collect(),
+        LintFormat::Json => {
+            let findings = findings
+                .iter()
+                .map(|finding| {
+                    json!({
+                        "kind": finding.kind.to_string(),
+                        "line": finding.location.line,
+                        "column": finding.location.column,
+                        "message": finding.message,
+                    })
+                })
+                .collect::<Vec<_>>();
+            format!("{:#}\n", json!({ "file": filename, "findings": findings }))
+        }
+    }
+}
+
+/// Lint the program of the {config}.
+///
+/// # Returns
+///     (formatted findings, number of findings)
+pub fn lint(
+    config: Config,
+    filename: &str,
+    format: LintFormat,
+) -> Result<(String, usize), ProgramError> {
+    let findings = lint_source(config.split_input().0)?;
+    Ok((format_findings(filename, &findings, format), findings.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(LintKind, usize, usize)> {
+        lint_source(source)
+            .unwrap()
+            .iter()
+            .map(|finding| (finding.kind, finding.location.line, finding.location.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        // Nothing to complain about
+        assert_eq!(kinds("++[>+<-]>.\n"), vec![]);
+
+        // Dead loops at the start and after a loop, a clear loop and an underflow
+        assert_eq!(
+            kinds("[comment]+>[-]<\n[-][.]<"),
+            vec![
+                (LintKind::DeadLoop, 1, 1),
+                (LintKind::DeadLoop, 1, 12),
+                (LintKind::DeadLoop, 2, 4),
+                (LintKind::PointerUnderflow, 2, 7),
+            ]
+        );
+
+        // Cancelling pairs and punctuation in comments
+        assert_eq!(
+            kinds("+>+-<>, Hello, world.\n"),
+            vec![
+                (LintKind::CancellingPair, 1, 3),
+                (LintKind::CancellingPair, 1, 5),
+                (LintKind::SuspiciousComment, 1, 14),
+                (LintKind::SuspiciousComment, 1, 21),
+            ]
+        );
+
+        // Empty loops with and without a known value
+        assert_eq!(
+            kinds("+[]+."),
+            vec![
+                (LintKind::EmptyLoop, 1, 2),
+                (LintKind::UnreachableCode, 1, 4)
+            ]
+        );
+        assert_eq!(kinds(",[]+."), vec![(LintKind::EmptyLoop, 1, 2)]);
+    }
+
+    #[test]
+    fn test_formats() {
+        let findings = lint_source("+-").unwrap();
+        assert_eq!(
+            format_findings("a.bf", &findings, LintFormat::Text),
+            "a.bf:1:1: cancelling-pair: '+-' has no effect\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&format_findings("a.bf", &findings, LintFormat::Json)).unwrap();
+        assert_eq!(json["findings"][0]["kind"], "cancelling-pair");
+        assert_eq!(json["findings"][0]["column"], 1);
+    }
+}
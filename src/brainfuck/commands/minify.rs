@@ -0,0 +1,108 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Minifier that turns a program into short brainfuck source without comments.
+use super::super::commands::config::Config;
+use super::super::jit::ast::AST;
+use super::super::vm::program::{Program, ProgramError};
+
+/// Minify the program of the {config}. Comments are dropped, adjacent updates are combined, loops
+/// that are never entered are removed and updates of known cell values use the shorter direction.
+/// The `#` instructions of the extended syntax are dropped, its embedded input is kept.
+pub fn minify(config: Config) -> Result<String, ProgramError> {
+    let (code, input) = config.split_input();
+    let program = Program::new(code, true)?;
+    let ast = AST::new(program, 0, config.memory_size)?
+        .combine_updates()
+        .optimize()
+        .remove_dead_code()
+        .combine_updates();
+    let mut source = ast.to_brainfuck();
+    if let Some(input) = input {
+        source.push('!');
+        source.push_str(input);
+    }
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::super::super::jit::evaluator::evaluate;
+    use super::*;
+
+    fn minify_code(code: &str) -> String {
+        minify(Config::new(code.to_string(), 16, false, None)).unwrap()
+    }
+
+    #[test]
+    fn test_minify() {
+        // Comments and cancelling instructions
+        assert_eq!(minify_code("print +-+ one\n><.<>"), "+.");
+        // Loops that are never entered
+        assert_eq!(minify_code("[.]+[-]++.[>]"), "++.[>]");
+        // Known values use the shorter direction
+        assert_eq!(minify_code("+++++.[-]+++."), "+++++.--.");
+        assert_eq!(minify_code("+++++.[-]-."), "+++++.[-]-.");
+        assert_eq!(minify_code(",[-]+."), ",[-]+.");
+        assert_eq!(minify_code(",[->>+<<]"), ",[->>+<<]");
+        assert_eq!(minify_code(&"+".repeat(200)), "-".repeat(56));
+
+        // Dumps are dropped, the embedded input is kept
+        let config = Config::new(",#.!ab[".to_string(), 16, false, None).with_extended_syntax(true);
+        assert_eq!(minify(config).unwrap(), ",.!ab[");
+    }
+
+    #[test]
+    fn test_examples() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "bf") {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            let minified = minify_code(&source);
+            assert!(minified.len() <= source.len(), "{:?}", path);
+
+            let original = AST::new(Program::new(&source, true).unwrap(), 0, 1 << 16).unwrap();
+            let minified = AST::new(Program::new(&minified, true).unwrap(), 0, 1 << 16).unwrap();
+            let (Ok(original), Ok(minified)) = (
+                evaluate(&original, "", 30000, 1 << 20),
+                evaluate(&minified, "", 30000, 1 << 20),
+            ) else {
+                continue;
+            };
+            if original.finished && minified.finished {
+                assert_eq!(original.output, minified.output, "{:?}", path);
+            }
+        }
+    }
+}
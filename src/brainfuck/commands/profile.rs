@@ -0,0 +1,356 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde_json::json;
+
+use super::super::vm::opcode::OpCode;
+use super::super::vm::preprocessor::SourceMap;
+use super::super::vm::profiler::Profiler;
+use super::super::vm::program::{Program, SourceLocation};
+
+// Heat colors from cold to hot
+const HEAT_COLORS: [&str; 5] = ["34", "36", "32", "33", "31"];
+// Color of instructions that were never executed
+const COLOR_DIM: &str = "2";
+
+/// Output format of the profiling data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum ProfileFormat {
+    /// One `index: count` line per instruction.
+    #[default]
+    Raw,
+    /// The source code colored by execution count.
+    Annotated,
+    /// Folded stacks of the enclosing loops for flamegraph tools.
+    Folded,
+    /// JSON report with instruction counts and loop statistics.
+    Json,
+}
+
+/// Selected outputs of the profiler.
+#[derive(Debug, Default)]
+pub struct ProfileOptions {
+    /// File for the instruction profile
+    pub profile: Option<PathBuf>,
+    pub format: ProfileFormat,
+    /// File for the memory access report
+    pub memory_report: Option<PathBuf>,
+    /// Image file for the memory access heatmap
+    pub heatmap: Option<PathBuf>,
+}
+
+impl ProfileOptions {
+    /// Returns true if any profiler output is requested.
+    pub fn enabled(&self) -> bool {
+        self.profile.is_some() || self.memory_report.is_some() || self.heatmap.is_some()
+    }
+}
+
+/// Render the profiling data in the provided format.
+///
+/// # Parameters
+///     code       - the source code of the program, expanded if it was preprocessed
+///     source_map - the locations of the expanded code in the macro source
+///     program    - the profiled program
+///     profiler   - the collected profiling data
+///     format     - the output format
+///
+pub fn format_profile(
+    code: &str,
+    source_map: Option<&SourceMap>,
+    program: &Program,
+    profiler: &Profiler,
+    format: ProfileFormat,
+) -> String {
+    let instructions = Instructions {
+        code,
+        source_map,
+        profiler,
+    };
+    match format {
+        ProfileFormat::Raw => raw(profiler),
+        ProfileFormat::Annotated => annotated(&instructions),
+        ProfileFormat::Folded => folded(&instructions),
+        ProfileFormat::Json => json(&instructions, program),
+    }
+}
+
+/// The profiled instructions of a program.
+struct Instructions<'a> {
+    code: &'a str,
+    source_map: Option<&'a SourceMap>,
+    profiler: &'a Profiler,
+}
+
+impl Instructions<'_> {
+    /// The source the locations point into: the macro source if the code was preprocessed.
+    fn source(&self) -> &str {
+        self.source_map.map_or(self.code, SourceMap::source)
+    }
+
+    /// Pair every instruction of the code with its location in the source and its execution
+    /// count. The profile data is indexed by uncompressed instructions, i.e. by the command
+    /// characters of the code.
+    fn iter(&self) -> impl Iterator<Item = (char, SourceLocation, usize)> + '_ {
+        SourceLocation::annotate(self.code)
+            .filter(|(value, _)| OpCode::convert(*value).is_some())
+            .zip(self.profiler.profile_data().iter())
+            .map(|((value, location), count)| {
+                let location = self
+                    .source_map
+                    .map_or(location, |source_map| source_map.map(location));
+                (value, location, *count)
+            })
+    }
+}
+
+fn raw(profiler: &Profiler) -> String {
+    profiler
+        .profile_data()
+        .iter()
+        .enumerate()
+        .map(|(index, value)| format!("{:05}: {}\n", index, *value))
+        .collect()
+}
+
+/// Map an execution count onto a heat color using a logarithmic scale.
+fn heat_color(count: usize, max: usize) -> &'static str {
+    if count == 0 || max == 0 {
+        return COLOR_DIM;
+    }
+    let scale = (count as f64).ln_1p() / (max as f64).ln_1p() * HEAT_COLORS.len() as f64;
+    HEAT_COLORS[(scale.ceil() as usize).clamp(1, HEAT_COLORS.len()) - 1]
+}
+
+fn colored(value: impl std::fmt::Display, color: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", color, value)
+}
+
+fn annotated(instructions: &Instructions) -> String {
+    // Instructions expanded from the same macro source share its count
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    instructions.iter().for_each(|(_, location, count)| {
+        *counts.entry(location.offset).or_default() += count;
+    });
+    let max = counts.values().copied().max().unwrap_or(0);
+
+    // Legend with the smallest count of every heat color
+    let mut output = String::from("heat:");
+    (0..HEAT_COLORS.len()).for_each(|index| {
+        let bound = ((max as f64).ln_1p() * index as f64 / HEAT_COLORS.len() as f64).exp_m1();
+        output.push_str(&format!(
+            " {}",
+            colored(format!(">{}", bound.floor() as usize), HEAT_COLORS[index])
+        ));
+    });
+    output.push('\n');
+
+    // Every line is prefixed with the highest execution count of its instructions
+    let mut line = String::new();
+    let mut line_max: Option<usize> = None;
+    let mut flush = |line: &mut String, line_max: &mut Option<usize>| {
+        match line_max.take() {
+            Some(count) => output.push_str(&format!("{:>12} | ", count)),
+            None => output.push_str(&format!("{:>12} | ", "")),
+        }
+        output.push_str(line);
+        output.push('\n');
+        line.clear();
+    };
+    SourceLocation::annotate(instructions.source()).for_each(|(value, location)| {
+        if value == '\n' {
+            flush(&mut line, &mut line_max);
+        } else if let Some(count) = counts.get(&location.offset) {
+            line_max = line_max.max(Some(*count));
+            line.push_str(&colored(value, heat_color(*count, max)));
+        } else {
+            line.push(value);
+        }
+    });
+    if !line.is_empty() {
+        flush(&mut line, &mut line_max);
+    }
+    output
+}
+
+/// Name of the flamegraph frame of the loop starting at {location}.
+fn frame(location: &SourceLocation) -> String {
+    format!("loop@{}:{}", location.line, location.column)
+}
+
+fn folded(instructions: &Instructions) -> String {
+    let mut stacks: BTreeMap<String, usize> = BTreeMap::new();
+    let mut frames = vec!["main".to_string()];
+    instructions.iter().for_each(|(value, location, count)| {
+        // The brackets belong to the loop they delimit
+        if value == '[' {
+            frames.push(frame(&location));
+        }
+        if count > 0 {
+            *stacks.entry(frames.join(";")).or_default() += count;
+        }
+        if value == ']' && 1 < frames.len() {
+            frames.pop();
+        }
+    });
+    stacks
+        .iter()
+        .map(|(stack, count)| format!("{} {}\n", stack, count))
+        .collect()
+}
+
+fn json(instructions: &Instructions, program: &Program) -> String {
+    let profiler = instructions.profiler;
+    let instructions = instructions
+        .iter()
+        .enumerate()
+        .map(|(index, (value, location, count))| {
+            json!({
+                "index": index,
+                "instruction": value.to_string(),
+                "line": location.line,
+                "column": location.column,
+                "count": count,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut loops = profiler.loops().iter().collect::<Vec<_>>();
+    loops.sort_by(|(a_ip, a), (b_ip, b)| b.steps.cmp(&a.steps).then(a_ip.cmp(b_ip)));
+    let loops = loops
+        .iter()
+        .map(|(ip, profile)| {
+            let location = program.location(**ip);
+            json!({
+                "ip": ip,
+                "line": location.map(|location| location.line),
+                "column": location.map(|location| location.column),
+                "entries": profile.entries,
+                "iterations": profile.iterations,
+                "steps": profile.steps,
+                "drift": profile.drift,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let report = json!({
+        "steps": profiler.steps(),
+        "instructions": instructions,
+        "loops": loops,
+    });
+    format!("{:#}\n", report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::super::super::io::string::{StdinString, StdoutString};
+    use super::super::super::vm::preprocessor::preprocess;
+    use super::super::super::vm::vm::{EvalError, Normal, VM};
+    use super::*;
+
+    #[test]
+    fn test_formats() -> Result<(), EvalError> {
+        let code = "++ two\n[>+<-]\n";
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let vm = VM::<Normal>::new(code, Box::new(stdin), Box::new(stdout), true, 4)?;
+        let mut vm = vm.enable_profiler();
+        vm.run()?;
+        let profiler = vm.profiler();
+
+        let raw = format_profile(code, None, vm.program(), profiler, ProfileFormat::Raw);
+        assert!(raw.starts_with("00000: 1\n00001: 1\n00002: 1\n00003: 2\n"));
+
+        let folded = format_profile(code, None, vm.program(), profiler, ProfileFormat::Folded);
+        assert_eq!(folded, "main 2\nmain;loop@2:1 11\n");
+
+        let annotated =
+            format_profile(code, None, vm.program(), profiler, ProfileFormat::Annotated);
+        let lines = annotated.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("           1 | "));
+        assert!(lines[1].ends_with(" two"));
+        assert!(lines[2].starts_with("           2 | "));
+
+        let json = format_profile(code, None, vm.program(), profiler, ProfileFormat::Json);
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["steps"], 12);
+        assert_eq!(report["instructions"][2]["line"], 2);
+        assert_eq!(report["loops"][0]["iterations"], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_macro_source() -> Result<(), EvalError> {
+        let source = "@define dec --\n+++\n[-@dec]\n";
+        let expansion = preprocess(source, Path::new("test.bf"), &[]).unwrap();
+        let source_map = Some(&expansion.source_map);
+        let program = expansion
+            .source_map
+            .map_program(Program::new(&expansion.code, true)?);
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let vm = VM::<Normal>::with_program(program, Box::new(stdin), Box::new(stdout), 4);
+        let mut vm = vm.enable_profiler();
+        vm.run()?;
+        let profiler = vm.profiler();
+        let profile =
+            |format| format_profile(&expansion.code, source_map, vm.program(), profiler, format);
+
+        // The locations point into the macro source, the decrements into the macro body
+        let report: serde_json::Value =
+            serde_json::from_str(&profile(ProfileFormat::Json)).unwrap();
+        let location = |index: usize| {
+            let instruction = &report["instructions"][index];
+            (instruction["line"].clone(), instruction["column"].clone())
+        };
+        assert_eq!(location(0), (2.into(), 1.into()));
+        assert_eq!(location(3), (3.into(), 1.into()));
+        assert_eq!(location(5), (1.into(), 13.into()));
+        assert_eq!(location(6), (1.into(), 14.into()));
+        assert_eq!(report["loops"][0]["line"], 3);
+        assert_eq!(report["loops"][0]["column"], 1);
+
+        let folded = profile(ProfileFormat::Folded);
+        assert_eq!(folded, "main 3\nmain;loop@3:1 5\n");
+
+        let annotated = profile(ProfileFormat::Annotated);
+        let lines = annotated.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("           1 | @define dec "));
+        assert!(lines[3].contains("@dec"));
+        Ok(())
+    }
+}
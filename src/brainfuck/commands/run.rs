@@ -30,30 +30,43 @@
 use std::fs::File;
 use std::io::Write;
 
+use super::super::commands::common::stdin;
 use super::super::commands::config::Config;
-use super::super::io::console::{StdinConsole, StdoutConsole};
+use super::super::commands::profile::{ProfileOptions, format_profile};
+use super::super::io::console::StdoutConsole;
+use super::super::vm::profiler::HOT_LOOPS;
 use super::super::vm::vm::{EvalError, Normal, VM};
 
-pub fn run(config: Config, profile: bool) -> Result<(), EvalError> {
-    let stdin = StdinConsole::new(None);
+pub fn run(config: Config, options: &ProfileOptions) -> Result<(), EvalError> {
     let stdout = StdoutConsole::new();
-    let mut vm = VM::<Normal>::new(
-        &config.code,
-        Box::new(stdin),
+    let mut vm = VM::<Normal>::with_program(
+        config.program()?,
+        stdin(&config, None),
         Box::new(stdout),
-        config.rle,
         config.memory_size,
-    )?;
-    if profile {
+    );
+    vm.enable_dumps();
+    if options.enabled() {
         let mut vm_profile = vm.enable_profiler();
         let result = vm_profile.run();
-        let profile_data = vm_profile
-            .profile_data()
-            .iter()
-            .enumerate()
-            .map(|(index, value)| format!("{:05}: {}\n", index, *value))
-            .collect::<String>();
-        File::create("profile.txt")?.write_all(profile_data.as_bytes())?;
+        let profiler = vm_profile.profiler();
+        if let Some(path) = &options.profile {
+            let profile_data = format_profile(
+                &config.code,
+                config.source_map.as_ref(),
+                vm_profile.program(),
+                profiler,
+                options.format,
+            );
+            File::create(path)?.write_all(profile_data.as_bytes())?;
+            eprint!("{}", profiler.loop_report(vm_profile.program(), HOT_LOOPS));
+        }
+        if let Some(path) = &options.memory_report {
+            File::create(path)?.write_all(profiler.memory().report().as_bytes())?;
+        }
+        if let Some(path) = &options.heatmap {
+            profiler.memory().write_heatmap(path)?;
+        }
         result
     } else {
         vm.run()
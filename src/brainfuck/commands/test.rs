@@ -0,0 +1,487 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Golden output tests for brainfuck programs.
+//!
+//! A test is a `*.bf` file with the expected output either in a sibling `.out` file or in an
+//! `{ expected: ... }` comment at the start of the program. The input comes from a sibling `.in`
+//! file or an `{ input: ... }` comment.
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use thiserror::Error;
+
+#[cfg(target_arch = "aarch64")]
+use super::super::jit::ast::MAX_OPT_LEVEL;
+
+use super::super::commands::config::Config;
+use super::super::commands::verify::{self, Outcome, Termination};
+use super::super::vm::vm::EvalError;
+
+// Number of unchanged lines shown around the differences
+const DIFF_CONTEXT: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum TestError {
+    #[error("Cannot read '{0}'")]
+    IOError(PathBuf, #[source] io::Error),
+    #[error("{failed} of {total} tests failed")]
+    Failed { failed: usize, total: usize },
+}
+
+/// Engine that executes the tests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Engine {
+    /// The interpreter (honours the global `--rle` option)
+    #[default]
+    Int,
+    /// The JIT with all optimizations. It ignores the step limit, so every test has to end.
+    #[cfg(target_arch = "aarch64")]
+    Jit,
+}
+
+/// A program with its input and expected output.
+#[derive(Debug, PartialEq)]
+pub struct TestCase {
+    pub path: PathBuf,
+    pub code: String,
+    pub input: String,
+    pub expected: String,
+}
+
+/// Result of a single test. A test without failure passed.
+#[derive(Debug)]
+pub struct TestResult {
+    pub path: PathBuf,
+    pub failure: Option<String>,
+}
+
+/// Results of all tests in the order of execution.
+#[derive(Debug)]
+pub struct TestReport {
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    pub fn failed(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.failure.is_some())
+            .count()
+    }
+
+    /// Turn the report into an error if any test failed.
+    pub fn check(&self) -> Result<(), TestError> {
+        match self.failed() {
+            0 => Ok(()),
+            failed => Err(TestError::Failed {
+                failed,
+                total: self.results.len(),
+            }),
+        }
+    }
+}
+
+impl Display for TestReport {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for result in &self.results {
+            match &result.failure {
+                None => writeln!(formatter, "PASS {}", result.path.display())?,
+                Some(failure) => {
+                    writeln!(formatter, "FAIL {}: {}", result.path.display(), failure)?
+                }
+            }
+        }
+        let failed = self.failed();
+        writeln!(
+            formatter,
+            "\n{} passed, {} failed",
+            self.results.len() - failed,
+            failed
+        )
+    }
+}
+
+/// Split the leading `{ key: value }` comments from the {code}. The comments are replaced by
+/// spaces so the source locations of the program do not change.
+///
+/// # Returns
+///     (code, input, expected output)
+fn parse_header(code: &str) -> (String, Option<String>, Option<String>) {
+    let mut input = None;
+    let mut expected = None;
+    let mut header_end = 0;
+    loop {
+        let rest = &code[header_end..];
+        let start = header_end + (rest.len() - rest.trim_start().len());
+        if !code[start..].starts_with('{') {
+            break;
+        }
+        // Find the closing brace that is not escaped
+        let mut escaped = false;
+        let Some(length) = code[start..].find(|c| {
+            let closing = c == '}' && !escaped;
+            escaped = c == '\\' && !escaped;
+            closing
+        }) else {
+            break;
+        };
+        let content = &code[start + 1..start + length];
+        let Some((key, value)) = content.trim_start().split_once(':') else {
+            break;
+        };
+        let target = match key {
+            "expected" => &mut expected,
+            "input" => &mut input,
+            _ => break,
+        };
+        *target = Some(unescape(value.trim()));
+        header_end = start + length + 1;
+    }
+
+    let blank = code[..header_end]
+        .chars()
+        .map(|c| if c == '\n' { '\n' } else { ' ' })
+        .collect::<String>();
+    (blank + &code[header_end..], input, expected)
+}
+
+/// Replace the escape sequences `\n`, `\t`, `\\`, `\{` and `\}` of a header value.
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Read the test of the program at {path}. Sibling `.in`/`.out` files take precedence over the
+/// header comments. Programs without expected output are no tests.
+pub fn load(path: &Path) -> Result<Option<TestCase>, TestError> {
+    let read = |path: PathBuf| match fs::read_to_string(&path) {
+        Ok(content) => Ok(Some(content)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(TestError::IOError(path, error)),
+    };
+    let Some(source) = read(path.to_path_buf())? else {
+        return Err(TestError::IOError(
+            path.to_path_buf(),
+            io::ErrorKind::NotFound.into(),
+        ));
+    };
+    let (code, input, expected) = parse_header(&source);
+    let input = read(path.with_extension("in"))?.or(input);
+    let expected = read(path.with_extension("out"))?.or(expected);
+    Ok(expected.map(|expected| TestCase {
+        path: path.to_path_buf(),
+        code,
+        input: input.unwrap_or_default(),
+        expected,
+    }))
+}
+
+/// Find all tests in the {paths}. Directories are searched recursively for `*.bf` files, files
+/// are taken as they are.
+pub fn discover(paths: &[PathBuf]) -> Result<Vec<TestCase>, TestError> {
+    let mut files = vec![];
+    let mut pending = paths.to_vec();
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            let entries =
+                fs::read_dir(&path).map_err(|error| TestError::IOError(path.clone(), error))?;
+            for entry in entries {
+                let entry = entry.map_err(|error| TestError::IOError(path.clone(), error))?;
+                let entry_path = entry.path();
+                if entry_path.is_dir() || entry_path.extension().is_some_and(|ext| ext == "bf") {
+                    pending.push(entry_path);
+                }
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    let mut tests = vec![];
+    for file in files {
+        if let Some(test) = load(&file)? {
+            tests.push(test);
+        }
+    }
+    Ok(tests)
+}
+
+/// Line based diff of the {expected} and the {actual} output. Lines starting with `-` are
+/// missing, lines starting with `+` are unexpected.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.split_inclusive('\n').collect::<Vec<_>>();
+    let actual = actual.split_inclusive('\n').collect::<Vec<_>>();
+
+    // Longest common subsequence of the lines from the end of both outputs
+    let mut lengths = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push((' ', expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            lines.push(('-', expected[i]));
+            i += 1;
+        } else {
+            lines.push(('+', actual[j]));
+            j += 1;
+        }
+    }
+
+    // Only keep the unchanged lines close to a difference
+    let changed = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _))| *kind != ' ')
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if changed.is_empty() {
+        return String::new();
+    }
+    let mut result = String::new();
+    let mut skipped = false;
+    for (index, (kind, line)) in lines.iter().enumerate() {
+        let near = changed
+            .iter()
+            .any(|changed| changed.abs_diff(index) <= DIFF_CONTEXT);
+        if !near {
+            skipped = true;
+            continue;
+        }
+        if skipped {
+            result.push_str("    ...\n");
+            skipped = false;
+        }
+        result.push_str(&format!("    {}{}\n", kind, line.trim_end_matches('\n')));
+        if !line.ends_with('\n') {
+            result.push_str("    \\ No newline at end of output\n");
+        }
+    }
+    if skipped {
+        result.push_str("    ...\n");
+    }
+    result
+}
+
+/// Indent all lines like the lines of the diff.
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Execute the {test} with the {engine}.
+fn execute(
+    config: &Config,
+    test: &TestCase,
+    engine: Engine,
+    step_limit: Option<usize>,
+) -> Result<Outcome, EvalError> {
+    let config = Config::new(test.code.clone(), config.memory_size, config.rle, None);
+    match engine {
+        Engine::Int => verify::interpreter(&config, config.rle, &test.input, step_limit),
+        #[cfg(target_arch = "aarch64")]
+        Engine::Jit => verify::jit(&config, MAX_OPT_LEVEL, &test.input),
+    }
+}
+
+/// Run the {test} and describe why it failed.
+fn run_test(
+    config: &Config,
+    test: &TestCase,
+    engine: Engine,
+    step_limit: Option<usize>,
+) -> Option<String> {
+    let outcome = match execute(config, test, engine, step_limit) {
+        Ok(outcome) => outcome,
+        Err(EvalError::ProgramError(error)) => {
+            let rendered = error.render(&test.path.display().to_string(), &test.code);
+            return Some(format!("invalid program\n{}", indent(&rendered)));
+        }
+        Err(error) => return Some(format!("{}", error)),
+    };
+    let output_differs = outcome.output != test.expected;
+    let reason = match (&outcome.termination, &outcome.fault, output_differs) {
+        (Termination::Finished, _, false) => return None,
+        (Termination::Finished, _, true) => "output differs".to_string(),
+        (_, Some(fault), _) => {
+            let rendered = fault.render(&test.path.display().to_string(), &test.code);
+            format!("runtime error\n{}", indent(&rendered))
+        }
+        (termination, None, _) => format!("{}", termination),
+    };
+    if output_differs {
+        let diff = diff(&test.expected, &outcome.output);
+        Some(format!("{}\n{}", reason, diff.trim_end_matches('\n')))
+    } else {
+        Some(reason)
+    }
+}
+
+/// Discover the tests in {paths} and run them with the {engine}. The memory size and RLE come
+/// from the {config}.
+pub fn test(
+    config: Config,
+    paths: &[PathBuf],
+    engine: Engine,
+    step_limit: Option<usize>,
+) -> Result<TestReport, TestError> {
+    let results = discover(paths)?
+        .iter()
+        .map(|test| TestResult {
+            path: test.path.clone(),
+            failure: run_test(&config, test, engine, step_limit),
+        })
+        .collect();
+    Ok(TestReport { results })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let (code, input, expected) =
+            parse_header("{ input: ab\\}c }\n { expected: A\\nB\\\\ }\n,[.,]{ comment }");
+        assert_eq!(input.as_deref(), Some("ab}c"));
+        assert_eq!(expected.as_deref(), Some("A\nB\\"));
+        assert_eq!(
+            code,
+            format!("{}\n{}\n,[.,]{{ comment }}", " ".repeat(16), " ".repeat(21))
+        );
+
+        let (code, input, expected) = parse_header("{ a comment, not a header }+.");
+        assert_eq!((input, expected), (None, None));
+        assert_eq!(code, "{ a comment, not a header }+.");
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb\n", "a\nb\n"), "");
+        let result = diff("1\n2\n3\n4\n5\n6\n7\n", "1\n2\n3\n4\nX\n6\n7");
+        assert_eq!(
+            result,
+            "    ...\n     3\n     4\n    -5\n    +X\n     6\n    -7\n    +7\n    \\ No newline at end of output\n"
+        );
+    }
+
+    #[test]
+    fn test_runner() {
+        let directory = std::env::temp_dir().join(format!("bf-test-{}", std::process::id()));
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        fs::write(directory.join("echo.bf"), ",.,.,.").unwrap();
+        fs::write(directory.join("echo.in"), "abc").unwrap();
+        fs::write(directory.join("echo.out"), "abc").unwrap();
+        fs::write(
+            directory.join("nested/inline.bf"),
+            "{ expected: 0 }\n++++++++[>++++++<-]>+.",
+        )
+        .unwrap();
+        fs::write(directory.join("nested/loop.bf"), "{ expected: }+[]").unwrap();
+        fs::write(directory.join("nested/none.bf"), "+.").unwrap();
+
+        let config = Config::new(String::new(), 16, false, None);
+        let report = test(
+            config,
+            std::slice::from_ref(&directory),
+            Engine::Int,
+            Some(1000),
+        )
+        .unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let failures = report
+            .results
+            .iter()
+            .map(|result| {
+                let name = result.path.strip_prefix(&directory).unwrap().to_path_buf();
+                (name, result.failure.clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failures,
+            vec![
+                (PathBuf::from("echo.bf"), None),
+                (
+                    PathBuf::from("nested/inline.bf"),
+                    Some(
+                        "output differs\n    -0\n    \\ No newline at end of output\n    +1\n    \\ No newline at end of output"
+                            .to_string()
+                    )
+                ),
+                (
+                    PathBuf::from("nested/loop.bf"),
+                    Some("step limit reached".to_string())
+                ),
+            ]
+        );
+        assert!(matches!(
+            report.check(),
+            Err(TestError::Failed {
+                failed: 2,
+                total: 3
+            })
+        ));
+    }
+}
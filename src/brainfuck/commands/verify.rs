@@ -0,0 +1,374 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+use std::fmt::Display;
+
+use thiserror::Error;
+
+#[cfg(target_arch = "aarch64")]
+use super::super::jit::{ast::MAX_OPT_LEVEL, jit::JIT};
+
+use super::super::commands::config::Config;
+use super::super::io::string::{StdinString, StdoutString};
+use super::super::vm::fault::Fault;
+use super::super::vm::vm::{EvalError, Normal, VM};
+
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("Evaluation error")]
+    EvalError(#[from] EvalError),
+    #[error("{0}")]
+    Divergence(String),
+}
+
+/// How the execution of an engine ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Termination {
+    Finished,
+    /// The data pointer left the memory
+    OutOfBounds,
+    Error(String),
+    StepLimit,
+}
+
+impl Display for Termination {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Termination::Finished => write!(formatter, "finished"),
+            Termination::OutOfBounds => write!(formatter, "data pointer out of bounds"),
+            Termination::Error(error) => write!(formatter, "error '{}'", error),
+            Termination::StepLimit => write!(formatter, "step limit reached"),
+        }
+    }
+}
+
+/// Final state of the program executed by one engine.
+#[derive(Debug)]
+pub struct Outcome {
+    pub engine: String,
+    pub termination: Termination,
+    pub output: String,
+    pub memory: Vec<u8>,
+    pub data_ptr: usize,
+    /// Number of executed uncompressed instructions, unknown for the JIT
+    pub steps: Option<usize>,
+    /// Details if the data pointer left the memory
+    pub fault: Option<Box<Fault>>,
+}
+
+/// Run the program in the interpreter. The step limit counts uncompressed instructions, so the
+/// interpreter stops at the same instruction with and without RLE.
+pub(super) fn interpreter(
+    config: &Config,
+    rle: bool,
+    input: &str,
+    step_limit: Option<usize>,
+) -> Result<Outcome, EvalError> {
+    let mut vm = VM::<Normal>::with_program(
+        config.program_with_rle(rle)?,
+        Box::new(StdinString::new(input)),
+        Box::new(StdoutString::new()),
+        config.memory_size,
+    );
+    let mut steps = 0;
+    let mut fault = None;
+    let termination = loop {
+        let count = vm
+            .program()
+            .code
+            .get(vm.ip())
+            .map_or(0, |opcode| opcode.count());
+        if step_limit.is_some_and(|limit| limit < steps + count) {
+            break Termination::StepLimit;
+        }
+        match vm.execute_step() {
+            // The last instruction reports the end of the program after executing it
+            Ok(true) => {
+                steps += count;
+                break Termination::Finished;
+            }
+            Ok(false) => steps += count,
+            Err(EvalError::DataPointerOutOfBounds(details)) => {
+                fault = Some(details);
+                break Termination::OutOfBounds;
+            }
+            Err(error) => break Termination::Error(error.to_string()),
+        }
+    };
+    Ok(Outcome {
+        engine: format!("interpreter{}", if rle { " (RLE)" } else { "" }),
+        termination,
+        output: vm.stdout().to_string(),
+        memory: vm.memory().clone(),
+        data_ptr: vm.data_ptr(),
+        steps: Some(steps),
+        fault,
+    })
+}
+
+#[cfg(target_arch = "aarch64")]
+pub(super) fn jit(config: &Config, opt_level: u8, input: &str) -> Result<Outcome, EvalError> {
+    let program = config.program_with_rle(true)?;
+    let jit = JIT::with_program(program, config.memory_size, opt_level)?;
+    let execution = jit.run(Some(input))?;
+    Ok(Outcome {
+        engine: format!("jit -O{}", opt_level),
+        termination: if execution.fault.is_some() {
+            Termination::OutOfBounds
+        } else {
+            Termination::Finished
+        },
+        output: execution.output,
+        memory: execution.memory,
+        data_ptr: execution.data_ptr,
+        steps: None,
+        fault: execution.fault,
+    })
+}
+
+/// Describe the first difference between the {reference} and the {other} outcome.
+fn compare(reference: &Outcome, other: &Outcome) -> Option<String> {
+    let name = &other.engine;
+    let reference_name = &reference.engine;
+    if reference.termination != other.termination {
+        return Some(format!(
+            "{} {}, but {} {}",
+            reference_name, reference.termination, name, other.termination
+        ));
+    }
+
+    // After reaching the step limit the output of one engine has to be a prefix of the other
+    let limited = reference.termination == Termination::StepLimit;
+    let position = reference
+        .output
+        .chars()
+        .zip(other.output.chars())
+        .position(|(a, b)| a != b);
+    if let Some(index) = position {
+        return Some(format!(
+            "output differs at character {}: {} wrote {:?}, {} wrote {:?}",
+            index,
+            reference_name,
+            reference.output.chars().nth(index).unwrap_or_default(),
+            name,
+            other.output.chars().nth(index).unwrap_or_default()
+        ));
+    }
+    if !limited && reference.output.len() != other.output.len() {
+        return Some(format!(
+            "output length differs: {} wrote {} characters, {} wrote {}",
+            reference_name,
+            reference.output.chars().count(),
+            name,
+            other.output.chars().count()
+        ));
+    }
+    // After an error the state depends on where the engine stopped in a run of compressed
+    // instructions, but a run of moves writes no output
+    if reference.termination != Termination::Finished {
+        return None;
+    }
+
+    if reference.data_ptr != other.data_ptr {
+        return Some(format!(
+            "data pointer differs: {} ended at {}, {} at {}",
+            reference_name, reference.data_ptr, name, other.data_ptr
+        ));
+    }
+    reference
+        .memory
+        .iter()
+        .zip(other.memory.iter())
+        .position(|(a, b)| a != b)
+        .map(|address| {
+            format!(
+                "memory differs at {:#x}: {} has {:#04x}, {} has {:#04x}",
+                address, reference_name, reference.memory[address], name, other.memory[address]
+            )
+        })
+}
+
+/// Execute the program with every engine and compare output, memory, data pointer and how the
+/// execution ended. The JIT has no step limit, so it only runs if the interpreter finished or left
+/// the memory.
+///
+/// # Returns
+///     the outcome of every engine or the first divergence
+pub fn verify_outcomes(
+    config: &Config,
+    input: &str,
+    step_limit: Option<usize>,
+) -> Result<Vec<Outcome>, VerifyError> {
+    let reference = interpreter(config, false, input, step_limit)?;
+    #[allow(unused_mut)]
+    let mut outcomes = vec![interpreter(config, true, input, step_limit)?];
+    #[cfg(target_arch = "aarch64")]
+    if matches!(
+        reference.termination,
+        Termination::Finished | Termination::OutOfBounds
+    ) {
+        for opt_level in 0..=MAX_OPT_LEVEL {
+            outcomes.push(jit(config, opt_level, input)?);
+        }
+    }
+
+    if let Some(divergence) = outcomes
+        .iter()
+        .find_map(|outcome| compare(&reference, outcome))
+    {
+        return Err(VerifyError::Divergence(divergence));
+    }
+    outcomes.insert(0, reference);
+    Ok(outcomes)
+}
+
+/// Verify the program and summarize the outcome of every engine.
+pub fn verify(
+    config: Config,
+    input: &str,
+    step_limit: Option<usize>,
+) -> Result<String, VerifyError> {
+    let outcomes = verify_outcomes(&config, input, step_limit)?;
+    let mut report = outcomes
+        .iter()
+        .map(|outcome| {
+            format!(
+                "{:<20}  {}, {} steps, {} output characters, data pointer {}\n",
+                outcome.engine,
+                outcome.termination,
+                outcome
+                    .steps
+                    .map_or("?".to_string(), |steps| steps.to_string()),
+                outcome.output.chars().count(),
+                outcome.data_ptr
+            )
+        })
+        .collect::<String>();
+    report.push_str("All engines agree\n");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    // Stop long running examples (e.g. mandel.bf) and examples waiting for input
+    const STEP_LIMIT: usize = 1 << 22;
+
+    #[test]
+    fn test_examples() -> Result<(), VerifyError> {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut count = 0;
+        for entry in fs::read_dir(examples).map_err(EvalError::from)? {
+            let path = entry.map_err(EvalError::from)?.path();
+            if path.extension().is_none_or(|extension| extension != "bf") {
+                continue;
+            }
+            let code = fs::read_to_string(&path).map_err(EvalError::from)?;
+            let config = Config::new(code, 1 << 16, false, None);
+            verify_outcomes(&config, "", Some(STEP_LIMIT))
+                .map_err(|error| VerifyError::Divergence(format!("{:?}: {}", path, error)))?;
+            count += 1;
+        }
+        assert!(count > 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_divergence() {
+        let outcome = |output: &str, memory: Vec<u8>| Outcome {
+            engine: "test".to_string(),
+            termination: Termination::Finished,
+            output: output.to_string(),
+            memory,
+            data_ptr: 0,
+            steps: None,
+            fault: None,
+        };
+        let reference = outcome("abc", vec![0, 1]);
+        assert_eq!(compare(&reference, &outcome("abc", vec![0, 1])), None);
+        assert!(
+            compare(&reference, &outcome("abd", vec![0, 1]))
+                .unwrap()
+                .starts_with("output differs at character 2")
+        );
+        assert!(
+            compare(&reference, &outcome("abc", vec![0, 2]))
+                .unwrap()
+                .starts_with("memory differs at 0x1")
+        );
+
+        let limited = Outcome {
+            termination: Termination::StepLimit,
+            ..outcome("ab", vec![])
+        };
+        assert!(
+            compare(&reference, &limited)
+                .unwrap()
+                .contains("step limit reached")
+        );
+
+        // Leaving the memory skips the state, but not the output
+        let out_of_bounds = |output: &str, memory: Vec<u8>| Outcome {
+            termination: Termination::OutOfBounds,
+            ..outcome(output, memory)
+        };
+        let reference = out_of_bounds("a", vec![0]);
+        assert_eq!(compare(&reference, &out_of_bounds("a", vec![1])), None);
+        assert!(
+            compare(&reference, &out_of_bounds("aa", vec![0]))
+                .unwrap()
+                .starts_with("output length differs")
+        );
+
+        // A program that finishes within the step limit on both engines
+        let config = Config::new("+++[>++<-]>.".to_string(), 4, false, None);
+        let outcomes = verify_outcomes(&config, "", Some(24)).unwrap();
+        assert_eq!(outcomes[0].termination, Termination::Finished);
+        assert_eq!(outcomes[1].steps, Some(24));
+        // Stopping after 23 instructions stops the RLE interpreter before the output as well
+        let outcomes = verify_outcomes(&config, "", Some(23)).unwrap();
+        assert_eq!(outcomes[0].termination, Termination::StepLimit);
+
+        // With RLE the data pointer stays in front of the run of moves that leaves the memory
+        let config = Config::new("+>>>>".to_string(), 3, false, None);
+        let outcomes = verify_outcomes(&config, "", None).unwrap();
+        assert_eq!((outcomes[0].data_ptr, outcomes[1].data_ptr), (2, 0));
+        assert_eq!(outcomes[1].termination, Termination::OutOfBounds);
+        assert!(outcomes[1].fault.is_some());
+
+        // The input embedded with the extended syntax is no code
+        let config = Config::new(",.!ab[".to_string(), 4, false, None).with_extended_syntax(true);
+        let outcomes = verify_outcomes(&config, "a", None).unwrap();
+        assert_eq!(outcomes[0].output, "a");
+    }
+}
@@ -29,5 +29,4 @@
 //
 pub(super) mod base;
 pub(super) mod console;
-#[cfg(test)]
 pub(super) mod string;
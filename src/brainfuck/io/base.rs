@@ -27,12 +27,74 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
-use std::{fmt::Debug, io};
+use std::{
+    fmt::{Debug, Display},
+    io,
+};
 
 pub trait Stdin: Debug {
     fn read(&mut self) -> Result<Option<char>, io::Error>;
+
+    /// Returns all characters consumed so far.
+    fn consumed(&self) -> String;
+
+    /// Number of characters consumed so far.
+    fn consumed_len(&self) -> usize;
+
+    /// Reset the input so that exactly {consumed} has been read. Characters that were read after
+    /// that point are replayed by the following reads.
+    fn rewind(&mut self, consumed: &str);
 }
 
-pub trait Stdout: Debug + ToString {
+pub trait Stdout: Debug + Display {
     fn write(&mut self, value: char);
+
+    /// Replace the output generated so far.
+    fn reset(&mut self, output: &str);
+}
+
+/// Characters of an input source. Characters after the current position are replayed before new
+/// characters are read from the source.
+#[derive(Debug, Default)]
+pub struct InputHistory {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl InputHistory {
+    pub fn new(chars: Vec<char>) -> Self {
+        InputHistory { chars, position: 0 }
+    }
+
+    /// Returns the next known character if there is one.
+    pub fn replay(&mut self) -> Option<char> {
+        let value = self.chars.get(self.position).copied();
+        if value.is_some() {
+            self.position += 1;
+        }
+        value
+    }
+
+    /// Append a character that was read from the source.
+    pub fn record(&mut self, value: char) {
+        self.chars.truncate(self.position);
+        self.chars.push(value);
+        self.position += 1;
+    }
+
+    pub fn consumed(&self) -> String {
+        self.chars[..self.position].iter().collect()
+    }
+
+    pub fn consumed_len(&self) -> usize {
+        self.position
+    }
+
+    pub fn rewind(&mut self, consumed: &str) {
+        let consumed: Vec<char> = consumed.chars().collect();
+        if !self.chars.starts_with(&consumed) {
+            self.chars = consumed.clone();
+        }
+        self.position = consumed.len();
+    }
 }
@@ -27,42 +27,61 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
+use std::fmt::Display;
 use std::io;
-use std::io::stdin;
-use std::io::stdout;
 use std::io::Read;
 use std::io::Write;
+use std::io::stdin;
+use std::io::stdout;
 
+use super::base::InputHistory;
 use super::base::Stdin;
 use super::base::Stdout;
 
 #[derive(Debug)]
 pub struct StdinConsole {
     prompt: Option<String>,
+    history: InputHistory,
 }
 
 impl StdinConsole {
     pub fn new(prompt: Option<String>) -> Self {
-        Self { prompt }
+        Self {
+            prompt,
+            history: InputHistory::default(),
+        }
     }
 }
 
 impl Stdin for StdinConsole {
     fn read(&mut self) -> Result<Option<char>, io::Error> {
+        if let Some(value) = self.history.replay() {
+            return Ok(Some(value));
+        }
         if let Some(p) = &self.prompt {
             print!("\n{}", p);
             io::stderr().flush()?;
         }
         let mut one_byte = [0];
         stdin().read_exact(&mut one_byte)?;
-        match one_byte[0] as char {
-            '\n' => {
-                return Ok(Some(0x0 as char));
-            }
-            c => {
-                return Ok(Some(c));
-            }
-        }
+        let value = match one_byte[0] as char {
+            '\n' => 0x0 as char,
+            c => c,
+        };
+        self.history.record(value);
+        Ok(Some(value))
+    }
+
+    fn consumed(&self) -> String {
+        self.history.consumed()
+    }
+
+    fn consumed_len(&self) -> usize {
+        self.history.consumed_len()
+    }
+
+    fn rewind(&mut self, consumed: &str) {
+        self.history.rewind(consumed)
     }
 }
 
@@ -83,10 +102,14 @@ impl Stdout for StdoutConsole {
         print!("{}", value);
         let _ = stdout().flush();
     }
+
+    fn reset(&mut self, output: &str) {
+        self.output = output.chars().collect();
+    }
 }
 
-impl ToString for StdoutConsole {
-    fn to_string(&self) -> String {
-        self.output.iter().collect()
+impl Display for StdoutConsole {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(&self.output.iter().collect::<String>())
     }
 }
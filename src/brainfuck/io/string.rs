@@ -27,28 +27,41 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
+use std::fmt::Display;
 use std::io;
-use std::str::Chars;
 
+use super::base::InputHistory;
 use super::base::Stdin;
 use super::base::Stdout;
 
 #[derive(Debug)]
-pub struct StdinString<'a> {
-    chars: Chars<'a>,
+pub struct StdinString {
+    history: InputHistory,
 }
 
-impl<'a> StdinString<'a> {
-    pub fn new(value: &'a str) -> Self {
+impl StdinString {
+    pub fn new(value: &str) -> Self {
         StdinString {
-            chars: value.chars(),
+            history: InputHistory::new(value.chars().collect()),
         }
     }
 }
 
-impl<'a> Stdin for StdinString<'a> {
-    fn read(self: &mut Self) -> Result<Option<char>, io::Error> {
-        Ok(self.chars.next())
+impl Stdin for StdinString {
+    fn read(&mut self) -> Result<Option<char>, io::Error> {
+        Ok(self.history.replay())
+    }
+
+    fn consumed(&self) -> String {
+        self.history.consumed()
+    }
+
+    fn consumed_len(&self) -> usize {
+        self.history.consumed_len()
+    }
+
+    fn rewind(&mut self, consumed: &str) {
+        self.history.rewind(consumed)
     }
 }
 
@@ -64,17 +77,22 @@ impl StdoutString {
 }
 
 impl Stdout for StdoutString {
-    fn write(self: &mut Self, value: char) {
+    fn write(&mut self, value: char) {
         self.output.push(value)
     }
+
+    fn reset(&mut self, output: &str) {
+        self.output = output.chars().collect();
+    }
 }
 
-impl ToString for StdoutString {
-    fn to_string(self: &Self) -> String {
-        self.output.iter().collect()
+impl Display for StdoutString {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(&self.output.iter().collect::<String>())
     }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -90,4 +108,18 @@ mod tests {
         assert_eq!(result.len(), value.len());
         assert_eq!(value, result.iter().collect::<String>());
     }
+
+    #[test]
+    fn test_rewind() {
+        let mut stdin = StdinString::new("abc");
+        stdin.read().unwrap();
+        stdin.read().unwrap();
+        assert_eq!(stdin.consumed(), "ab");
+        assert_eq!(stdin.consumed_len(), 2);
+        stdin.rewind("a");
+        assert_eq!(stdin.read().unwrap(), Some('b'));
+        stdin.rewind("xy");
+        assert_eq!(stdin.consumed(), "xy");
+        assert_eq!(stdin.read().unwrap(), None);
+    }
 }
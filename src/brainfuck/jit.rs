@@ -27,6 +27,13 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
- mod ast;
- mod codegen;
+pub mod analysis;
+pub mod ast;
+mod codegen;
+mod constants;
+pub mod evaluator;
+#[cfg(target_arch = "aarch64")]
+#[allow(clippy::module_inception)]
 pub mod jit;
+#[cfg(target_arch = "aarch64")]
+pub mod perf;
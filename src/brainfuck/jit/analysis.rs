@@ -0,0 +1,584 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Abstract interpretation of the AST. It computes the range of the data pointer at every node
+//! and the range of the cell values where they are known.
+//!
+//! Leaving the memory stops the program, so the data pointer ranges are limited to the memory.
+//! Loops are repeated until their state does not change anymore. After a few repetitions the
+//! ranges that still grow are extended to the whole memory (or all cell values).
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use super::super::vm::program::SourceLocation;
+use super::ast::AST;
+
+// Number of loop repetitions before growing ranges are extended
+const WIDEN_AFTER: usize = 2;
+
+/// Inclusive range of values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval<T> {
+    pub min: T,
+    pub max: T,
+}
+
+impl<T: Ord + Copy> Interval<T> {
+    pub fn new(min: T, max: T) -> Self {
+        Interval { min, max }
+    }
+
+    pub fn single(value: T) -> Self {
+        Interval::new(value, value)
+    }
+
+    /// Smallest interval containing both intervals.
+    pub fn join(&self, other: &Self) -> Self {
+        Interval::new(min(self.min, other.min), max(self.max, other.max))
+    }
+
+    pub fn contains(&self, other: &Self) -> bool {
+        self.min <= other.min && other.max <= self.max
+    }
+
+    pub fn exact(&self) -> Option<T> {
+        (self.min == self.max).then_some(self.min)
+    }
+}
+
+impl<T: Display + PartialEq> Display for Interval<T> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(formatter, "{}", self.min)
+        } else {
+            write!(formatter, "{}..={}", self.min, self.max)
+        }
+    }
+}
+
+const UNKNOWN: Interval<u8> = Interval { min: 0, max: 255 };
+const ZERO: Interval<u8> = Interval { min: 0, max: 0 };
+
+impl Interval<u8> {
+    /// Add the {values} with wrap around. If only some values wrap around, the result is unknown.
+    fn wrapping_add(&self, values: &Interval<u8>) -> Self {
+        let low = self.min as u16 + values.min as u16;
+        let high = self.max as u16 + values.max as u16;
+        if high <= 255 || 256 <= low {
+            Interval::new(low as u8, high as u8)
+        } else {
+            UNKNOWN
+        }
+    }
+}
+
+/// Abstract state of the program before an instruction.
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    /// Offset of the data pointer from the first cell
+    data_ptr: Interval<isize>,
+    /// Values of the cells that differ from {default}
+    cells: HashMap<isize, Interval<u8>>,
+    /// Values of all other cells
+    default: Interval<u8>,
+}
+
+impl State {
+    fn get(&self, offset: isize) -> Interval<u8> {
+        self.cells.get(&offset).copied().unwrap_or(self.default)
+    }
+
+    /// Values of the cells in the {range}.
+    fn values(&self, range: Interval<isize>) -> Interval<u8> {
+        if let Some(offset) = range.exact() {
+            return self.get(offset);
+        }
+        self.cells
+            .iter()
+            .filter(|(offset, _)| range.min <= **offset && **offset <= range.max)
+            .fold(self.default, |values, (_, cell)| values.join(cell))
+    }
+
+    fn current(&self) -> Interval<u8> {
+        self.values(self.data_ptr)
+    }
+
+    /// Write the {values} into the cells of the {range}. If the range has more than one cell, all
+    /// cells of the range might keep their values.
+    fn write(&mut self, range: Interval<isize>, values: Interval<u8>) {
+        if let Some(offset) = range.exact() {
+            self.cells.insert(offset, values);
+        } else {
+            for (_, cell) in self
+                .cells
+                .iter_mut()
+                .filter(|(offset, _)| range.min <= **offset && **offset <= range.max)
+            {
+                *cell = cell.join(&values);
+            }
+            self.default = self.default.join(&values);
+        }
+        let default = self.default;
+        self.cells.retain(|_, cell| *cell != default);
+    }
+
+    fn join(&self, other: &State) -> State {
+        let mut cells: HashMap<isize, Interval<u8>> = HashMap::new();
+        for offset in self.cells.keys().chain(other.cells.keys()) {
+            cells.insert(*offset, self.get(*offset).join(&other.get(*offset)));
+        }
+        let default = self.default.join(&other.default);
+        cells.retain(|_, cell| *cell != default);
+        State {
+            data_ptr: self.data_ptr.join(&other.data_ptr),
+            cells,
+            default,
+        }
+    }
+
+    /// Whether every concrete state of {self} is a state of {other}.
+    fn within(&self, other: &State) -> bool {
+        other.data_ptr.contains(&self.data_ptr)
+            && other.default.contains(&self.default)
+            && self
+                .cells
+                .keys()
+                .chain(other.cells.keys())
+                .all(|offset| other.get(*offset).contains(&self.get(*offset)))
+    }
+
+    /// Extend the ranges that grew from {self} to {next} to the whole memory or all cell values.
+    fn widen(&self, next: &State, memory_size: usize) -> State {
+        let mut widened = next.clone();
+        if next.data_ptr.min < self.data_ptr.min {
+            widened.data_ptr.min = 0;
+        }
+        if self.data_ptr.max < next.data_ptr.max {
+            widened.data_ptr.max = memory_size as isize - 1;
+        }
+        for offset in self.cells.keys().chain(next.cells.keys()) {
+            if self.get(*offset) != next.get(*offset) {
+                widened.cells.insert(*offset, UNKNOWN);
+            }
+        }
+        if self.default != next.default {
+            widened.default = UNKNOWN;
+        }
+        widened
+    }
+
+    /// State at the start of the loop body. None if the loop is never entered.
+    fn enter_loop(&self) -> Option<State> {
+        let current = self.current();
+        if current == ZERO {
+            return None;
+        }
+        let mut state = self.clone();
+        if self.data_ptr.exact().is_some() {
+            state.write(
+                self.data_ptr,
+                Interval::new(max(current.min, 1), current.max),
+            );
+        }
+        Some(state)
+    }
+
+    /// State after the loop. None if the loop never ends.
+    fn exit_loop(&self) -> Option<State> {
+        if 0 < self.current().min {
+            return None;
+        }
+        let mut state = self.clone();
+        if self.data_ptr.exact().is_some() {
+            state.write(self.data_ptr, ZERO);
+        }
+        Some(state)
+    }
+}
+
+/// Results of the analysis for a node of the AST.
+#[derive(Debug, Clone, Default)]
+pub struct NodeInfo {
+    /// Location of the innermost loop containing the node
+    pub location: Option<SourceLocation>,
+    /// Range of the data pointer before the node (None if the node is unreachable)
+    pub data_ptr: Option<Interval<isize>>,
+    /// Range of the cells the node moves to or adds to (None if it accesses no other cell)
+    pub access: Option<Interval<isize>>,
+}
+
+/// Results of the analysis of a program. The nodes are numbered in pre-order without the
+/// `AST::Program` node.
+#[derive(Debug)]
+pub struct Analysis {
+    pub memory_size: usize,
+    pub nodes: Vec<NodeInfo>,
+    /// Range of the data pointer during the whole execution
+    pub data_ptr: Interval<isize>,
+    /// The data pointer might reach the end of the memory
+    pub unbounded: bool,
+}
+
+impl Analysis {
+    /// Whether the {node} stays inside the memory in every execution.
+    pub fn in_bounds(&self, node: usize) -> bool {
+        let memory = Interval::new(0, self.memory_size as isize - 1);
+        self.nodes[node]
+            .access
+            .is_none_or(|access| memory.contains(&access))
+    }
+
+    /// Number of nodes that access other cells and the number of them that might leave the
+    /// memory.
+    pub fn checks(&self) -> (usize, usize) {
+        let accesses = self.nodes.iter().filter(|node| node.access.is_some());
+        let checked = (0..self.nodes.len())
+            .filter(|node| !self.in_bounds(*node))
+            .count();
+        (accesses.count(), checked)
+    }
+
+    /// Smallest memory size for the program, unless the data pointer might reach the end of the
+    /// memory.
+    pub fn minimal_memory_size(&self) -> Option<usize> {
+        (!self.unbounded).then_some(self.data_ptr.max as usize + 1)
+    }
+
+    /// Descriptions of the nodes that leave the memory whenever they are reached.
+    pub fn warnings(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter_map(|node| {
+                let access = node.access?;
+                let message = if access.max < 0 {
+                    "the data pointer moves in front of the first cell"
+                } else if self.memory_size as isize <= access.min {
+                    "the data pointer moves beyond the end of the memory"
+                } else {
+                    return None;
+                };
+                Some(match node.location {
+                    Some(location) => format!(
+                        "{} in the loop at line {}, column {}",
+                        message, location.line, location.column
+                    ),
+                    None => format!("{} outside of loops", message),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Number of nodes in the {nodes} including the nodes of loop bodies.
+fn size(nodes: &[AST]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            AST::Loop(body, _) => 1 + size(body),
+            AST::Program(body) => size(body),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Add a {NodeInfo} with the loop location for every node.
+fn collect_nodes(nodes: &[AST], location: Option<SourceLocation>, infos: &mut Vec<NodeInfo>) {
+    for node in nodes {
+        match node {
+            AST::Program(body) => collect_nodes(body, location, infos),
+            AST::Loop(body, loop_location) => {
+                infos.push(NodeInfo {
+                    location,
+                    ..Default::default()
+                });
+                collect_nodes(body, Some(*loop_location), infos);
+            }
+            _ => infos.push(NodeInfo {
+                location,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+struct Analyzer {
+    memory_size: usize,
+    nodes: Vec<NodeInfo>,
+    /// States at the start of the loops
+    invariants: HashMap<usize, State>,
+    unbounded: bool,
+}
+
+fn join_option(range: Option<Interval<isize>>, other: Interval<isize>) -> Option<Interval<isize>> {
+    Some(range.map_or(other, |range| range.join(&other)))
+}
+
+impl Analyzer {
+    /// Record that the {node} accesses the cells of the {range} and limit the range to the memory.
+    fn access(&mut self, node: usize, range: Interval<isize>) -> Option<Interval<isize>> {
+        self.nodes[node].access = join_option(self.nodes[node].access, range);
+        let last = self.memory_size as isize - 1;
+        if last < range.max {
+            self.unbounded = true;
+        }
+        let limited = Interval::new(max(range.min, 0), min(range.max, last));
+        (limited.min <= limited.max).then_some(limited)
+    }
+
+    fn move_data_ptr(&mut self, node: usize, mut state: State, amount: isize) -> Option<State> {
+        let range = Interval::new(state.data_ptr.min + amount, state.data_ptr.max + amount);
+        state.data_ptr = self.access(node, range)?;
+        Some(state)
+    }
+
+    fn add_to(&mut self, node: usize, mut state: State, offset: isize) -> Option<State> {
+        let source = state.current();
+        if source == ZERO {
+            return Some(state);
+        }
+        let range = Interval::new(state.data_ptr.min + offset, state.data_ptr.max + offset);
+        let Some(target) = self.access(node, range) else {
+            // Only a zero cell does not access the target
+            return (source.min == 0).then_some(state);
+        };
+        let values = match target.exact() {
+            Some(offset) if state.data_ptr.exact().is_some() => {
+                state.get(offset).wrapping_add(&source)
+            }
+            _ => UNKNOWN,
+        };
+        state.write(target, values);
+        state.write(state.data_ptr, ZERO);
+        Some(state)
+    }
+
+    /// Analyze the {nodes} starting at the node number {index}.
+    ///
+    /// # Returns
+    ///     the state after the nodes or None if the end is unreachable
+    fn block(
+        &mut self,
+        nodes: &[AST],
+        mut state: Option<State>,
+        index: &mut usize,
+    ) -> Option<State> {
+        for node in nodes {
+            if let AST::Program(body) = node {
+                state = self.block(body, state, index);
+                continue;
+            }
+            let current = *index;
+            *index += 1;
+            let Some(mut before) = state.take() else {
+                *index += size(std::slice::from_ref(node)) - 1;
+                continue;
+            };
+            self.nodes[current].data_ptr =
+                join_option(self.nodes[current].data_ptr, before.data_ptr);
+            state = match node {
+                AST::IncValue(count) => {
+                    let value = before
+                        .current()
+                        .wrapping_add(&Interval::single(*count as u8));
+                    before.write(before.data_ptr, value);
+                    Some(before)
+                }
+                AST::DecValue(count) => {
+                    let amount = (*count as u8).wrapping_neg();
+                    let value = before.current().wrapping_add(&Interval::single(amount));
+                    before.write(before.data_ptr, value);
+                    Some(before)
+                }
+                AST::Set(value) => {
+                    before.write(before.data_ptr, Interval::single(*value));
+                    Some(before)
+                }
+                AST::Input => {
+                    before.write(before.data_ptr, UNKNOWN);
+                    Some(before)
+                }
+                AST::Output => Some(before),
+                AST::IncDataPtr(count) => self.move_data_ptr(current, before, *count as isize),
+                AST::DecDataPtr(count) => self.move_data_ptr(current, before, -(*count as isize)),
+                AST::AddTo(offset) => self.add_to(current, before, *offset),
+                AST::Loop(body, _) => {
+                    *index += size(body);
+                    self.repeat(current, body, before)
+                }
+                AST::Program(_) => unreachable!(),
+            };
+        }
+        state
+    }
+
+    /// Repeat the analysis of the loop {body} until the state at the start of the loop does not
+    /// change anymore.
+    fn repeat(&mut self, node: usize, body: &[AST], entry: State) -> Option<State> {
+        let mut head = match self.invariants.get(&node) {
+            // Nothing new reaches the loop
+            Some(invariant) if entry.within(invariant) => return invariant.exit_loop(),
+            Some(invariant) => invariant.join(&entry),
+            None => entry,
+        };
+        let mut repetitions = 0;
+        loop {
+            let mut index = node + 1;
+            let next = match self.block(body, head.enter_loop(), &mut index) {
+                Some(end) => head.join(&end),
+                None => head.clone(),
+            };
+            if next == head {
+                break;
+            }
+            repetitions += 1;
+            head = if WIDEN_AFTER <= repetitions {
+                let widened = head.widen(&next, self.memory_size);
+                if head.data_ptr.max < next.data_ptr.max {
+                    self.unbounded = true;
+                }
+                widened
+            } else {
+                next
+            };
+        }
+        self.nodes[node].data_ptr = join_option(self.nodes[node].data_ptr, head.data_ptr);
+        let exit = head.exit_loop();
+        self.invariants.insert(node, head);
+        exit
+    }
+}
+
+/// Analyze the {ast} for a memory with {memory_size} cells.
+pub fn analyze(ast: &AST, memory_size: usize) -> Analysis {
+    let mut nodes = vec![];
+    collect_nodes(std::slice::from_ref(ast), None, &mut nodes);
+    let mut analyzer = Analyzer {
+        memory_size,
+        nodes,
+        invariants: HashMap::new(),
+        unbounded: false,
+    };
+    let start = State {
+        data_ptr: Interval::single(0),
+        cells: HashMap::new(),
+        default: ZERO,
+    };
+    let mut index = 0;
+    let end = analyzer.block(std::slice::from_ref(ast), Some(start), &mut index);
+
+    let last = memory_size as isize - 1;
+    let data_ptr = analyzer
+        .nodes
+        .iter()
+        .flat_map(|node| [node.data_ptr, node.access])
+        .flatten()
+        .chain(end.map(|state| state.data_ptr))
+        .fold(Interval::single(0), |range, other| range.join(&other));
+    Analysis {
+        memory_size,
+        nodes: analyzer.nodes,
+        data_ptr: Interval::new(max(data_ptr.min, 0), min(data_ptr.max, last)),
+        unbounded: analyzer.unbounded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck::vm::program::Program;
+
+    fn analyze_code(code: &str, memory_size: usize) -> Analysis {
+        let ast = AST::new(Program::new(code, true).unwrap(), 1, 1 << 16).unwrap();
+        analyze(&ast, memory_size)
+    }
+
+    #[test]
+    fn test_data_ptr_range() {
+        // A balanced loop keeps the data pointer range
+        let analysis = analyze_code("++++++++[>++++[>++>+++<<-]>>>+<<<<-]>>.", 100);
+        assert_eq!(analysis.data_ptr, Interval::new(0, 4));
+        assert_eq!(analysis.minimal_memory_size(), Some(5));
+        assert_eq!(analysis.checks(), (7, 0));
+        assert!(analysis.warnings().is_empty());
+
+        // A loop that moves the data pointer can reach the end of the memory
+        let analysis = analyze_code(">+[>+]", 100);
+        assert_eq!(analysis.data_ptr, Interval::new(0, 99));
+        assert_eq!(analysis.minimal_memory_size(), None);
+        assert_eq!(analysis.checks(), (2, 1));
+
+        // The clear loop ends with a zero cell, so the scan to the left is never executed
+        let analysis = analyze_code(">>[-][<]<", 8);
+        assert_eq!(analysis.minimal_memory_size(), Some(3));
+        assert!(analysis.nodes[3].data_ptr.is_none());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let analysis = analyze_code("+[>+<-]<", 10);
+        assert_eq!(
+            analysis.warnings(),
+            vec!["the data pointer moves in front of the first cell outside of loops"]
+        );
+
+        let analysis = analyze_code("+[->>>>+<<<<]", 3);
+        assert_eq!(
+            analysis.warnings(),
+            vec!["the data pointer moves beyond the end of the memory outside of loops"]
+        );
+
+        // Loops that are never executed do not leave the memory
+        let analysis = analyze_code(">,[<<]", 10);
+        assert_eq!(
+            analysis.warnings(),
+            vec![
+                "the data pointer moves in front of the first cell in the loop at line 1, column 3"
+            ]
+        );
+        assert!(analyze_code("[<<]", 10).warnings().is_empty());
+    }
+
+    #[test]
+    fn test_cell_values() {
+        let wrap = Interval::new(250, 255).wrapping_add(&Interval::single(10));
+        assert_eq!(wrap, Interval::new(4, 9));
+        assert_eq!(
+            Interval::new(250, 255).wrapping_add(&Interval::single(3)),
+            UNKNOWN
+        );
+
+        // The first loop is never executed. The second one moves the 3 into the next cell, so the
+        // third loop is executed and leaves the memory.
+        let analysis = analyze_code("+++>[<]<[->+<]>[<<]", 10);
+        assert!(analysis.nodes[3].data_ptr.is_none());
+        assert_eq!(analysis.nodes[8].data_ptr, Some(Interval::single(1)));
+        assert_eq!(analysis.warnings().len(), 1);
+    }
+}
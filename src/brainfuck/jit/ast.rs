@@ -32,9 +32,80 @@ use log::debug;
 
 use crate::brainfuck::jit::codegen::create_code_gen;
 
+use super::super::jit::analysis::Analysis;
 use super::super::jit::codegen::code_generator::CodeGenerator;
-use super::super::vm::{opcode::OpCode, program::Program};
+use super::super::vm::{
+    opcode::OpCode,
+    program::{Program, ProgramError, SourceLocation},
+};
 
+/// Code range of a top-level loop in the generated code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeSymbol {
+    // Offset from the start of the generated code
+    pub start: usize,
+    pub size: usize,
+    pub location: SourceLocation,
+}
+
+/// Bounds check in the generated code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundsCheck {
+    /// Index of the checked node in pre-order
+    pub node: usize,
+    /// Number of cells the node moves the data pointer or accesses away from it
+    pub movement: isize,
+    /// Location of the innermost loop around the node
+    pub location: Option<SourceLocation>,
+}
+
+/// Result of the code generation.
+pub struct GeneratedCode {
+    pub code: ExecutableBuffer,
+    /// Locations of the instrumented loops in the order of their counters
+    pub loops: Vec<SourceLocation>,
+    /// Code ranges of the top-level loops
+    pub symbols: Vec<CodeSymbol>,
+    /// Bounds checks in the order of their numbers
+    pub checks: Vec<BoundsCheck>,
+}
+
+/// State that is collected while generating code.
+struct CodeGenState<'a> {
+    loops: Option<Vec<SourceLocation>>,
+    symbols: Vec<CodeSymbol>,
+    checks: Vec<BoundsCheck>,
+    /// Nodes that stay inside the memory need no bounds checks
+    analysis: Option<&'a Analysis>,
+    /// Number of the next node in pre-order
+    node: usize,
+    /// Location of the innermost loop around the current node
+    location: Option<SourceLocation>,
+}
+
+impl CodeGenState<'_> {
+    /// The number of the bounds check of the {node} moving by {movement} cells, unless the
+    /// analysis proves that it stays inside the memory.
+    fn check(&mut self, node: usize, movement: isize) -> Option<usize> {
+        if self
+            .analysis
+            .is_some_and(|analysis| analysis.in_bounds(node))
+        {
+            return None;
+        }
+        self.checks.push(BoundsCheck {
+            node,
+            movement,
+            location: self.location,
+        });
+        Some(self.checks.len() - 1)
+    }
+}
+
+/// Highest supported optimization level.
+pub const MAX_OPT_LEVEL: u8 = 2;
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub enum AST {
     DecDataPtr(usize),
@@ -42,7 +113,8 @@ pub enum AST {
     IncDataPtr(usize),
     IncValue(usize),
     Input,
-    Loop(Vec<AST>),
+    // The location is the position of the opening bracket in the source code
+    Loop(Vec<AST>, SourceLocation),
     Output,
     Program(Vec<AST>),
     Set(u8),
@@ -51,11 +123,23 @@ pub enum AST {
 }
 
 impl AST {
-    pub fn new(program: Program) -> AST {
-        AST::Program(AST::convert_opcodes(&program, 0, program.code.len())).optimize()
+    /// Convert the program into an AST. Optimization level 0 keeps the program as it is, level 1
+    /// replaces common loop patterns and level 2 propagates the known cell values. The start of
+    /// the program is only evaluated if it stays inside the memory of {memory_size} cells.
+    pub fn new(program: Program, opt_level: u8, memory_size: usize) -> Result<AST, ProgramError> {
+        let ast = AST::Program(AST::convert_opcodes(&program, 0, program.code.len())?);
+        Ok(match opt_level {
+            0 => ast,
+            1 => ast.optimize(),
+            _ => ast.optimize().propagate_constants(memory_size),
+        })
     }
 
-    fn convert_opcodes(program: &Program, start: usize, end: usize) -> Vec<AST> {
+    fn convert_opcodes(
+        program: &Program,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<AST>, ProgramError> {
         debug!("Convert opcodes from {} => {}", start, end);
         let mut nodes = vec![];
         let mut index = start;
@@ -70,18 +154,38 @@ impl AST {
                 OpCode::Input => AST::Input,
                 OpCode::Output => AST::Output,
                 OpCode::LoopStart => {
-                    let loop_end = program.jump_table.get(&index.clone()).unwrap();
+                    // Only loops that end inside the current block are valid
+                    let loop_end = match program.jump_table.get(&index) {
+                        Some(&loop_end) if index < loop_end && loop_end < end => loop_end,
+                        _ => {
+                            return Err(ProgramError::UnclosedLoop {
+                                location: program.locations[index],
+                                hint: None,
+                            });
+                        }
+                    };
                     debug!("Convert loopbody from {} => {}", index, loop_end);
-                    let code_block = AST::convert_opcodes(program, index + 1, *loop_end);
-                    index = *loop_end;
-                    AST::Loop(code_block)
+                    let code_block = AST::convert_opcodes(program, index + 1, loop_end)?;
+                    let location = program.locations[index];
+                    index = loop_end;
+                    AST::Loop(code_block, location)
+                }
+                OpCode::LoopEnd => {
+                    return Err(ProgramError::UnmatchedClose {
+                        location: program.locations[index],
+                        hint: None,
+                    });
+                }
+                // The compiled code has no debug instructions
+                OpCode::Dump => {
+                    index += 1;
+                    continue;
                 }
-                OpCode::LoopEnd => unreachable!(),
             };
             nodes.push(ast_node);
             index += 1;
         }
-        nodes
+        Ok(nodes)
     }
 
     pub fn optimize(self) -> Self {
@@ -89,12 +193,14 @@ impl AST {
             AST::Program(codeblock) => {
                 AST::Program(codeblock.into_iter().map(AST::optimize).collect())
             }
-            AST::Loop(codeblock) => {
+            AST::Loop(codeblock, location) => {
                 let updated_codeblock: Vec<AST> =
                     codeblock.into_iter().map(|x| x.optimize()).collect();
                 match updated_codeblock[..] {
-                    // Set value to zero loops
-                    [AST::DecValue(_)] | [AST::IncValue(_)] => AST::Set(0),
+                    // Set value to zero loops. Only odd steps reach zero from every value.
+                    [AST::DecValue(count)] | [AST::IncValue(count)] if count % 2 == 1 => {
+                        AST::Set(0)
+                    }
                     // Add move value e.g., ->>>+<<<
                     [
                         AST::DecValue(1),
@@ -137,7 +243,7 @@ impl AST {
                     //     AST::DecDataPtr(b),
                     // ] if a == b => AST::MultiplyOffset(b as isize - a as isize, v),
                     // no optimizations
-                    _ => AST::Loop(updated_codeblock),
+                    _ => AST::Loop(updated_codeblock, location),
                 }
             }
             _ => self,
@@ -151,7 +257,7 @@ impl AST {
         "".to_string()
     }
 
-    fn pretty_print_with_indent(nodes: &Vec<AST>, indent: String) -> String {
+    fn pretty_print_with_indent(nodes: &[AST], indent: String) -> String {
         nodes
             .iter()
             .map(|node| match node {
@@ -161,50 +267,314 @@ impl AST {
                 AST::IncValue(count) => format!("{}+ ({})\n", indent, count),
                 AST::Output => format!("{}.\n", indent),
                 AST::Input => format!("{},\n", indent),
-                AST::Loop(codeblock) => {
+                AST::Loop(codeblock, _) => {
                     let sub_indent = format!("{}    ", indent);
                     let sub_string = AST::pretty_print_with_indent(codeblock, sub_indent);
                     format!("{}[\n{}{}]\n", indent, sub_string, indent)
                 }
                 AST::Set(value) => format!("{}set({})\n", indent, value),
                 AST::AddTo(offset) => format!("{}addto({})\n", indent, offset),
-                _ => unreachable!(),
+                AST::Program(codeblock) => AST::pretty_print_with_indent(codeblock, indent.clone()),
             })
             .collect()
     }
 
-    fn generate_code_block(nodes: &Vec<AST>, code_gen: &mut Box<dyn CodeGenerator>) {
+    /// The shortest node that adds {delta} to the current cell.
+    pub(super) fn value_update(delta: u8) -> AST {
+        if delta <= 128 {
+            AST::IncValue(delta as usize)
+        } else {
+            AST::DecValue(256 - delta as usize)
+        }
+    }
+
+    /// Combine adjacent updates of the same cell and adjacent movements of the data pointer.
+    /// Updates and movements that cancel each other are removed.
+    pub fn combine_updates(self) -> Self {
+        match self {
+            AST::Program(nodes) => AST::Program(AST::combine_block(nodes)),
+            AST::Loop(nodes, location) => AST::Loop(AST::combine_block(nodes), location),
+            _ => self,
+        }
+    }
+
+    fn combine_block(nodes: Vec<AST>) -> Vec<AST> {
+        let mut result: Vec<AST> = vec![];
+        for node in nodes.into_iter().map(AST::combine_updates) {
+            let delta = match node {
+                AST::IncValue(count) => count as isize,
+                AST::DecValue(count) => -(count as isize),
+                AST::IncDataPtr(count) => count as isize,
+                AST::DecDataPtr(count) => -(count as isize),
+                _ => {
+                    result.push(node);
+                    continue;
+                }
+            };
+            let moves = matches!(node, AST::IncDataPtr(_) | AST::DecDataPtr(_));
+            let previous = match result.last() {
+                Some(AST::IncValue(count)) if !moves => *count as isize,
+                Some(AST::DecValue(count)) if !moves => -(*count as isize),
+                Some(AST::IncDataPtr(count)) if moves => *count as isize,
+                Some(AST::DecDataPtr(count)) if moves => -(*count as isize),
+                _ => {
+                    result.push(node);
+                    continue;
+                }
+            };
+            result.pop();
+            let total = previous + delta;
+            if moves {
+                match total {
+                    0 => (),
+                    1.. => result.push(AST::IncDataPtr(total as usize)),
+                    _ => result.push(AST::DecDataPtr(total.unsigned_abs())),
+                }
+            } else if total.rem_euclid(256) != 0 {
+                result.push(AST::value_update(total.rem_euclid(256) as u8));
+            }
+        }
+        result
+    }
+
+    /// Index of the {node} in pre-order, as numbered by the code generation and the analysis, and
+    /// the location of the innermost loop around it. The program itself has no index.
+    pub fn position(&self, node: &AST) -> Option<(usize, Option<SourceLocation>)> {
+        fn search(
+            nodes: &[AST],
+            node: &AST,
+            index: &mut usize,
+            location: Option<SourceLocation>,
+        ) -> Option<(usize, Option<SourceLocation>)> {
+            for current in nodes {
+                if std::ptr::eq(current, node) {
+                    return Some((*index, location));
+                }
+                *index += 1;
+                if let AST::Loop(body, loop_location) = current
+                    && let Some(position) = search(body, node, index, Some(*loop_location))
+                {
+                    return Some(position);
+                }
+            }
+            None
+        }
+        match self {
+            AST::Program(nodes) => search(nodes, node, &mut 0, None),
+            _ => search(std::slice::from_ref(self), node, &mut 0, None),
+        }
+    }
+
+    /// Brainfuck source code of the AST. `Set` nodes clear the cell with `[-]` before updating it
+    /// and `AddTo` nodes become the loops they were created from.
+    pub fn to_brainfuck(&self) -> String {
+        match self {
+            AST::DecDataPtr(count) => "<".repeat(*count),
+            AST::IncDataPtr(count) => ">".repeat(*count),
+            AST::DecValue(count) => "-".repeat(*count),
+            AST::IncValue(count) => "+".repeat(*count),
+            AST::Input => ",".to_string(),
+            AST::Output => ".".to_string(),
+            AST::Loop(nodes, _) => format!(
+                "[{}]",
+                nodes.iter().map(AST::to_brainfuck).collect::<String>()
+            ),
+            AST::Program(nodes) => nodes.iter().map(AST::to_brainfuck).collect(),
+            AST::Set(value) => format!("[-]{}", AST::value_update(*value).to_brainfuck()),
+            AST::AddTo(offset) => {
+                let (forward, back) = if *offset < 0 { ("<", ">") } else { (">", "<") };
+                let distance = offset.unsigned_abs();
+                format!("[-{}+{}]", forward.repeat(distance), back.repeat(distance))
+            }
+        }
+    }
+
+    /// Generate the code of all {nodes}.
+    fn generate_code_block(
+        nodes: &[AST],
+        code_gen: &mut Box<dyn CodeGenerator>,
+        state: &mut CodeGenState,
+    ) {
         nodes
             .iter()
-            .for_each(|node| node.generate_code_inner(code_gen))
+            .for_each(|node| node.generate_code_inner(code_gen, state))
     }
 
-    fn generate_code_inner(&self, code_gen: &mut Box<dyn CodeGenerator>) {
+    fn generate_code_inner(&self, code_gen: &mut Box<dyn CodeGenerator>, state: &mut CodeGenState) {
+        let node = state.node;
+        if !matches!(self, AST::Program(_)) {
+            state.node += 1;
+        }
         match self {
             AST::IncValue(count) => code_gen.update_value(*count as i32),
             AST::DecValue(count) => code_gen.update_value(-(*count as i32)),
-            AST::IncDataPtr(count) => code_gen.update_memory_ptr(*count as i32),
-            AST::DecDataPtr(count) => code_gen.update_memory_ptr(-(*count as i32)),
+            AST::IncDataPtr(count) => {
+                let check = state.check(node, *count as isize);
+                code_gen.update_memory_ptr(*count as i32, check)
+            }
+            AST::DecDataPtr(count) => {
+                let check = state.check(node, -(*count as isize));
+                code_gen.update_memory_ptr(-(*count as i32), check)
+            }
             AST::Output => code_gen.output(),
             AST::Input => code_gen.input(),
             AST::Set(value) => code_gen.set(*value),
-            AST::AddTo(offset) => code_gen.add_to(*offset as i32),
-            AST::Loop(codeblock) => {
+            AST::AddTo(offset) => {
+                let check = state.check(node, *offset);
+                code_gen.add_to(*offset as i32, check)
+            }
+            AST::Loop(codeblock, location) => {
+                let counter = state.loops.as_mut().map(|loops| {
+                    loops.push(*location);
+                    2 * (loops.len() - 1)
+                });
+                if let Some(counter) = counter {
+                    code_gen.increment_counter(counter);
+                }
                 let (loop_start, loop_end) = code_gen.loop_start();
-                AST::generate_code_block(codeblock, code_gen);
+                let outer = state.location.replace(*location);
+                AST::generate_code_block(codeblock, code_gen, state);
+                state.location = outer;
+                if let Some(counter) = counter {
+                    code_gen.increment_counter(counter + 1);
+                }
                 code_gen.loop_end(loop_start, loop_end);
             }
             AST::Program(codeblock) => {
                 code_gen.function_prolog();
-                AST::generate_code_block(codeblock, code_gen);
+                codeblock.iter().for_each(|node| {
+                    let start = code_gen.offset();
+                    node.generate_code_inner(code_gen, state);
+                    if let AST::Loop(_, location) = node {
+                        state.symbols.push(CodeSymbol {
+                            start,
+                            size: code_gen.offset() - start,
+                            location: *location,
+                        });
+                    }
+                });
                 code_gen.function_epilog();
             }
         }
     }
 
     pub fn generate_code(&self) -> ExecutableBuffer {
+        self.generate(false, None).code
+    }
+
+    /// Generate the code of the program. If {instrument} is set, every loop increments a counter
+    /// at its header and at its back-edge. The counters of the n-th loop are 2 * n (header) and
+    /// 2 * n + 1 (back-edge). Every pointer movement checks the bounds of the memory, unless the
+    /// {analysis} proves that it stays inside the memory.
+    pub fn generate(&self, instrument: bool, analysis: Option<&Analysis>) -> GeneratedCode {
         let mut code_generator = create_code_gen();
-        self.generate_code_inner(&mut code_generator);
-        code_generator.finalize()
+        let mut state = CodeGenState {
+            loops: if instrument { Some(vec![]) } else { None },
+            symbols: vec![],
+            checks: vec![],
+            analysis,
+            node: 0,
+            location: None,
+        };
+        self.generate_code_inner(&mut code_generator, &mut state);
+        GeneratedCode {
+            code: code_generator.finalize(),
+            loops: state.loops.unwrap_or_default(),
+            symbols: state.symbols,
+            checks: state.checks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck::jit::analysis::analyze;
+
+    #[test]
+    fn test_instrumented_loops() {
+        let program = Program::new("+[->++<]\n>[.-][-]", true).unwrap();
+        let ast = AST::new(program, 1, 1 << 16).unwrap();
+        let generated = ast.generate(true, None);
+        // The clear loop is optimized into a set and has no counters
+        let positions = generated
+            .loops
+            .iter()
+            .map(|location| (location.line, location.column))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![(1, 2), (2, 2)]);
+        assert!(generated.code.len() > ast.generate_code().len());
+
+        // Top-level loops are consecutive ranges of the code
+        let symbols = &generated.symbols;
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].location, generated.loops[0]);
+        assert!(symbols[0].start + symbols[0].size <= symbols[1].start);
+        assert!(symbols[1].start + symbols[1].size < generated.code.len());
+    }
+
+    #[test]
+    fn test_large_counts() {
+        // Counts and offsets that do not fit into the immediates of the instructions
+        let code = format!(
+            "{}{}[-{}+{}]{}",
+            "+".repeat(5000),
+            ">".repeat(5000),
+            "<".repeat(300),
+            ">".repeat(300),
+            "<".repeat(70000)
+        );
+        let ast = AST::new(Program::new(&code, true).unwrap(), 1, 1 << 16).unwrap();
+        assert!(ast.pretty_print().contains("addto(-300)"));
+        assert!(!ast.generate_code().is_empty());
+    }
+
+    #[test]
+    fn test_bounds_checks() {
+        // The analysis proves that the loop stays inside the memory, but not the scan to the left
+        let program = Program::new("++[->>+<<]>>[<]", true).unwrap();
+        let ast = AST::new(program, 1, 1 << 16).unwrap();
+        let checked = ast.generate(false, None).code.len();
+        let analysis = analyze(&ast, 16);
+        assert_eq!(analysis.checks(), (3, 1));
+        let unchecked = ast.generate(false, Some(&analysis)).code.len();
+        assert!(unchecked < checked);
+
+        let checks = ast.generate(false, Some(&analysis)).checks;
+        assert_eq!(checks.len(), 1);
+        assert_eq!((checks[0].node, checks[0].movement), (4, -1));
+        assert_eq!(checks[0].location.map(|location| location.column), Some(13));
+        let AST::Program(nodes) = &ast else {
+            panic!("Expected a program");
+        };
+        let AST::Loop(body, _) = &nodes[3] else {
+            panic!("Expected the scan loop");
+        };
+        assert_eq!(ast.position(&body[0]), Some((4, checks[0].location)));
+    }
+
+    #[test]
+    fn test_clear_loops() {
+        // Only odd steps reach zero from every value, [--] never terminates for odd values
+        let program = Program::new("[-][+++][--][++]", true).unwrap();
+        let ast = AST::new(program, 1, 1 << 16).unwrap();
+        assert_eq!(
+            ast.pretty_print(),
+            "set(0)\nset(0)\n[\n    - (2)\n]\n[\n    + (2)\n]\n"
+        );
+
+        // Nested programs are printed like their nodes
+        let nested = AST::Program(vec![AST::Program(vec![AST::Output])]);
+        assert_eq!(nested.pretty_print(), ".\n");
+    }
+
+    #[test]
+    fn test_invalid_program() {
+        let mut program = Program::new("+[-]", false).unwrap();
+        program.jump_table.clear();
+        assert!(matches!(
+            AST::new(program, 0, 1 << 16),
+            Err(ProgramError::UnclosedLoop { location, hint: None }) if location.column == 2
+        ));
     }
 }
@@ -33,6 +33,5 @@ pub mod code_generator;
 use crate::brainfuck::jit::codegen::code_generator::CodeGenerator;
 
 pub fn create_code_gen() -> Box<dyn CodeGenerator> {
-    #[cfg(target_arch = "aarch64")]
     Box::new(arm::ARM64CodeGenerator::new())
 }
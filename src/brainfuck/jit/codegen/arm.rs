@@ -30,13 +30,22 @@
 /// The AOT compiler generates a function that has the following parameters:
 ///
 /// 1. a function pointer the output function that expectes a u8 as its only parameter
-/// 2. a function pointer the input function that has not parameters and returns the U8 the user typed
+/// 2. a function pointer the input function that gets the current memory value and returns the U8
+///    the user typed (or the current value if there is no input left)
 /// 3. a pointer to the memory of the program
+/// 4. a pointer to the counter table (only used by instrumented code)
+/// 5. the size of the memory
+///
+/// It returns the final memory pointer in X0. If the program leaves the memory, it returns the
+/// pointer outside of the memory immediately and the number of the failed bounds check in X1.
 ///
 /// The AOT compiler uses the following local registers:
 /// X19 - output function
 /// X20 - input function
 /// X21 - memory pointer
+/// X22 - counter table
+/// X23 - start of the memory
+/// X24 - end of the memory
 use super::code_generator::CodeGenerator;
 use dynasm::dynasm;
 use dynasmrt::{Assembler, aarch64::Aarch64Relocation};
@@ -45,14 +54,50 @@ use dynasmrt::{DynamicLabel, DynasmApi, DynasmLabelApi, ExecutableBuffer};
 #[derive(Debug)]
 pub struct ARM64CodeGenerator {
     assembler: Assembler<Aarch64Relocation>,
+    // Start of the function epilog
+    exit: DynamicLabel,
+    // Labels of the failed bounds checks and their numbers
+    failed_checks: Vec<(DynamicLabel, usize)>,
 }
 
 impl ARM64CodeGenerator {
     pub fn new() -> Self {
+        let mut assembler = dynasmrt::aarch64::Assembler::new().unwrap();
+        let exit = assembler.new_dynamic_label();
         Self {
-            assembler: dynasmrt::aarch64::Assembler::new().unwrap(),
+            assembler,
+            exit,
+            failed_checks: vec![],
         }
     }
+
+    /// Label that returns from the function with the number of the failed {check}.
+    fn failed_check(&mut self, check: usize) -> DynamicLabel {
+        let label = self.assembler.new_dynamic_label();
+        self.failed_checks.push((label, check));
+        label
+    }
+
+    /// Return from the function if the memory pointer is outside of the memory.
+    fn check_memory_ptr(&mut self, check: usize) {
+        let failed = self.failed_check(check);
+        dynasm!(self.assembler
+            ; .arch aarch64
+            ; cmp x21, x23
+            ; b.lo => failed
+            ; cmp x21, x24
+            ; b.hs => failed
+        );
+    }
+
+    /// Load the 32 bit {value} into X14 for values that do not fit into an immediate.
+    fn load_x14(&mut self, value: u32) {
+        dynasm!(self.assembler
+            ; .arch aarch64
+            ; movz x14, value & 0xffff
+            ; movk x14, value >> 16, lsl 16
+        );
+    }
 }
 
 impl CodeGenerator for ARM64CodeGenerator {
@@ -60,28 +105,50 @@ impl CodeGenerator for ARM64CodeGenerator {
         self.assembler.finalize().unwrap()
     }
 
+    fn offset(&self) -> usize {
+        self.assembler.offset().0
+    }
+
     fn function_prolog(&mut self) {
         dynasm!(self.assembler
             ; .arch aarch64
-            ; stp x29, x30, [sp, -0x30]!
+            ; stp x29, x30, [sp, -0x40]!
             ; stp x19, x20, [sp, 0x10]
             ; stp x21, x22, [sp, 0x20]
+            ; stp x23, x24, [sp, 0x30]
             ; mov x29, sp
             ; mov x19, x0
             ; mov x20, x1
             ; mov x21, x2
-            ; mov x22, xzr
+            ; mov x22, x3
+            ; mov x23, x2
+            ; add x24, x2, x4
         );
     }
 
     fn function_epilog(&mut self) {
+        let exit = self.exit;
         dynasm!(self.assembler
             ; .arch aarch64
+            ; => exit
+            ; mov x0, x21
             ; ldp x19, x20, [sp, 0x10]
             ; ldp x21, x22, [sp, 0x20]
-            ; ldp x29, x30, [sp], 0x30
+            ; ldp x23, x24, [sp, 0x30]
+            ; ldp x29, x30, [sp], 0x40
             ; ret
         );
+        // The failed bounds checks are out of line so they do not slow down the checks
+        for (label, check) in std::mem::take(&mut self.failed_checks) {
+            let check = check as u32;
+            dynasm!(self.assembler
+                ; .arch aarch64
+                ; => label
+                ; movz x1, check & 0xffff
+                ; movk x1, check >> 16, lsl 16
+                ; b => exit
+            );
+        }
     }
 
     fn set(&mut self, value: u8) {
@@ -100,48 +167,106 @@ impl CodeGenerator for ARM64CodeGenerator {
         }
     }
 
-    fn add_to(&mut self, offset: i32) {
+    fn add_to(&mut self, offset: i32, check: Option<usize>) {
+        // The target cell is only accessed if there is something to add like the original loop
+        let skip = self.assembler.new_dynamic_label();
         dynasm!(self.assembler
             ; .arch aarch64
             ; ldrb w13, [x21]
-            ; ldurb w14, [x21, offset]
-            ; add w14, w14, w13
-            ; sturb w14, [x21, offset]
-            ; mov x13, #0
-            ; strb w13, [x21]
+            ; cbz w13, => skip
         );
-    }
-
-    fn update_value(&mut self, update: i32) {
-        if update < 0 {
+        if check.is_none() && (-256..256).contains(&offset) {
             dynasm!(self.assembler
                 ; .arch aarch64
-                ; ldrb w13, [x21]
-                ; sub w13, w13, -update as u32
-                ; strb w13, [x21]
+                ; ldurb w14, [x21, offset]
+                ; add w14, w14, w13
+                ; sturb w14, [x21, offset]
             );
         } else {
+            self.load_x14(offset.unsigned_abs());
+            if offset < 0 {
+                dynasm!(self.assembler
+                    ; .arch aarch64
+                    ; sub x14, x21, x14
+                );
+            } else {
+                dynasm!(self.assembler
+                    ; .arch aarch64
+                    ; add x14, x21, x14
+                );
+            }
+            if let Some(check) = check {
+                // Return the address of the target cell if it is outside of the memory
+                let outside = self.assembler.new_dynamic_label();
+                let inside = self.assembler.new_dynamic_label();
+                let failed = self.failed_check(check);
+                dynasm!(self.assembler
+                    ; .arch aarch64
+                    ; cmp x14, x23
+                    ; b.lo => outside
+                    ; cmp x14, x24
+                    ; b.lo => inside
+                    ; => outside
+                    ; mov x21, x14
+                    ; b => failed
+                    ; => inside
+                );
+            }
             dynasm!(self.assembler
                 ; .arch aarch64
-                ; ldrb w13, [x21]
-                ; add w13, w13, update as u32
-                ; strb w13, [x21]
+                ; ldrb w15, [x14]
+                ; add w15, w15, w13
+                ; strb w15, [x14]
             );
-        };
+        }
+        dynasm!(self.assembler
+            ; .arch aarch64
+            ; strb wzr, [x21]
+            ; => skip
+        );
+    }
+
+    fn update_value(&mut self, update: i32) {
+        // The cells wrap around so every update is an addition of less than 256
+        let update = update.rem_euclid(256) as u32;
+        dynasm!(self.assembler
+            ; .arch aarch64
+            ; ldrb w13, [x21]
+            ; add w13, w13, update
+            ; strb w13, [x21]
+        );
     }
 
-    fn update_memory_ptr(&mut self, update: i32) {
-        if update < 0 {
+    fn update_memory_ptr(&mut self, update: i32, check: Option<usize>) {
+        let amount = update.unsigned_abs();
+        if 0x1000 <= amount {
+            // Too large for an immediate
+            self.load_x14(amount);
+            if update < 0 {
+                dynasm!(self.assembler
+                    ; .arch aarch64
+                    ;  sub x21, x21, x14
+                );
+            } else {
+                dynasm!(self.assembler
+                    ; .arch aarch64
+                    ;  add x21, x21, x14
+                );
+            }
+        } else if update < 0 {
             dynasm!(self.assembler
                 ; .arch aarch64
-                ;  sub x21, x21, -update as u32
+                ;  sub x21, x21, amount
             );
         } else {
             dynasm!(self.assembler
                 ; .arch aarch64
-                ;  add x21, x21, update as u32
+                ;  add x21, x21, amount
             );
         };
+        if let Some(check) = check {
+            self.check_memory_ptr(check);
+        }
     }
 
     fn output(&mut self) {
@@ -156,7 +281,8 @@ impl CodeGenerator for ARM64CodeGenerator {
     fn input(&mut self) {
         dynasm!(self.assembler
             ; .arch aarch64
-            ;  blr x20
+            ; ldrb w0, [x21]
+            ; blr x20
             ; strb w0, [x21]
         );
     }
@@ -179,4 +305,14 @@ impl CodeGenerator for ARM64CodeGenerator {
             ; => loop_end
         );
     }
+
+    fn increment_counter(&mut self, counter: usize) {
+        self.load_x14((counter * 8) as u32);
+        dynasm!(self.assembler
+            ; .arch aarch64
+            ; ldr x13, [x22, x14]
+            ; add x13, x13, 1
+            ; str x13, [x22, x14]
+        );
+    }
 }
@@ -33,6 +33,9 @@ use std::any::Any;
 pub trait CodeGenerator: Any + 'static {
     fn finalize(self: Box<Self>) -> ExecutableBuffer;
 
+    /// Offset of the next instruction from the start of the generated code.
+    fn offset(&self) -> usize;
+
     // Core operations necessary for brainfuck
     fn function_prolog(&mut self);
     fn function_epilog(&mut self);
@@ -47,8 +50,10 @@ pub trait CodeGenerator: Any + 'static {
     /// Call the output function with the current memory cell as parameter.
     fn output(&mut self);
 
-    /// Update the memory pointer address by adding the {update} value.
-    fn update_memory_ptr(&mut self, update: i32);
+    /// Update the memory pointer address by adding the {update} value. If {check} is set, the
+    /// function returns the new memory pointer and the number of the {check} if it is outside of
+    /// the memory.
+    fn update_memory_ptr(&mut self, update: i32, check: Option<usize>);
 
     /// Update the current memory value by adding the {update} value.
     fn update_value(&mut self, update: i32);
@@ -59,6 +64,13 @@ pub trait CodeGenerator: Any + 'static {
     fn set(&mut self, value: u8);
 
     /// Takes the current memory cell value and adds it to the memory cell {offset} bytes away.
-    /// After that the current memory cell will be set to 0.
-    fn add_to(&mut self, offset: i32);
+    /// After that the current memory cell will be set to 0. If {check} is set, the function
+    /// returns the address of the other cell and the number of the {check} if it is outside of
+    /// the memory.
+    fn add_to(&mut self, offset: i32, check: Option<usize>);
+
+    // Instrumentation
+
+    /// Increment the 64 bit {counter} of the counter table.
+    fn increment_counter(&mut self, counter: usize);
 }
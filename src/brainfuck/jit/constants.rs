@@ -0,0 +1,310 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Constant propagation over the AST.
+//!
+//! Every cell starts with zero, so the values of many cells are known at compile time. The pass
+//! follows the known values through the program, removes loops that are never entered and
+//! replaces updates of known values with `Set` nodes. The start of a program that reads no input
+//! can even be evaluated completely at compile time.
+use std::collections::HashMap;
+
+use super::ast::AST;
+use super::evaluator::evaluate;
+
+// Limits for evaluating the start of the program at compile time. Smaller memories are used as
+// they are.
+const PRECOMPUTE_MEMORY: usize = 1 << 16;
+const PRECOMPUTE_STEPS: usize = 1 << 16;
+
+/// Known values of the cells relative to the data pointer at the start of the block.
+struct Constants {
+    data_ptr: isize,
+    /// Values of the cells by offset (None if unknown)
+    cells: HashMap<isize, Option<u8>>,
+    /// Value of the cells that are not in {cells}
+    default: Option<u8>,
+}
+
+impl Constants {
+    fn unknown() -> Self {
+        Constants {
+            data_ptr: 0,
+            cells: HashMap::new(),
+            default: None,
+        }
+    }
+
+    fn get(&self, offset: isize) -> Option<u8> {
+        self.cells.get(&offset).copied().unwrap_or(self.default)
+    }
+
+    fn current(&self) -> Option<u8> {
+        self.get(self.data_ptr)
+    }
+
+    fn set_current(&mut self, value: Option<u8>) {
+        self.cells.insert(self.data_ptr, value);
+    }
+}
+
+impl AST {
+    /// Remove loops that are never entered and replace updates of known cell values with `Set`
+    /// nodes. The start of the program that reads no input is replaced with its output and the
+    /// resulting memory, if it stays inside the memory of {memory_size} cells.
+    pub fn propagate_constants(self, memory_size: usize) -> Self {
+        match self {
+            AST::Program(nodes) => {
+                let nodes = AST::precompute_prefix(nodes, memory_size);
+                let mut constants = Constants {
+                    default: Some(0),
+                    ..Constants::unknown()
+                };
+                AST::Program(AST::propagate_block(nodes, &mut constants, false))
+            }
+            _ => self,
+        }
+    }
+
+    /// Remove loops that are never entered and updates that do not change the known value. Unlike
+    /// `propagate_constants` the updates stay increments and decrements where possible, which
+    /// keeps the program short as source code.
+    pub fn remove_dead_code(self) -> Self {
+        match self {
+            AST::Program(nodes) => {
+                let mut constants = Constants {
+                    default: Some(0),
+                    ..Constants::unknown()
+                };
+                AST::Program(AST::propagate_block(nodes, &mut constants, true))
+            }
+            _ => self,
+        }
+    }
+
+    /// Propagate the known values through the {nodes}. Updates of known values become `Set` nodes
+    /// or, if {relative} is set, the shortest increment or decrement.
+    fn propagate_block(nodes: Vec<AST>, constants: &mut Constants, relative: bool) -> Vec<AST> {
+        let mut result: Vec<AST> = vec![];
+        // Value of the current cell before the last `Set` node in {result}
+        let mut before_set = None;
+        for node in nodes {
+            let value = match node {
+                AST::IncValue(count) => constants
+                    .current()
+                    .map(|value| value.wrapping_add(count as u8)),
+                AST::DecValue(count) => constants
+                    .current()
+                    .map(|value| value.wrapping_sub(count as u8)),
+                AST::Set(value) => Some(value),
+                _ => None,
+            };
+            match node {
+                AST::IncValue(_) | AST::DecValue(_) | AST::Set(_) if value.is_some() => {
+                    let previous = constants.current();
+                    if previous == value {
+                        continue;
+                    }
+                    constants.set_current(value);
+                    let value = value.unwrap();
+                    // A value that is overwritten right away needs no update
+                    let previous = match result.last() {
+                        Some(AST::Set(_)) => {
+                            result.pop();
+                            before_set
+                        }
+                        _ => previous,
+                    };
+                    match previous {
+                        Some(previous) if previous == value => (),
+                        // Clearing the cell with `[-]` might be shorter than the update
+                        Some(previous)
+                            if relative && distance(previous, value) <= 3 + distance(0, value) =>
+                        {
+                            result.push(AST::value_update(value.wrapping_sub(previous)))
+                        }
+                        _ => {
+                            before_set = previous;
+                            result.push(AST::Set(value));
+                        }
+                    }
+                    continue;
+                }
+                AST::IncValue(_) | AST::DecValue(_) | AST::Input => constants.set_current(None),
+                AST::IncDataPtr(count) => constants.data_ptr += count as isize,
+                AST::DecDataPtr(count) => constants.data_ptr -= count as isize,
+                AST::AddTo(offset) => {
+                    let source = constants.current();
+                    if source == Some(0) {
+                        continue;
+                    }
+                    let target = constants.data_ptr + offset;
+                    let sum = source
+                        .zip(constants.get(target))
+                        .map(|(source, target)| source.wrapping_add(target));
+                    constants.cells.insert(target, sum);
+                    constants.set_current(Some(0));
+                }
+                AST::Loop(body, location) => {
+                    if constants.current() == Some(0) {
+                        continue;
+                    }
+                    // Every iteration might start with different values and change every cell
+                    let body = AST::propagate_block(body, &mut Constants::unknown(), relative);
+                    *constants = Constants::unknown();
+                    constants.set_current(Some(0));
+                    result.push(AST::Loop(body, location));
+                    continue;
+                }
+                AST::Output | AST::Set(_) | AST::Program(_) => (),
+            }
+            result.push(node);
+        }
+        result
+    }
+
+    /// Evaluate the {nodes} up to the first node that reads input and replace them with nodes that
+    /// write the same output and set the cells to the resulting values.
+    fn precompute_prefix(mut nodes: Vec<AST>, memory_size: usize) -> Vec<AST> {
+        let length = nodes
+            .iter()
+            .position(AST::reads_input)
+            .unwrap_or(nodes.len());
+        let rest = nodes.split_off(length);
+        let prefix = AST::Program(nodes);
+        let memory_size = memory_size.min(PRECOMPUTE_MEMORY);
+        let evaluation = match evaluate(&prefix, "", memory_size, PRECOMPUTE_STEPS) {
+            Ok(evaluation) if evaluation.finished => evaluation,
+            // Programs leaving the memory or taking too long are not changed
+            _ => {
+                let AST::Program(mut nodes) = prefix else {
+                    unreachable!()
+                };
+                nodes.extend(rest);
+                return nodes;
+            }
+        };
+
+        // The first cell holds the output values before it gets its final value
+        let mut nodes = vec![];
+        for value in evaluation.output.chars() {
+            nodes.push(AST::Set(value as u8));
+            nodes.push(AST::Output);
+        }
+        let mut position = 0;
+        for (address, value) in evaluation.memory.iter().enumerate() {
+            if *value != 0 || (address == 0 && !evaluation.output.is_empty()) {
+                AST::move_data_ptr(&mut nodes, position, address);
+                nodes.push(AST::Set(*value));
+                position = address;
+            }
+        }
+        AST::move_data_ptr(&mut nodes, position, evaluation.data_ptr);
+        nodes.extend(rest);
+        nodes
+    }
+
+    fn move_data_ptr(nodes: &mut Vec<AST>, from: usize, to: usize) {
+        if from < to {
+            nodes.push(AST::IncDataPtr(to - from));
+        } else if to < from {
+            nodes.push(AST::DecDataPtr(from - to));
+        }
+    }
+
+    fn reads_input(&self) -> bool {
+        match self {
+            AST::Input => true,
+            AST::Loop(body, _) | AST::Program(body) => body.iter().any(AST::reads_input),
+            _ => false,
+        }
+    }
+}
+
+/// Number of increments or decrements that change {from} into {to}.
+fn distance(from: u8, to: u8) -> u8 {
+    let delta = to.wrapping_sub(from);
+    delta.min(delta.wrapping_neg())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck::vm::program::Program;
+
+    fn propagate(code: &str) -> AST {
+        AST::new(Program::new(code, true).unwrap(), 2, 1 << 16).unwrap()
+    }
+
+    #[test]
+    fn test_propagation() {
+        // The first loop is never entered, the updates are folded into a single set and the set
+        // to zero in the loop is overwritten right away
+        let ast = propagate(",>[-.]++--+<[[-]+++.>]>[.]");
+        assert_eq!(
+            ast.pretty_print(),
+            ",\n> (1)\nset(1)\n< (1)\n[\n    set(3)\n    .\n    > (1)\n]\n> (1)\n[\n    .\n]\n"
+        );
+
+        // After the add-to loop both cells are known
+        let ast = propagate(",[-]+++[->+<]>[-]<[.]");
+        assert_eq!(
+            ast.pretty_print(),
+            ",\nset(3)\naddto(1)\n> (1)\nset(0)\n< (1)\n"
+        );
+    }
+
+    #[test]
+    fn test_precompute() {
+        let ast = propagate("++++++++[>++++++++<-]>+.+.");
+        assert_eq!(
+            ast.pretty_print(),
+            "set(65)\n.\nset(66)\n.\nset(0)\n> (1)\nset(66)\n"
+        );
+
+        // The part after the first input is not evaluated
+        let ast = propagate("+++.,.");
+        assert_eq!(ast.pretty_print(), "set(3)\n.\n,\n.\n");
+
+        // Programs that do not finish stay as they are
+        let ast = propagate("+[>+]");
+        assert_eq!(ast.pretty_print(), "set(1)\n[\n    > (1)\n    + (1)\n]\n");
+
+        let ast = propagate("->>>><<");
+        assert_eq!(ast.pretty_print(), "set(255)\n> (2)\n");
+
+        // Programs that leave the memory stay as they are and print nothing beyond the fault
+        let code = "+.>>>>>>+.";
+        let ast = AST::new(Program::new(code, true).unwrap(), 2, 4).unwrap();
+        assert_eq!(ast.pretty_print(), "set(1)\n.\n> (6)\nset(1)\n.\n");
+        assert!(evaluate(&ast, "", 4, 100).is_err());
+        let ast = AST::new(Program::new(code, true).unwrap(), 2, 8).unwrap();
+        assert_eq!(ast.pretty_print(), "set(1)\n.\n.\n> (6)\nset(1)\n");
+    }
+}
@@ -0,0 +1,215 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Reference interpreter for the AST.
+//!
+//! It executes the optimized nodes directly, which allows checking the optimizations independent
+//! of the code generation (e.g., on hosts without a JIT backend).
+use std::str::Chars;
+
+use super::super::{
+    jit::ast::AST,
+    vm::{fault::Fault, vm::EvalError},
+};
+
+/// State of the program after the evaluation.
+#[derive(Debug, PartialEq)]
+pub struct Evaluation {
+    pub memory: Vec<u8>,
+    pub data_ptr: usize,
+    pub output: String,
+    /// Number of evaluated nodes including every loop condition check
+    pub steps: usize,
+    /// False if the evaluation stopped at the step limit
+    pub finished: bool,
+}
+
+struct Evaluator<'a> {
+    /// The evaluated program to locate failed nodes
+    ast: &'a AST,
+    memory: Vec<u8>,
+    data_ptr: usize,
+    input: Chars<'a>,
+    output: String,
+    steps: usize,
+    step_limit: usize,
+}
+
+/// Evaluate the program {ast} with the given {input} and stop after {step_limit} steps.
+///
+/// Reading past the end of the input keeps the current cell unchanged like the other engines.
+pub fn evaluate(
+    ast: &AST,
+    input: &str,
+    memory_size: usize,
+    step_limit: usize,
+) -> Result<Evaluation, EvalError> {
+    let mut evaluator = Evaluator {
+        ast,
+        memory: vec![0; memory_size],
+        data_ptr: 0,
+        input: input.chars(),
+        output: String::new(),
+        steps: 0,
+        step_limit,
+    };
+    let finished = evaluator.evaluate(ast)?;
+    Ok(Evaluation {
+        memory: evaluator.memory,
+        data_ptr: evaluator.data_ptr,
+        output: evaluator.output,
+        steps: evaluator.steps,
+        finished,
+    })
+}
+
+impl Evaluator<'_> {
+    /// Evaluate the {node} and return false if the step limit was reached.
+    fn evaluate(&mut self, node: &AST) -> Result<bool, EvalError> {
+        if let AST::Program(block) = node {
+            return self.evaluate_block(block);
+        }
+        if self.steps == self.step_limit {
+            return Ok(false);
+        }
+        self.steps += 1;
+        match node {
+            AST::DecDataPtr(count) => self.data_ptr = self.address(node, -(*count as isize))?,
+            AST::IncDataPtr(count) => self.data_ptr = self.address(node, *count as isize)?,
+            AST::DecValue(count) => {
+                self.memory[self.data_ptr] = self.memory[self.data_ptr].wrapping_sub(*count as u8)
+            }
+            AST::IncValue(count) => {
+                self.memory[self.data_ptr] = self.memory[self.data_ptr].wrapping_add(*count as u8)
+            }
+            AST::Input => {
+                if let Some(value) = self.input.next() {
+                    self.memory[self.data_ptr] = value as u8;
+                }
+            }
+            AST::Output => self.output.push(self.memory[self.data_ptr] as char),
+            AST::Set(value) => self.memory[self.data_ptr] = *value,
+            AST::AddTo(offset) => {
+                let value = self.memory[self.data_ptr];
+                // Like the original loop the target is only accessed if there is something to add
+                if value != 0 {
+                    let target = self.address(node, *offset)?;
+                    self.memory[target] = self.memory[target].wrapping_add(value);
+                    self.memory[self.data_ptr] = 0;
+                }
+            }
+            AST::Loop(block, _) => {
+                while self.memory[self.data_ptr] != 0 {
+                    if !self.evaluate_block(block)? {
+                        return Ok(false);
+                    }
+                    if self.steps == self.step_limit {
+                        return Ok(false);
+                    }
+                    self.steps += 1;
+                }
+            }
+            AST::Program(_) => unreachable!("Programs are evaluated as blocks"),
+        }
+        Ok(true)
+    }
+
+    fn evaluate_block(&mut self, block: &[AST]) -> Result<bool, EvalError> {
+        for node in block {
+            if !self.evaluate(node)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Address of the cell {offset} cells away from the data pointer accessed by the {node}.
+    fn address(&self, node: &AST, offset: isize) -> Result<usize, EvalError> {
+        self.data_ptr
+            .checked_add_signed(offset)
+            .filter(|address| *address < self.memory.len())
+            .ok_or_else(|| {
+                let (index, location) = self.ast.position(node).unwrap_or_default();
+                EvalError::DataPointerOutOfBounds(Box::new(Fault::node(
+                    index,
+                    location,
+                    self.data_ptr,
+                    offset,
+                    &self.memory,
+                )))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck::jit::ast::MAX_OPT_LEVEL;
+    use crate::brainfuck::vm::program::Program;
+
+    fn evaluate_code(code: &str, opt_level: u8, step_limit: usize) -> Evaluation {
+        let ast = AST::new(Program::new(code, true).unwrap(), opt_level, 1 << 16).unwrap();
+        evaluate(&ast, "A", 16, step_limit).unwrap()
+    }
+
+    #[test]
+    fn test_optimizations() {
+        // Clear loops, add-to loops and even clear steps that never reach zero
+        let code = ",[->>+<<]+++[-]>>.<<+++++[--]";
+        let plain = evaluate_code(code, 0, 10_000);
+        let optimized = evaluate_code(code, MAX_OPT_LEVEL, 10_000);
+        assert!(!plain.finished);
+        assert!(!optimized.finished);
+        assert_eq!(plain.output, "A");
+        assert_eq!(optimized.output, "A");
+
+        let code = ",[->>+<<]+++[-]>>.<<";
+        let plain = evaluate_code(code, 0, 10_000);
+        let optimized = evaluate_code(code, MAX_OPT_LEVEL, 10_000);
+        assert!(plain.finished);
+        assert!(optimized.steps < plain.steps);
+        assert_eq!(
+            (optimized.memory, optimized.data_ptr, optimized.output),
+            (plain.memory, plain.data_ptr, plain.output)
+        );
+
+        let ast = AST::new(
+            Program::new("+[-<+>]", true).unwrap(),
+            MAX_OPT_LEVEL,
+            1 << 16,
+        )
+        .unwrap();
+        let Err(EvalError::DataPointerOutOfBounds(fault)) = evaluate(&ast, "", 16, 100) else {
+            panic!("Expected the data pointer to leave the memory");
+        };
+        // The add-to node has no loop around it
+        assert_eq!((fault.ip, fault.data_ptr, fault.movement), (1, 0, -1));
+        assert_eq!(fault.location, None);
+    }
+}
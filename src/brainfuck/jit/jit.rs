@@ -27,62 +27,146 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::io::{self, Write};
 use std::{
-    io::{stdin, Read},
+    io::{Read, stdin},
     mem,
     time::Instant,
 };
 
+use log::info;
+
 use dynasmrt::ExecutableBuffer;
 
 use super::super::{
+    jit::analysis::{Analysis, analyze},
     jit::ast::AST,
-    vm::{program::Program, vm::EvalError},
+    jit::perf::{PerfOptions, perf_symbols, write_jitdump, write_perf_map},
+    vm::{
+        fault::Fault,
+        program::{Program, SourceLocation},
+        vm::EvalError,
+    },
 };
 
 extern "C" fn output(value: libc::c_char) {
     let mut stdout = io::stdout();
-    let _ = stdout.write(&[value as u8]);
+    let _ = stdout.write(&[value]);
 }
 
-extern "C" fn input() -> libc::c_char {
+// The input function gets the current cell value and returns it unchanged at the end of the input.
+extern "C" fn input(current: libc::c_char) -> libc::c_char {
     let mut one_byte = [0];
-    stdin().read_exact(&mut one_byte).unwrap();
+    if stdin().read_exact(&mut one_byte).is_err() {
+        return current;
+    }
     match one_byte[0] as char {
-        '\n' => {
-            return 0x0 as libc::c_char;
-        }
-        c => {
-            return c as libc::c_char;
-        }
+        '\n' => 0x0 as libc::c_char,
+        c => c as libc::c_char,
     }
 }
 
+thread_local! {
+    // Remaining input and output so far of a program executed with captured I/O
+    static CAPTURED_IO: RefCell<(VecDeque<char>, String)> =
+        const { RefCell::new((VecDeque::new(), String::new())) };
+}
+
+extern "C" fn captured_output(value: libc::c_char) {
+    CAPTURED_IO.with_borrow_mut(|(_, output)| output.push(value as char));
+}
+
+extern "C" fn captured_input(current: libc::c_char) -> libc::c_char {
+    CAPTURED_IO.with_borrow_mut(|(input, _)| {
+        input
+            .pop_front()
+            .map_or(current, |value| value as u8 as libc::c_char)
+    })
+}
+
+// Values returned by the generated assembly code
+#[repr(C)]
+struct JITResult {
+    memory_ptr: *const libc::c_char,
+    // Number of the failed bounds check if the memory pointer is outside of the memory
+    check: usize,
+}
+
 // Function type of the generated assembly code
 type JITFunction = unsafe extern "C" fn(
     extern "C" fn(libc::c_char) -> (),
-    extern "C" fn() -> libc::c_char,
+    extern "C" fn(libc::c_char) -> libc::c_char,
     *const libc::c_char,
-) -> ();
+    *mut u64,
+    usize,
+) -> JITResult;
 
+/// State of the program after the generated code finished.
+#[derive(Debug)]
+pub struct Execution {
+    pub memory: Vec<u8>,
+    pub data_ptr: usize,
+    /// Counters of every loop in source order (only for instrumented code)
+    pub counters: Vec<LoopCounter>,
+    /// Output of the program if the I/O was captured
+    pub output: String,
+    /// Details if the program stopped because it left the memory
+    pub fault: Option<Box<Fault>>,
+}
+
+/// Execution counts of a loop collected by the instrumented code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopCounter {
+    pub location: SourceLocation,
+    /// Number of times the loop header was reached
+    pub entries: u64,
+    /// Number of times the back-edge was taken
+    pub iterations: u64,
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub struct JIT {
     program_ast: AST,
     memory_size: usize,
+    /// Bounds checks are only generated where the analysis cannot prove they are unnecessary
+    analysis: Option<Analysis>,
+    perf: PerfOptions,
 }
 
 impl JIT {
-    pub fn new(code: &str, rle: bool, memory_size: usize) -> Result<Self, EvalError> {
+    pub fn new(
+        code: &str,
+        rle: bool,
+        memory_size: usize,
+        opt_level: u8,
+    ) -> Result<Self, EvalError> {
+        JIT::with_program(Program::new(code, rle)?, memory_size, opt_level)
+    }
+
+    /// Create a JIT for an already parsed program, e.g., one with locations in the macro source.
+    pub fn with_program(
+        program: Program,
+        memory_size: usize,
+        opt_level: u8,
+    ) -> Result<Self, EvalError> {
         let now = Instant::now();
-        let program = Program::new(code, rle)?;
-        let program_ast = AST::new(program);
+        let program_ast = AST::new(program, opt_level, memory_size)?;
+        let analysis = (0 < opt_level).then(|| analyze(&program_ast, memory_size));
         let elapsed = now.elapsed();
-        println!("Parsing time: {:.2?}", elapsed);
+        info!("Parsing time: {:.2?}", elapsed);
+        if let Some(analysis) = &analysis {
+            let (accesses, checked) = analysis.checks();
+            info!("Bounds checks: {} of {} accesses", checked, accesses);
+        }
 
         Ok(JIT {
             program_ast,
             memory_size,
+            analysis,
+            perf: PerfOptions::default(),
         })
     }
 
@@ -91,16 +175,102 @@ impl JIT {
     }
 
     pub fn generate_code(&self) -> ExecutableBuffer {
-        self.program_ast.generate_code()
+        self.program_ast
+            .generate(false, self.analysis.as_ref())
+            .code
+    }
+
+    /// Run the program. With {input} the I/O is captured instead of using the console. The
+    /// execution reports whether the program left the memory instead of returning an error.
+    pub fn run(&self, input: Option<&str>) -> Result<Execution, EvalError> {
+        self.execute(false, input)
     }
 
-    pub fn run(&self) -> Vec<u8> {
-        let assembly_code = self.generate_code();
+    /// Run instrumented code that counts the entries and iterations of every loop. The counters
+    /// of the execution are in source order.
+    pub fn run_profiled(&self, input: Option<&str>) -> Result<Execution, EvalError> {
+        self.execute(true, input)
+    }
+
+    /// Describe the generated code to `perf` when the code gets executed.
+    pub fn enable_perf(&mut self, perf: PerfOptions) {
+        self.perf = perf;
+    }
+
+    /// Generate and run the code. With {input} the I/O is captured instead of using the console.
+    fn execute(&self, instrument: bool, input: Option<&str>) -> Result<Execution, EvalError> {
+        let generated = self
+            .program_ast
+            .generate(instrument, self.analysis.as_ref());
+        let assembly_code = &generated.code;
+
+        if self.perf.enabled() {
+            let base = assembly_code.as_ptr() as usize;
+            let symbols = perf_symbols(assembly_code.len(), &generated.symbols);
+            if self.perf.map {
+                write_perf_map(base, &symbols)?;
+            }
+            if self.perf.jitdump {
+                write_jitdump(base, assembly_code, &symbols)?;
+            }
+        }
 
         let callback: JITFunction = unsafe { mem::transmute(assembly_code.as_ptr()) };
         let memory = vec![0; self.memory_size];
+        let mut counters = vec![0u64; 2 * generated.loops.len()];
+        let (output_function, input_function): (extern "C" fn(_), extern "C" fn(_) -> _) =
+            match input {
+                Some(value) => {
+                    CAPTURED_IO.set((value.chars().collect(), String::new()));
+                    (captured_output, captured_input)
+                }
+                None => (output, self::input),
+            };
 
-        unsafe { callback(output, input, memory.as_ptr() as *const libc::c_char) };
-        memory
+        // The generated code returns the final memory pointer
+        let result = unsafe {
+            callback(
+                output_function,
+                input_function,
+                memory.as_ptr() as *const libc::c_char,
+                counters.as_mut_ptr(),
+                memory.len(),
+            )
+        };
+        // The generated code returns a pointer outside of the memory if the program left it
+        let offset = (result.memory_ptr as usize).wrapping_sub(memory.as_ptr() as usize);
+        let (data_ptr, fault) = if offset < memory.len() {
+            (offset, None)
+        } else {
+            // The failed node moved the data pointer or accessed the cell outside of the memory
+            let check = &generated.checks[result.check];
+            let data_ptr = offset.wrapping_sub(check.movement as usize);
+            let fault = Fault::node(
+                check.node,
+                check.location,
+                data_ptr,
+                check.movement,
+                &memory,
+            );
+            (data_ptr, Some(Box::new(fault)))
+        };
+        let captured = CAPTURED_IO.with_borrow_mut(|(_, output)| mem::take(output));
+        let counters = generated
+            .loops
+            .into_iter()
+            .zip(counters.chunks(2))
+            .map(|(location, counter)| LoopCounter {
+                location,
+                entries: counter[0],
+                iterations: counter[1],
+            })
+            .collect();
+        Ok(Execution {
+            memory,
+            data_ptr,
+            counters,
+            output: captured,
+            fault,
+        })
     }
 }
@@ -0,0 +1,231 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Describe the generated code to Linux `perf`.
+//!
+//! The perf map (`/tmp/perf-<pid>.map`) is picked up by `perf report` directly. The jitdump file
+//! (`/tmp/jit-<pid>.dump`) has to be merged into the recording with `perf inject --jit`; it
+//! also contains the code itself, which allows `perf annotate` on brainfuck loops.
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::process;
+
+use super::ast::CodeSymbol;
+
+// Jitdump constants, see tools/perf/Documentation/jitdump-specification.txt of the Linux kernel
+const JITDUMP_MAGIC: u32 = 0x4A695444;
+const JITDUMP_VERSION: u32 = 1;
+const JITDUMP_HEADER_SIZE: u32 = 40;
+const JITDUMP_CODE_LOAD: u32 = 0;
+const EM_AARCH64: u32 = 183;
+
+/// Selected perf outputs.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PerfOptions {
+    pub map: bool,
+    pub jitdump: bool,
+}
+
+impl PerfOptions {
+    pub fn enabled(&self) -> bool {
+        self.map || self.jitdump
+    }
+}
+
+/// Named code range relative to the start of the generated code.
+#[derive(Debug, PartialEq)]
+pub struct PerfSymbol {
+    pub start: usize,
+    pub size: usize,
+    pub name: String,
+}
+
+/// Cover the complete code with symbols. Every top-level loop gets its own symbol named after
+/// its source location, the code in between is attributed to `brainfuck::main`.
+pub fn perf_symbols(code_size: usize, symbols: &[CodeSymbol]) -> Vec<PerfSymbol> {
+    let mut result = vec![];
+    let mut position = 0;
+    let main = |start: usize, end: usize| PerfSymbol {
+        start,
+        size: end - start,
+        name: "brainfuck::main".to_string(),
+    };
+    symbols
+        .iter()
+        .filter(|symbol| symbol.size > 0)
+        .for_each(|symbol| {
+            if position < symbol.start {
+                result.push(main(position, symbol.start));
+            }
+            result.push(PerfSymbol {
+                start: symbol.start,
+                size: symbol.size,
+                name: format!(
+                    "brainfuck::loop@{}:{}",
+                    symbol.location.line, symbol.location.column
+                ),
+            });
+            position = symbol.start + symbol.size;
+        });
+    if position < code_size {
+        result.push(main(position, code_size));
+    }
+    result
+}
+
+/// Write `/tmp/perf-<pid>.map` for the code loaded at {base}.
+pub fn write_perf_map(base: usize, symbols: &[PerfSymbol]) -> Result<PathBuf, io::Error> {
+    let path = PathBuf::from(format!("/tmp/perf-{}.map", process::id()));
+    let content = symbols
+        .iter()
+        .map(|symbol| {
+            format!(
+                "{:x} {:x} {}\n",
+                base + symbol.start,
+                symbol.size,
+                symbol.name
+            )
+        })
+        .collect::<String>();
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+fn timestamp() -> u64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
+
+/// Write `/tmp/jit-<pid>.dump` with one code load record per symbol for the {code} loaded at
+/// {base}. The file gets mapped into the process as executable so `perf record` notices it.
+pub fn write_jitdump(
+    base: usize,
+    code: &[u8],
+    symbols: &[PerfSymbol],
+) -> Result<PathBuf, io::Error> {
+    let pid = process::id();
+    let path = PathBuf::from(format!("/tmp/jit-{}.dump", pid));
+    let file = File::create(&path)?;
+
+    // perf finds the jitdump file through the mmap event of this mapping. It has to stay mapped
+    // until the process terminates.
+    let mapping = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            libc::sysconf(libc::_SC_PAGESIZE) as usize,
+            libc::PROT_READ | libc::PROT_EXEC,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if mapping == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&JITDUMP_MAGIC.to_ne_bytes())?;
+    writer.write_all(&JITDUMP_VERSION.to_ne_bytes())?;
+    writer.write_all(&JITDUMP_HEADER_SIZE.to_ne_bytes())?;
+    writer.write_all(&EM_AARCH64.to_ne_bytes())?;
+    writer.write_all(&0u32.to_ne_bytes())?;
+    writer.write_all(&pid.to_ne_bytes())?;
+    writer.write_all(&timestamp().to_ne_bytes())?;
+    writer.write_all(&0u64.to_ne_bytes())?;
+
+    symbols
+        .iter()
+        .enumerate()
+        .try_for_each(|(index, symbol)| -> Result<(), io::Error> {
+            let address = (base + symbol.start) as u64;
+            // record header + load fields + name with terminating zero + code
+            let size = 16 + 40 + symbol.name.len() + 1 + symbol.size;
+            writer.write_all(&JITDUMP_CODE_LOAD.to_ne_bytes())?;
+            writer.write_all(&(size as u32).to_ne_bytes())?;
+            writer.write_all(&timestamp().to_ne_bytes())?;
+            writer.write_all(&pid.to_ne_bytes())?;
+            writer.write_all(&pid.to_ne_bytes())?;
+            writer.write_all(&address.to_ne_bytes())?;
+            writer.write_all(&address.to_ne_bytes())?;
+            writer.write_all(&(symbol.size as u64).to_ne_bytes())?;
+            writer.write_all(&(index as u64).to_ne_bytes())?;
+            writer.write_all(symbol.name.as_bytes())?;
+            writer.write_all(&[0])?;
+            writer.write_all(&code[symbol.start..symbol.start + symbol.size])
+        })?;
+    writer.flush()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::vm::program::SourceLocation;
+    use super::*;
+
+    #[test]
+    fn test_perf_symbols() {
+        let location = |line, column| SourceLocation {
+            offset: 0,
+            line,
+            column,
+        };
+        let symbols = vec![
+            CodeSymbol {
+                start: 8,
+                size: 16,
+                location: location(1, 3),
+            },
+            CodeSymbol {
+                start: 24,
+                size: 8,
+                location: location(2, 1),
+            },
+        ];
+        let perf = perf_symbols(40, &symbols);
+        let names = perf
+            .iter()
+            .map(|symbol| (symbol.start, symbol.size, symbol.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                (0, 8, "brainfuck::main"),
+                (8, 16, "brainfuck::loop@1:3"),
+                (24, 8, "brainfuck::loop@2:1"),
+                (32, 8, "brainfuck::main"),
+            ]
+        );
+    }
+}
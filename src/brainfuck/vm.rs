@@ -28,6 +28,13 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
 pub mod debugger;
+pub mod diagnostic;
+pub mod fault;
+pub mod memory_profile;
 pub mod opcode;
+pub mod preprocessor;
+pub mod profiler;
 pub mod program;
+pub mod snapshot;
+#[allow(clippy::module_inception)]
 pub mod vm;
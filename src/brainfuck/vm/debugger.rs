@@ -28,26 +28,81 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
 use std::cmp::min;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, VecDeque};
+use std::path::Path;
 
+use super::super::io::base::{Stdin, Stdout};
 use super::super::io::console::{StdinConsole, StdoutConsole};
 use super::super::vm::vm::EvalError;
 use super::super::vm::vm::Normal;
+use super::opcode::OpCode;
+use super::program::Program;
+use super::snapshot::{Snapshot, SnapshotError};
 use super::vm::VM;
 
+// Maximum number of steps that can be undone
+const HISTORY_SIZE: usize = 1 << 16;
+
+/// State before an executed step. A step modifies at most the cell the data pointer points to.
+#[derive(Debug)]
+struct HistoryEntry {
+    ip: usize,
+    data_ptr: usize,
+    cell: u8,
+    steps: usize,
+    // Number of characters consumed before an input step
+    input: Option<usize>,
+}
+
 #[derive(Debug)]
 pub struct Debugger {
     vm: VM<Normal>,
     breakpoints: BTreeSet<usize>,
+    history: VecDeque<HistoryEntry>,
 }
 
 impl Debugger {
     pub fn new(code: &str, rle: bool, memory_size: usize) -> Result<Self, EvalError> {
         let stdin = StdinConsole::new(Some("INPUT: ".to_string()));
         let stdout = StdoutConsole::new();
-        let vm = VM::<Normal>::new(code, Box::new(stdin), Box::new(stdout), rle, memory_size)?;
+        Debugger::with_io(code, rle, memory_size, Box::new(stdin), Box::new(stdout))
+    }
+
+    /// Create a debugger that reads and writes through the provided I/O instead of the console.
+    pub fn with_io(
+        code: &str,
+        rle: bool,
+        memory_size: usize,
+        stdin: Box<dyn Stdin>,
+        stdout: Box<dyn Stdout>,
+    ) -> Result<Self, EvalError> {
+        let program = Program::new(code, rle)?;
+        Ok(Debugger::with_program(program, memory_size, stdin, stdout))
+    }
+
+    /// Create a debugger for an already parsed program, e.g., one using the extended syntax.
+    pub fn with_program(
+        program: Program,
+        memory_size: usize,
+        stdin: Box<dyn Stdin>,
+        stdout: Box<dyn Stdout>,
+    ) -> Self {
+        let vm = VM::<Normal>::with_program(program, stdin, stdout, memory_size);
         let breakpoints = BTreeSet::new();
-        Ok(Debugger { vm, breakpoints })
+        let history = VecDeque::new();
+        Debugger {
+            vm,
+            breakpoints,
+            history,
+        }
+    }
+
+    pub fn program(&self) -> &Program {
+        self.vm.program()
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.vm.memory().len()
     }
 
     /// Returns the VM machine registers.
@@ -58,6 +113,49 @@ impl Debugger {
         (self.vm.ip(), self.vm.data_ptr())
     }
 
+    /// Set the instruction pointer.
+    ///
+    /// # Parameters
+    ///     ip - index of the next instruction to execute
+    ///
+    pub fn set_ip(&mut self, ip: usize) -> Result<(), EvalError> {
+        self.vm.set_ip(ip)
+    }
+
+    /// Set the data pointer.
+    ///
+    /// # Parameters
+    ///     data_ptr - index of the memory cell
+    ///
+    pub fn set_data_ptr(&mut self, data_ptr: usize) -> Result<(), EvalError> {
+        self.vm.set_data_ptr(data_ptr)
+    }
+
+    /// Save the state of the VM and the breakpoints into a snapshot file.
+    ///
+    /// # Parameters
+    ///     path - the snapshot file to create
+    ///
+    pub fn snapshot_save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let mut snapshot = self.vm.snapshot();
+        snapshot.breakpoints = self.breakpoints_list();
+        snapshot.save(path)
+    }
+
+    /// Restore the state of the VM and the breakpoints from a snapshot file. The step history is
+    /// discarded.
+    ///
+    /// # Parameters
+    ///     path - the snapshot file to load
+    ///
+    pub fn snapshot_load(&mut self, path: &Path) -> Result<(), SnapshotError> {
+        let snapshot = Snapshot::load(path)?;
+        self.vm.restore(&snapshot)?;
+        self.breakpoints = snapshot.breakpoints.iter().copied().collect();
+        self.history.clear();
+        Ok(())
+    }
+
     /// Add new breakpoint.
     ///
     /// # Parameters
@@ -103,6 +201,27 @@ impl Debugger {
         None
     }
 
+    /// Write the given bytes into memory.
+    ///
+    /// # Parameters
+    ///     start  - address of the first byte
+    ///     values - bytes to write
+    ///
+    pub fn memory_write(&mut self, start: usize, values: &[u8]) -> Result<(), EvalError> {
+        self.vm.write_memory(start, values)
+    }
+
+    /// Fill a memory range with a single value.
+    ///
+    /// # Parameters
+    ///     start - address of the first byte
+    ///     len   - number of bytes to fill
+    ///     value - value to store
+    ///
+    pub fn memory_fill(&mut self, start: usize, len: usize, value: u8) -> Result<(), EvalError> {
+        self.vm.fill_memory(start, len, value)
+    }
+
     /// Helper function to generate a hex dump of the given memory range
     pub fn memory_dump(&self, start: usize, len: usize) -> String {
         let element_count = 0x10;
@@ -152,20 +271,174 @@ impl Debugger {
     }
 
     pub fn step(&mut self) -> Result<bool, EvalError> {
-        self.vm.execute_step()
+        let (ip, data_ptr) = self.registers();
+        let cell = self.vm.memory()[data_ptr];
+        let steps = self.vm.steps();
+        let input =
+            (self.vm.program().code.get(ip) == Some(&OpCode::Input)).then(|| self.vm.input_len());
+        let finished = self.vm.execute_step()?;
+        if self.history.len() == HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back(HistoryEntry {
+            ip,
+            data_ptr,
+            cell,
+            steps,
+            input,
+        });
+        Ok(finished)
+    }
+
+    /// Undo the last step. Input that was consumed is read again by the next steps, output that
+    /// was written stays as it is.
+    ///
+    /// # Returns
+    ///     false if there is no step left to undo
+    pub fn step_back(&mut self) -> Result<bool, EvalError> {
+        let Some(entry) = self.history.pop_back() else {
+            return Ok(false);
+        };
+        self.vm.set_ip(entry.ip)?;
+        self.vm.set_data_ptr(entry.data_ptr)?;
+        self.vm.write_memory(entry.data_ptr, &[entry.cell])?;
+        self.vm.set_steps(entry.steps);
+        if let Some(count) = entry.input {
+            let consumed = self.vm.input().chars().take(count).collect::<String>();
+            self.vm.rewind_input(&consumed);
+        }
+        Ok(true)
     }
 
     pub fn run(&mut self) -> Result<bool, EvalError> {
+        self.run_while(|_| true)
+    }
+
+    /// Like `run`, but give control back after at most {steps} steps so that the caller can
+    /// check for interrupts.
+    ///
+    /// # Returns
+    ///     None if the steps ran out before a breakpoint was hit or the program ended
+    pub fn run_for(&mut self, steps: usize) -> Result<Option<bool>, EvalError> {
+        let mut remaining = steps;
+        let finished = self.run_while(|_| {
+            remaining = remaining.saturating_sub(1);
+            0 < remaining
+        })?;
+        Ok(if !finished && remaining == 0 {
+            None
+        } else {
+            Some(finished)
+        })
+    }
+
+    /// Run until the innermost loop around the current instruction is left, a breakpoint is hit
+    /// or the program ends. Outside of a loop this behaves like `run`.
+    pub fn step_out(&mut self) -> Result<bool, EvalError> {
+        let ip = self.vm.ip();
+        let enclosing = self
+            .vm
+            .program()
+            .jump_table
+            .iter()
+            .filter(|(start, end)| start < end && **start < ip && ip <= **end)
+            .min_by_key(|(start, end)| **end - **start)
+            .map(|(start, end)| (*start, *end));
+        match enclosing {
+            Some((start, end)) => self.run_while(|ip| start <= ip && ip <= end),
+            None => self.run(),
+        }
+    }
+
+    /// Keep stepping while {condition} holds for the instruction pointer and no breakpoint is hit.
+    /// The `#` instructions of the extended syntax break like breakpoints.
+    fn run_while<F: FnMut(usize) -> bool>(&mut self, mut condition: F) -> Result<bool, EvalError> {
         loop {
             if self.step()? {
                 break;
             }
             // Check if we reached a break point
             let ip = self.vm.ip();
-            if self.breakpoints.contains(&ip) {
+            let dump = self.vm.program().code.get(ip) == Some(&OpCode::Dump);
+            if self.breakpoints.contains(&ip) || dump || !condition(ip) {
                 return Ok(false);
             }
         }
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::io::string::{StdinString, StdoutString};
+    use super::*;
+
+    #[test]
+    fn test_step_back() -> Result<(), EvalError> {
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let mut debugger =
+            Debugger::with_io("++>+<-", false, 4, Box::new(stdin), Box::new(stdout))?;
+        (0..5).try_for_each(|_| debugger.step().map(|_| ()))?;
+        assert_eq!(debugger.registers(), (5, 0));
+        assert_eq!(debugger.memory(0), Some(2));
+        assert_eq!(debugger.memory(1), Some(1));
+
+        assert!(debugger.step_back()?);
+        assert!(debugger.step_back()?);
+        assert_eq!(debugger.registers(), (3, 1));
+        assert_eq!(debugger.memory(1), Some(0));
+        assert_eq!(debugger.vm.steps(), 3);
+        (0..3).try_for_each(|_| debugger.step_back().map(|_| ()))?;
+        assert_eq!(debugger.registers(), (0, 0));
+        assert_eq!(debugger.memory(0), Some(0));
+        assert!(!debugger.step_back()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_back_input() -> Result<(), EvalError> {
+        let stdin = StdinString::new("ab");
+        let stdout = StdoutString::new();
+        let mut debugger = Debugger::with_io(",>,", false, 4, Box::new(stdin), Box::new(stdout))?;
+        (0..3).try_for_each(|_| debugger.step().map(|_| ()))?;
+        assert_eq!(debugger.memory(1), Some(b'b'));
+        assert!(debugger.step_back()?);
+        assert!(debugger.step_back()?);
+        assert!(debugger.step_back()?);
+        // The replay reads the same input again
+        (0..3).try_for_each(|_| debugger.step().map(|_| ()))?;
+        assert_eq!(debugger.memory(0), Some(b'a'));
+        assert_eq!(debugger.memory(1), Some(b'b'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_breaks() -> Result<(), EvalError> {
+        let program = Program::new_extended("+#>+#.", false)?;
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let mut debugger = Debugger::with_program(program, 4, Box::new(stdin), Box::new(stdout));
+        assert!(!debugger.run()?);
+        assert_eq!(debugger.registers(), (1, 0));
+        assert!(!debugger.run()?);
+        assert_eq!(debugger.registers(), (4, 1));
+        assert!(debugger.run()?);
+        assert_eq!(debugger.memory(1), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_for() -> Result<(), EvalError> {
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let mut debugger = Debugger::with_io("+[]+", false, 4, Box::new(stdin), Box::new(stdout))?;
+        assert_eq!(debugger.run_for(10)?, None);
+        debugger.breakpoints_add(3);
+        debugger.memory_write(0, &[0])?;
+        assert_eq!(debugger.run_for(10)?, Some(false));
+        assert_eq!(debugger.registers(), (3, 0));
+        assert_eq!(debugger.run_for(10)?, Some(true));
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Compiler like rendering of errors with an excerpt of the source code.
+use super::program::SourceLocation;
+
+/// Render the {message} with the source line of the {location} and a caret with the {label} below
+/// the column. The {notes} are listed after the excerpt.
+///
+/// ```text
+/// error: unmatched ']'
+///  --> hello.bf:3:9
+///   |
+/// 3 | ++[->+<]]
+///   |         ^ there is no loop to close
+/// ```
+pub fn render(
+    filename: &str,
+    source: &str,
+    location: SourceLocation,
+    message: &str,
+    label: &str,
+    notes: &[String],
+) -> String {
+    let line = source.lines().nth(location.line - 1).unwrap_or_default();
+    let number = location.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Keep the tabs so the caret lines up with the column
+    let padding = line
+        .chars()
+        .take(location.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+
+    let mut result = format!(
+        "error: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}^ {}\n",
+        message,
+        gutter,
+        filename,
+        location.line,
+        location.column,
+        gutter,
+        number,
+        line,
+        gutter,
+        padding,
+        label
+    );
+    if !notes.is_empty() {
+        result.push_str(&format!("{} |\n", gutter));
+        for note in notes {
+            result.push_str(&format!("{} = {}\n", gutter, note));
+        }
+    }
+    result
+}
@@ -0,0 +1,185 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! State of the VM at a failed instruction.
+use std::fmt::Display;
+
+use super::diagnostic;
+use super::opcode::OpCode;
+use super::program::SourceLocation;
+
+// Number of cells shown on each side of the data pointer
+const TAPE_CONTEXT: usize = 8;
+
+/// Describes where and how the data pointer left the memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    /// Index of the failed opcode, for the JIT the index of the failed AST node in pre-order
+    pub ip: usize,
+    pub opcode: OpCode,
+    /// Location of the instruction, for the JIT the location of the innermost loop around it
+    pub location: Option<SourceLocation>,
+    pub data_ptr: usize,
+    /// Number of cells the instruction tried to move the data pointer
+    pub movement: isize,
+    /// Number of (uncompressed) instructions executed before the failed one, unknown for the JIT
+    pub steps: Option<usize>,
+    pub memory_size: usize,
+    /// Address of the first cell of the tape excerpt
+    pub tape_start: usize,
+    /// Cells around the data pointer
+    pub tape: Vec<u8>,
+}
+
+impl Fault {
+    pub fn new(
+        ip: usize,
+        opcode: OpCode,
+        location: Option<SourceLocation>,
+        data_ptr: usize,
+        movement: isize,
+        steps: Option<usize>,
+        memory: &[u8],
+    ) -> Self {
+        let tape_start = data_ptr.saturating_sub(TAPE_CONTEXT);
+        let tape_end = memory.len().min(data_ptr + TAPE_CONTEXT + 1);
+        Fault {
+            ip,
+            opcode,
+            location,
+            data_ptr,
+            movement,
+            steps,
+            memory_size: memory.len(),
+            tape_start,
+            tape: memory[tape_start..tape_end].to_vec(),
+        }
+    }
+
+    /// Fault of the AST node {node} (in pre-order) moving the data pointer by {movement} cells.
+    /// The optimized nodes have no opcodes, so the fault uses the movement as its opcode.
+    pub fn node(
+        node: usize,
+        location: Option<SourceLocation>,
+        data_ptr: usize,
+        movement: isize,
+        memory: &[u8],
+    ) -> Self {
+        let opcode = if movement < 0 {
+            OpCode::DecDataPtr(movement.unsigned_abs())
+        } else {
+            OpCode::IncDataPtr(movement as usize)
+        };
+        Fault::new(node, opcode, location, data_ptr, movement, None, memory)
+    }
+
+    fn steps_suffix(&self) -> String {
+        self.steps
+            .map(|steps| format!(" after {} steps", steps))
+            .unwrap_or_default()
+    }
+
+    /// The tape excerpt with the current cell in brackets.
+    pub fn tape_excerpt(&self) -> String {
+        let cells = self
+            .tape
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                if self.tape_start + index == self.data_ptr {
+                    format!("[{:02x}]", value)
+                } else {
+                    format!("{:02x}", value)
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("{:#06x}: {}", self.tape_start, cells.join(" "))
+    }
+
+    /// Render the fault with an excerpt of the {source} read from {filename}.
+    pub fn render(&self, filename: &str, source: &str) -> String {
+        let notes = [
+            format!(
+                "instruction {} ('{}' x{}){}",
+                self.ip,
+                self.opcode.to_char(),
+                self.opcode.count(),
+                self.steps_suffix()
+            ),
+            format!(
+                "tape {} (memory size {})",
+                self.tape_excerpt(),
+                self.memory_size
+            ),
+        ];
+        let label = format!(
+            "moves the data pointer from {} by {:+}",
+            self.data_ptr, self.movement
+        );
+        match self.location {
+            Some(location) => diagnostic::render(
+                filename,
+                source,
+                location,
+                "data pointer out of bounds",
+                &label,
+                &notes,
+            ),
+            None => format!(
+                "error: data pointer out of bounds, {}\n{}",
+                label,
+                notes
+                    .iter()
+                    .map(|note| format!("  = {}\n", note))
+                    .collect::<String>()
+            ),
+        }
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(location) = self.location {
+            write!(
+                formatter,
+                "line {}, column {}: ",
+                location.line, location.column
+            )?;
+        }
+        write!(
+            formatter,
+            "instruction {} moves the data pointer from {} by {:+}{}, tape {}",
+            self.ip,
+            self.data_ptr,
+            self.movement,
+            self.steps_suffix(),
+            self.tape_excerpt()
+        )
+    }
+}
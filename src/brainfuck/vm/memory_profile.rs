@@ -0,0 +1,240 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::opcode::OpCode;
+
+// Number of cells per row of the heatmap
+const HEATMAP_COLUMNS: usize = 64;
+// Width and height in pixels of a cell in the heatmap
+const HEATMAP_CELL_SIZE: usize = 8;
+
+/// Access statistics of a single memory cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CellStats {
+    pub reads: usize,
+    pub writes: usize,
+    /// Smallest value the cell held when it was accessed.
+    pub min: u8,
+    /// Largest value the cell held when it was accessed.
+    pub max: u8,
+}
+
+impl CellStats {
+    pub fn accesses(&self) -> usize {
+        self.reads + self.writes
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryProfile {
+    cells: Vec<CellStats>,
+    data_ptr_range: Option<(usize, usize)>,
+}
+
+impl MemoryProfile {
+    pub fn new(memory_size: usize) -> Self {
+        MemoryProfile {
+            cells: vec![CellStats::default(); memory_size],
+            data_ptr_range: None,
+        }
+    }
+
+    /// Record the memory accesses of an executed opcode.
+    ///
+    /// # Parameters
+    ///     opcode   - the executed opcode
+    ///     address  - data pointer before the opcode was executed
+    ///     value    - value of the cell at {address} after the opcode was executed
+    ///     data_ptr - data pointer after the opcode was executed
+    ///
+    pub fn record(&mut self, opcode: OpCode, address: usize, value: u8, data_ptr: usize) {
+        let (min, max) = self.data_ptr_range.unwrap_or((address, address));
+        self.data_ptr_range = Some((min.min(address), max.max(data_ptr)));
+
+        let (reads, writes) = match opcode {
+            OpCode::IncValue(_) | OpCode::DecValue(_) => (1, 1),
+            OpCode::Input => (0, 1),
+            OpCode::Output | OpCode::LoopStart | OpCode::LoopEnd => (1, 0),
+            OpCode::IncDataPtr(_) | OpCode::DecDataPtr(_) | OpCode::Dump => return,
+        };
+        let cell = &mut self.cells[address];
+        if cell.accesses() == 0 {
+            cell.min = value;
+            cell.max = value;
+        }
+        cell.reads += reads;
+        cell.writes += writes;
+        cell.min = cell.min.min(value);
+        cell.max = cell.max.max(value);
+    }
+
+    pub fn cells(&self) -> &Vec<CellStats> {
+        &self.cells
+    }
+
+    /// Returns the smallest and largest data pointer reached.
+    pub fn data_ptr_range(&self) -> Option<(usize, usize)> {
+        self.data_ptr_range
+    }
+
+    /// Generate a report with the data pointer range and the statistics of every accessed cell.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        match self.data_ptr_range() {
+            Some((min, max)) => report.push_str(&format!(
+                "Data pointer range: {}..={} (minimal memory size: {})\n",
+                min,
+                max,
+                max + 1
+            )),
+            None => report.push_str("Data pointer range: none\n"),
+        }
+        let accessed = self.cells().iter().filter(|cell| cell.accesses() > 0);
+        report.push_str(&format!(
+            "Cells accessed: {}, reads: {}, writes: {}\n\n",
+            accessed.clone().count(),
+            accessed.clone().map(|cell| cell.reads).sum::<usize>(),
+            accessed.map(|cell| cell.writes).sum::<usize>()
+        ));
+        report.push_str(&format!(
+            "{:>8}  {:>12}  {:>12}  {:>3}  {:>3}\n",
+            "ADDRESS", "READS", "WRITES", "MIN", "MAX"
+        ));
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.accesses() > 0)
+            .for_each(|(address, cell)| {
+                report.push_str(&format!(
+                    "{:08x}  {:>12}  {:>12}  {:>3}  {:>3}\n",
+                    address, cell.reads, cell.writes, cell.min, cell.max
+                ))
+            });
+        report
+    }
+
+    /// Render the number of accesses per cell as RGB image. Every row holds 64 cells, untouched
+    /// cells are black and the color goes from blue (cold) to red (hot).
+    ///
+    /// # Returns
+    ///     (width, height, pixels)
+    fn heatmap(&self) -> (usize, usize, Vec<u8>) {
+        let used = self
+            .cells
+            .iter()
+            .rposition(|cell| cell.accesses() > 0)
+            .map_or(1, |last| last + 1);
+        let max = self
+            .cells
+            .iter()
+            .map(|cell| cell.accesses())
+            .max()
+            .unwrap_or(0);
+        let width = HEATMAP_COLUMNS * HEATMAP_CELL_SIZE;
+        let height = used.div_ceil(HEATMAP_COLUMNS) * HEATMAP_CELL_SIZE;
+        let mut pixels = vec![0; width * height * 3];
+        self.cells[..used]
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.accesses() > 0)
+            .for_each(|(address, cell)| {
+                let heat = (cell.accesses() as f64).ln_1p() / (max as f64).ln_1p();
+                let color = [(255.0 * heat) as u8, 32, (255.0 * (1.0 - heat)) as u8];
+                let x = (address % HEATMAP_COLUMNS) * HEATMAP_CELL_SIZE;
+                let y = (address / HEATMAP_COLUMNS) * HEATMAP_CELL_SIZE;
+                (y..y + HEATMAP_CELL_SIZE).for_each(|row| {
+                    (x..x + HEATMAP_CELL_SIZE).for_each(|column| {
+                        let index = (row * width + column) * 3;
+                        pixels[index..index + 3].copy_from_slice(&color);
+                    })
+                });
+            });
+        (width, height, pixels)
+    }
+
+    /// Write the heatmap into a file. Files with a `.png` extension are written as PNG,
+    /// everything else as binary PPM.
+    pub fn write_heatmap(&self, path: &Path) -> Result<(), io::Error> {
+        let (width, height, pixels) = self.heatmap();
+        let mut writer = BufWriter::new(File::create(path)?);
+        if path.extension().is_some_and(|extension| extension == "png") {
+            let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .write_header()
+                .and_then(|mut png_writer| png_writer.write_image_data(&pixels))
+                .map_err(io::Error::other)
+        } else {
+            write!(writer, "P6\n{} {}\n255\n", width, height)?;
+            writer.write_all(&pixels)?;
+            writer.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::io::string::{StdinString, StdoutString};
+    use super::super::vm::{EvalError, Normal, VM};
+
+    #[test]
+    fn test_memory_profile() -> Result<(), EvalError> {
+        let stdin = StdinString::new("A");
+        let stdout = StdoutString::new();
+        let code = ",[>++<-]>>+<.";
+        let vm = VM::<Normal>::new(code, Box::new(stdin), Box::new(stdout), true, 16)?;
+        let mut vm = vm.enable_profiler();
+        vm.run()?;
+
+        let memory = vm.profiler().memory();
+        assert_eq!(memory.data_ptr_range(), Some((0, 2)));
+        let cells = memory.cells();
+        assert_eq!((cells[0].reads, cells[0].writes), (65 + 66, 66));
+        assert_eq!((cells[0].min, cells[0].max), (0, 65));
+        assert_eq!((cells[1].reads, cells[1].writes), (66, 65));
+        assert_eq!((cells[1].min, cells[1].max), (2, 130));
+        assert_eq!((cells[2].reads, cells[2].writes), (1, 1));
+        assert_eq!(cells[3].accesses(), 0);
+
+        let report = memory.report();
+        assert!(report.starts_with("Data pointer range: 0..=2 (minimal memory size: 3)\n"));
+        assert_eq!(report.lines().count(), 7);
+
+        let (width, height, pixels) = memory.heatmap();
+        assert_eq!((width, height), (512, 8));
+        assert_eq!(&pixels[24 * 3..24 * 3 + 3], &[0, 0, 0]);
+        assert_ne!(&pixels[0..3], &[0, 0, 0]);
+        Ok(())
+    }
+}
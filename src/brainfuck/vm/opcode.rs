@@ -37,10 +37,12 @@ pub enum OpCode {
     LoopEnd,
     LoopStart,
     Output,
+    /// Debug instruction `#` of the extended syntax
+    Dump,
 }
 
 impl OpCode {
-    pub fn to_char(&self) -> char {
+    pub fn to_char(self) -> char {
         match self {
             Self::DecDataPtr(_) => '<',
             Self::DecValue(_) => '-',
@@ -50,16 +52,18 @@ impl OpCode {
             Self::LoopStart => '[',
             Self::LoopEnd => ']',
             Self::Output => '.',
+            Self::Dump => '#',
         }
     }
 
-    /// Return the count of the the instruction.
-    pub fn count(&self) -> usize {
+    /// Return the count of the the instruction. Debug instructions are not counted.
+    pub fn count(self) -> usize {
         match self {
             OpCode::DecDataPtr(count)
             | OpCode::DecValue(count)
             | OpCode::IncDataPtr(count)
-            | OpCode::IncValue(count) => *count,
+            | OpCode::IncValue(count) => count,
+            OpCode::Dump => 0,
             _ => 1,
         }
     }
@@ -79,4 +83,12 @@ impl OpCode {
             _ => None,
         }
     }
+
+    /// Convert function of the extended syntax, which adds `#` to dump the state of the VM.
+    pub fn convert_extended(value: char) -> Option<Self> {
+        match value {
+            '#' => Some(OpCode::Dump),
+            _ => OpCode::convert(value),
+        }
+    }
 }
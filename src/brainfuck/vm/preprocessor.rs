@@ -0,0 +1,813 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Macro preprocessor that expands a source into a plain brainfuck program.
+//!
+//! Directives are lines starting with `@`:
+//!
+//! ```text
+//! @define name(a, b) body    macro with a single line body (or a flag without body)
+//! @macro name(a, b)          macro whose body ends with a line `@end`
+//! @include "file.bf"         insert the file, relative to the including file
+//! @ifdef NAME / @ifndef NAME / @else / @endif
+//! ```
+//!
+//! `@name(x, y)` expands a macro and `$a` refers to a parameter in its body. A command, a macro
+//! call or a parameter followed by `*N` is repeated N times, e.g., `+*65`. Every character of the
+//! expansion remembers where it came from, so diagnostics point into the macro source.
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use super::diagnostic;
+use super::program::{Program, ProgramError, SourceLocation};
+
+// Maximum nesting of macro calls
+const MAX_DEPTH: usize = 64;
+
+// Maximum number of characters of an expansion
+const MAX_LENGTH: usize = 1 << 22;
+
+// Characters that can be repeated with `*N`
+const COMMANDS: &str = "+-<>,.[]";
+
+/// Location in one of the preprocessed files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub file: PathBuf,
+    pub location: SourceLocation,
+}
+
+impl Display for Position {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            formatter,
+            "{}:{}:{}",
+            self.file.display(),
+            self.location.line,
+            self.location.column
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PreprocessorError {
+    #[error("Unknown macro '{name}' at {position}")]
+    UnknownMacro { name: String, position: Position },
+    #[error("Unknown parameter '{name}' at {position}")]
+    UnknownParameter { name: String, position: Position },
+    #[error("Macro '{name}' expects {expected} arguments but got {found} at {position}")]
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+        position: Position,
+    },
+    #[error("Unclosed argument list of macro '{name}' at {position}")]
+    UnclosedArguments { name: String, position: Position },
+    #[error("Macro '{name}' is never closed with '@end' at {position}")]
+    UnclosedMacro { name: String, position: Position },
+    #[error("Conditional is never closed with '@endif' at {position}")]
+    UnclosedConditional { position: Position },
+    #[error("Invalid directive at {position}: {message}")]
+    InvalidDirective { message: String, position: Position },
+    #[error("Macro '{name}' nests more than {MAX_DEPTH} calls at {position}")]
+    RecursionLimit { name: String, position: Position },
+    #[error("Expansion exceeds {MAX_LENGTH} characters at {position}")]
+    ExpansionLimit { position: Position },
+    #[error("Cannot include '{}' at {position}", .path.display())]
+    Include {
+        path: PathBuf,
+        position: Position,
+        #[source]
+        source: io::Error,
+    },
+    #[error("'{}' includes itself at {position}", .path.display())]
+    IncludeCycle { path: PathBuf, position: Position },
+}
+
+impl PreprocessorError {
+    /// The file and location the error occurred at.
+    pub fn position(&self) -> &Position {
+        match self {
+            PreprocessorError::UnknownMacro { position, .. }
+            | PreprocessorError::UnknownParameter { position, .. }
+            | PreprocessorError::ArgumentCount { position, .. }
+            | PreprocessorError::UnclosedArguments { position, .. }
+            | PreprocessorError::UnclosedMacro { position, .. }
+            | PreprocessorError::UnclosedConditional { position }
+            | PreprocessorError::InvalidDirective { position, .. }
+            | PreprocessorError::RecursionLimit { position, .. }
+            | PreprocessorError::ExpansionLimit { position }
+            | PreprocessorError::Include { position, .. }
+            | PreprocessorError::IncludeCycle { position, .. } => position,
+        }
+    }
+
+    /// Render the error with an excerpt of the {source} of the file it occurred in.
+    pub fn render(&self, source: &str) -> String {
+        let (message, label) = match self {
+            PreprocessorError::UnknownMacro { name, .. } => (
+                format!("unknown macro '{}'", name),
+                "not defined".to_string(),
+            ),
+            PreprocessorError::UnknownParameter { name, .. } => (
+                format!("unknown parameter '{}'", name),
+                "not a parameter of the macro".to_string(),
+            ),
+            PreprocessorError::ArgumentCount {
+                name,
+                expected,
+                found,
+                ..
+            } => (
+                format!("macro '{}' expects {} arguments", name, expected),
+                format!("called with {} arguments", found),
+            ),
+            PreprocessorError::UnclosedArguments { name, .. } => (
+                format!("unclosed argument list of macro '{}'", name),
+                "the call starts here".to_string(),
+            ),
+            PreprocessorError::UnclosedMacro { name, .. } => (
+                format!("macro '{}' is never closed", name),
+                "missing '@end'".to_string(),
+            ),
+            PreprocessorError::UnclosedConditional { .. } => (
+                "unclosed conditional".to_string(),
+                "missing '@endif'".to_string(),
+            ),
+            PreprocessorError::InvalidDirective { message, .. } => {
+                (message.clone(), "invalid directive".to_string())
+            }
+            PreprocessorError::RecursionLimit { name, .. } => (
+                format!("macro '{}' nests more than {} calls", name, MAX_DEPTH),
+                "the calls start here".to_string(),
+            ),
+            PreprocessorError::ExpansionLimit { .. } => (
+                format!("expansion exceeds {} characters", MAX_LENGTH),
+                "expanded here".to_string(),
+            ),
+            PreprocessorError::Include { path, source, .. } => (
+                format!("cannot include '{}': {}", path.display(), source),
+                "included here".to_string(),
+            ),
+            PreprocessorError::IncludeCycle { path, .. } => (
+                format!("'{}' includes itself", path.display()),
+                "included here".to_string(),
+            ),
+        };
+        let position = self.position();
+        diagnostic::render(
+            &position.file.display().to_string(),
+            source,
+            position.location,
+            &message,
+            &label,
+            &[],
+        )
+    }
+}
+
+/// Location in the main file of every character of the expanded program. Characters of included
+/// files are located at their `@include` directive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    locations: Vec<SourceLocation>,
+    // Source of the main file
+    source: String,
+}
+
+impl SourceMap {
+    /// Location of the character at {offset} of the expanded program.
+    pub fn location(&self, offset: usize) -> Option<SourceLocation> {
+        self.locations.get(offset).copied()
+    }
+
+    /// Move a {location} of the expanded program into the main file.
+    pub fn map(&self, location: SourceLocation) -> SourceLocation {
+        self.location(location.offset).unwrap_or(location)
+    }
+
+    /// Source of the main file that the mapped locations point into.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Replace the locations of the {program} parsed from the expanded code with the locations
+    /// in the main file.
+    pub fn map_program(&self, mut program: Program) -> Program {
+        program.locations = program
+            .locations
+            .iter()
+            .map(|location| self.map(*location))
+            .collect();
+        program
+    }
+
+    /// Move the location of the {error} into the main file. The hint refers to the lines of the
+    /// expanded code and is dropped.
+    pub fn map_error(&self, error: ProgramError) -> ProgramError {
+        match error {
+            ProgramError::UnmatchedClose { location, .. } => ProgramError::UnmatchedClose {
+                location: self.map(location),
+                hint: None,
+            },
+            ProgramError::UnclosedLoop { location, .. } => ProgramError::UnclosedLoop {
+                location: self.map(location),
+                hint: None,
+            },
+        }
+    }
+}
+
+/// The expanded program.
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub code: String,
+    pub source_map: SourceMap,
+}
+
+/// Character of a source with the file and location it came from.
+#[derive(Debug, Clone, Copy)]
+struct Char {
+    value: char,
+    file: usize,
+    location: SourceLocation,
+}
+
+struct SourceFile {
+    path: PathBuf,
+    // Canonical path to detect include cycles
+    canonical: Option<PathBuf>,
+    // The `@` of the directive that included the file
+    included_at: Option<Char>,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Char>,
+}
+
+struct Conditional {
+    active: bool,
+    // Whether the lines around the conditional are active
+    enclosing: bool,
+    has_else: bool,
+    start: Char,
+}
+
+struct Preprocessor {
+    files: Vec<SourceFile>,
+    macros: HashMap<String, Macro>,
+    defines: HashSet<String>,
+}
+
+/// Expand the {source} of the file at {path}. The {defines} count as defined for `@ifdef`.
+pub fn preprocess(
+    source: &str,
+    path: &Path,
+    defines: &[String],
+) -> Result<Expansion, PreprocessorError> {
+    let mut preprocessor = Preprocessor {
+        files: vec![],
+        macros: HashMap::new(),
+        defines: defines.iter().cloned().collect(),
+    };
+    let text = preprocessor.process_file(source, path, None)?;
+    let expanded = preprocessor.expand(&text, 0)?;
+    Ok(Expansion {
+        code: expanded.iter().map(|value| value.value).collect(),
+        source_map: SourceMap {
+            locations: expanded
+                .iter()
+                .map(|value| preprocessor.main_location(*value))
+                .collect(),
+            source: source.to_string(),
+        },
+    })
+}
+
+fn is_identifier(value: char) -> bool {
+    value.is_ascii_alphanumeric() || value == '_'
+}
+
+/// Read the identifier at the start of {chars} and return it with the remaining characters.
+fn identifier(chars: &[Char]) -> (String, &[Char]) {
+    let length = chars
+        .iter()
+        .take_while(|value| is_identifier(value.value))
+        .count();
+    (text(&chars[..length]), &chars[length..])
+}
+
+fn trim(chars: &[Char]) -> &[Char] {
+    let start = chars
+        .iter()
+        .position(|value| !value.value.is_whitespace())
+        .unwrap_or(chars.len());
+    let end = chars
+        .iter()
+        .rposition(|value| !value.value.is_whitespace())
+        .map_or(start, |end| end + 1);
+    &chars[start..end]
+}
+
+fn text(chars: &[Char]) -> String {
+    chars.iter().map(|value| value.value).collect()
+}
+
+/// Read a repetition `*N` at the start of {chars} and return the count with the remaining
+/// characters. Without a repetition the count is 1.
+fn repetition(chars: &[Char]) -> (usize, &[Char]) {
+    let Some((first, rest)) = chars.split_first() else {
+        return (1, chars);
+    };
+    let digits = rest
+        .iter()
+        .take_while(|value| value.value.is_ascii_digit())
+        .count();
+    match text(&rest[..digits]).parse() {
+        Ok(count) if first.value == '*' => (count, &rest[digits..]),
+        _ => (1, chars),
+    }
+}
+
+impl Preprocessor {
+    fn position(&self, value: Char) -> Position {
+        Position {
+            file: self.files[value.file].path.clone(),
+            location: value.location,
+        }
+    }
+
+    fn main_location(&self, mut value: Char) -> SourceLocation {
+        while let Some(include) = self.files[value.file].included_at {
+            value = include;
+        }
+        value.location
+    }
+
+    fn invalid(&self, message: &str, at: Char) -> PreprocessorError {
+        PreprocessorError::InvalidDirective {
+            message: message.to_string(),
+            position: self.position(at),
+        }
+    }
+
+    /// Split the directive off a {line}: the `@`, the keyword and the rest of the line.
+    fn directive(line: &[Char]) -> Option<(Char, String, &[Char])> {
+        let line = trim(line);
+        let (at, rest) = line.split_first()?;
+        let (keyword, rest) = identifier(rest);
+        let keywords = [
+            "define", "macro", "end", "include", "ifdef", "ifndef", "else", "endif",
+        ];
+        (at.value == '@' && keywords.contains(&keyword.as_str())).then_some((*at, keyword, rest))
+    }
+
+    /// Handle the directives of the file and return the remaining text.
+    fn process_file(
+        &mut self,
+        source: &str,
+        path: &Path,
+        included_at: Option<Char>,
+    ) -> Result<Vec<Char>, PreprocessorError> {
+        let canonical = fs::canonicalize(path).ok();
+        let mut parent = included_at;
+        while let Some(include) = parent {
+            let file = &self.files[include.file];
+            if canonical.is_some() && file.canonical == canonical {
+                return Err(PreprocessorError::IncludeCycle {
+                    path: path.to_path_buf(),
+                    position: self.position(included_at.unwrap()),
+                });
+            }
+            parent = file.included_at;
+        }
+        let file = self.files.len();
+        self.files.push(SourceFile {
+            path: path.to_path_buf(),
+            canonical,
+            included_at,
+        });
+
+        let chars = SourceLocation::annotate(source)
+            .map(|(value, location)| Char {
+                value,
+                file,
+                location,
+            })
+            .collect::<Vec<_>>();
+        let mut lines = chars.split_inclusive(|value| value.value == '\n');
+        let mut conditionals: Vec<Conditional> = vec![];
+        let mut output = vec![];
+        while let Some(line) = lines.next() {
+            let active = conditionals
+                .last()
+                .is_none_or(|conditional| conditional.active);
+            let Some((at, keyword, rest)) = Preprocessor::directive(line) else {
+                if active {
+                    output.extend_from_slice(line);
+                }
+                continue;
+            };
+            match keyword.as_str() {
+                "ifdef" | "ifndef" => {
+                    let name = text(trim(rest));
+                    if name.is_empty() || !name.chars().all(is_identifier) {
+                        return Err(self.invalid("expected a name after the conditional", at));
+                    }
+                    let defined = self.macros.contains_key(&name) || self.defines.contains(&name);
+                    conditionals.push(Conditional {
+                        active: active && defined == (keyword == "ifdef"),
+                        enclosing: active,
+                        has_else: false,
+                        start: at,
+                    });
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.has_else => {
+                        conditional.active = conditional.enclosing && !conditional.active;
+                        conditional.has_else = true;
+                    }
+                    _ => return Err(self.invalid("'@else' without '@ifdef'", at)),
+                },
+                "endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(self.invalid("'@endif' without '@ifdef'", at));
+                    }
+                }
+                _ if !active => (),
+                "end" => return Err(self.invalid("'@end' without '@macro'", at)),
+                "include" => {
+                    let name = text(trim(rest));
+                    let Some(name) = name
+                        .strip_prefix('"')
+                        .and_then(|name| name.strip_suffix('"'))
+                    else {
+                        return Err(self.invalid("expected a quoted file name", at));
+                    };
+                    let target = path.parent().unwrap_or(Path::new("")).join(name);
+                    let content = fs::read_to_string(&target).map_err(|source| {
+                        PreprocessorError::Include {
+                            path: target.clone(),
+                            position: self.position(at),
+                            source,
+                        }
+                    })?;
+                    output.extend(self.process_file(&content, &target, Some(at))?);
+                    // Keep the line break of the directive
+                    output.extend(line.last().filter(|value| value.value == '\n'));
+                }
+                _ => {
+                    let (name, params, rest) = self.signature(rest, at)?;
+                    let body = if keyword == "define" {
+                        trim(rest).to_vec()
+                    } else {
+                        let mut body = vec![];
+                        loop {
+                            let Some(line) = lines.next() else {
+                                return Err(PreprocessorError::UnclosedMacro {
+                                    name,
+                                    position: self.position(at),
+                                });
+                            };
+                            if let Some((_, keyword, _)) = Preprocessor::directive(line)
+                                && keyword == "end"
+                            {
+                                break;
+                            }
+                            body.extend_from_slice(line);
+                        }
+                        body
+                    };
+                    self.macros.insert(name, Macro { params, body });
+                }
+            }
+        }
+        if let Some(conditional) = conditionals.first() {
+            return Err(PreprocessorError::UnclosedConditional {
+                position: self.position(conditional.start),
+            });
+        }
+        Ok(output)
+    }
+
+    /// Parse the name and the parameters of a macro definition.
+    fn signature<'a>(
+        &self,
+        chars: &'a [Char],
+        at: Char,
+    ) -> Result<(String, Vec<String>, &'a [Char]), PreprocessorError> {
+        let (name, rest) = identifier(trim(chars));
+        if name.is_empty() {
+            return Err(self.invalid("expected a macro name", at));
+        }
+        let rest = match rest.split_first() {
+            Some((open, rest)) if open.value == '(' => rest,
+            _ => return Ok((name, vec![], rest)),
+        };
+        let Some(end) = rest.iter().position(|value| value.value == ')') else {
+            return Err(self.invalid("unclosed parameter list", at));
+        };
+        let params = text(&rest[..end])
+            .split(',')
+            .map(|param| param.trim().to_string())
+            .filter(|param| !param.is_empty())
+            .collect::<Vec<_>>();
+        if !params.iter().all(|param| param.chars().all(is_identifier)) {
+            return Err(self.invalid("parameters have to be names", at));
+        }
+        Ok((name, params, &rest[end + 1..]))
+    }
+
+    /// Expand the macro calls and repetitions of the {chars}.
+    fn expand(&self, chars: &[Char], depth: usize) -> Result<Vec<Char>, PreprocessorError> {
+        let mut output = vec![];
+        let mut rest = chars;
+        while let Some((current, tail)) = rest.split_first() {
+            let (expansion, tail) = match tail.first() {
+                Some(next) if current.value == '@' && is_identifier(next.value) => {
+                    self.call(*current, tail, depth)?
+                }
+                _ if COMMANDS.contains(current.value) => (vec![*current], tail),
+                _ => {
+                    output.push(*current);
+                    rest = tail;
+                    continue;
+                }
+            };
+            let (count, tail) = repetition(tail);
+            self.check_length(output.len(), count, expansion.len(), *current)?;
+            for _ in 0..count {
+                output.extend_from_slice(&expansion);
+            }
+            rest = tail;
+        }
+        Ok(output)
+    }
+
+    /// Fail if appending {count} times {length} characters to {current} characters exceeds the
+    /// maximum length of an expansion.
+    fn check_length(
+        &self,
+        current: usize,
+        count: usize,
+        length: usize,
+        at: Char,
+    ) -> Result<(), PreprocessorError> {
+        let total = count
+            .checked_mul(length)
+            .and_then(|added| added.checked_add(current));
+        match total {
+            Some(total) if total <= MAX_LENGTH => Ok(()),
+            _ => Err(PreprocessorError::ExpansionLimit {
+                position: self.position(at),
+            }),
+        }
+    }
+
+    /// Expand the call of the macro whose name starts {chars} after the {at} sign.
+    fn call<'a>(
+        &self,
+        at: Char,
+        chars: &'a [Char],
+        depth: usize,
+    ) -> Result<(Vec<Char>, &'a [Char]), PreprocessorError> {
+        let (name, mut rest) = identifier(chars);
+        let Some(definition) = self.macros.get(&name) else {
+            return Err(PreprocessorError::UnknownMacro {
+                name,
+                position: self.position(at),
+            });
+        };
+        if depth == MAX_DEPTH {
+            return Err(PreprocessorError::RecursionLimit {
+                name,
+                position: self.position(at),
+            });
+        }
+        let mut arguments = vec![];
+        if rest.first().is_some_and(|value| value.value == '(') {
+            (arguments, rest) = self.arguments(&rest[1..], &name, at)?;
+            // `@name()` calls a macro without parameters
+            if definition.params.is_empty() && arguments.len() == 1 && arguments[0].is_empty() {
+                arguments.clear();
+            }
+        }
+        if arguments.len() != definition.params.len() {
+            return Err(PreprocessorError::ArgumentCount {
+                name,
+                expected: definition.params.len(),
+                found: arguments.len(),
+                position: self.position(at),
+            });
+        }
+        let body = self.substitute(definition, &arguments)?;
+        Ok((self.expand(&body, depth + 1)?, rest))
+    }
+
+    /// Split the arguments up to the closing parenthesis at the commas outside of parentheses.
+    fn arguments<'a>(
+        &self,
+        chars: &'a [Char],
+        name: &str,
+        at: Char,
+    ) -> Result<(Vec<Vec<Char>>, &'a [Char]), PreprocessorError> {
+        let mut arguments = vec![];
+        let mut start = 0;
+        let mut nesting = 0;
+        for (index, value) in chars.iter().enumerate() {
+            match value.value {
+                '(' => nesting += 1,
+                ')' if 0 < nesting => nesting -= 1,
+                ',' | ')' if nesting == 0 => {
+                    arguments.push(trim(&chars[start..index]).to_vec());
+                    start = index + 1;
+                    if value.value == ')' {
+                        return Ok((arguments, &chars[index + 1..]));
+                    }
+                }
+                _ => (),
+            }
+        }
+        Err(PreprocessorError::UnclosedArguments {
+            name: name.to_string(),
+            position: self.position(at),
+        })
+    }
+
+    /// Replace the parameters in the body of the {definition} with the {arguments}.
+    fn substitute(
+        &self,
+        definition: &Macro,
+        arguments: &[Vec<Char>],
+    ) -> Result<Vec<Char>, PreprocessorError> {
+        let mut output = vec![];
+        let mut rest = definition.body.as_slice();
+        while let Some((current, tail)) = rest.split_first() {
+            if current.value != '$' || !tail.first().is_some_and(|next| is_identifier(next.value)) {
+                output.push(*current);
+                rest = tail;
+                continue;
+            }
+            let (name, tail) = identifier(tail);
+            let Some(index) = definition.params.iter().position(|param| *param == name) else {
+                return Err(PreprocessorError::UnknownParameter {
+                    name,
+                    position: self.position(*current),
+                });
+            };
+            let (count, tail) = repetition(tail);
+            self.check_length(output.len(), count, arguments[index].len(), *current)?;
+            for _ in 0..count {
+                output.extend_from_slice(&arguments[index]);
+            }
+            rest = tail;
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(source: &str, defines: &[&str]) -> Result<String, PreprocessorError> {
+        let defines = defines
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        Ok(preprocess(source, Path::new("test.bf"), &defines)?.code)
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("+*3>*2 a*2 +*0.", &[]).unwrap(), "+++>> a*2 .");
+        let source = "@define clear [-]\n\
+                      @macro move(from, to)\n\
+                      $from[-$to+$from]\n\
+                      @end\n\
+                      @clear @move(>*2, <<)*2 @clear()\n";
+        assert_eq!(
+            expand(source, &[]).unwrap(),
+            "[-] >>[-<<+>>]\n>>[-<<+>>]\n [-]\n"
+        );
+        // Macro calls in arguments and repeated parameters
+        let source = "@define twice(x) $x*2\n@twice(@twice(+))";
+        assert_eq!(expand(source, &[]).unwrap(), "++++");
+
+        let source = "@ifdef WIDE\n>>\n@else\n>\n@endif\n@ifndef WIDE\n.\n@endif\n";
+        assert_eq!(expand(source, &["WIDE"]).unwrap(), ">>\n");
+        assert_eq!(expand(source, &[]).unwrap(), ">\n.\n");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            expand("+@missing", &[]),
+            Err(PreprocessorError::UnknownMacro { name, position })
+                if name == "missing" && position.location.column == 2
+        ));
+        assert!(matches!(
+            expand("@define f(a, b) $a$b\n@f(+)", &[]),
+            Err(PreprocessorError::ArgumentCount {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            expand("@define f $g\n@f", &[]),
+            Err(PreprocessorError::UnknownParameter { .. })
+        ));
+        assert!(matches!(
+            expand("@macro f\n+\n", &[]),
+            Err(PreprocessorError::UnclosedMacro { .. })
+        ));
+        assert!(matches!(
+            expand("@ifdef A\n", &[]),
+            Err(PreprocessorError::UnclosedConditional { .. })
+        ));
+        assert!(matches!(
+            expand("@define f @f\n@f", &[]),
+            Err(PreprocessorError::RecursionLimit { .. })
+        ));
+        for source in [
+            "+*99999999999",
+            "@define f(x) $x*99999999999\n@f(+)",
+            "@define a +*1000\n@define b @a*1000\n@b*1000",
+        ] {
+            assert!(matches!(
+                expand(source, &[]),
+                Err(PreprocessorError::ExpansionLimit { .. })
+            ));
+        }
+        let error = expand("\n @include missing.bf", &[]).unwrap_err();
+        assert_eq!(
+            error.render("\n @include missing.bf"),
+            "error: expected a quoted file name\n --> test.bf:2:2\n  |\n2 |  @include missing.bf\n  \
+             |  ^ invalid directive\n"
+        );
+    }
+
+    #[test]
+    fn test_source_map() {
+        let directory = std::env::temp_dir().join(format!("bf-macros-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("lib.bf"), "@define inc(n) +*$n\n").unwrap();
+        fs::write(directory.join("cycle.bf"), "@include \"cycle.bf\"\n").unwrap();
+        let path = directory.join("main.bf");
+        let source = "@include \"lib.bf\"\n@macro open\n  [\n@end\n@inc(2)@open\n";
+        let expansion = preprocess(source, &path, &[]).unwrap();
+        assert_eq!(expansion.code, "\n++  [\n\n");
+
+        // The increments come from the included file, the loop from the macro body
+        let Err(error) = Program::new(&expansion.code, false) else {
+            panic!("Expected an unclosed loop");
+        };
+        let ProgramError::UnclosedLoop { location, hint } = expansion.source_map.map_error(error)
+        else {
+            panic!("Expected an unclosed loop");
+        };
+        assert_eq!((location.line, location.column, hint), (3, 3, None));
+        let program = expansion
+            .source_map
+            .map_program(Program::new("++", false).unwrap());
+        assert_eq!(program.locations[1].line, 1);
+
+        let cycle = preprocess("@include \"cycle.bf\"", &path, &[]);
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(matches!(cycle, Err(PreprocessorError::IncludeCycle { .. })));
+    }
+}
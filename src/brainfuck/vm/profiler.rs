@@ -0,0 +1,352 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::memory_profile::MemoryProfile;
+use super::opcode::OpCode;
+use super::program::{Program, SourceLocation};
+use super::vm::VMConfig;
+
+/// Number of loops listed in the hot-loop reports of the interpreter and the JIT.
+pub const HOT_LOOPS: usize = 20;
+
+/// Aggregated execution data of a single loop.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LoopProfile {
+    /// Number of times the `[` of the loop was executed from outside of the loop.
+    pub entries: usize,
+    /// Number of times the loop body was executed.
+    pub iterations: usize,
+    /// Number of steps executed from entering the loop until leaving it (nested loops included).
+    pub steps: usize,
+    /// Sum of the data pointer changes over all iterations. A balanced loop has a drift of 0.
+    pub drift: isize,
+}
+
+/// Loop that is currently executed.
+#[derive(Debug)]
+struct ActiveLoop {
+    start: usize,
+    entry_step: usize,
+    iteration_data_ptr: usize,
+}
+
+#[derive(Debug)]
+pub struct Profiler {
+    profile_data: Vec<usize>,
+    ip_map: Vec<usize>,
+    loops: HashMap<usize, LoopProfile>,
+    active: Vec<ActiveLoop>,
+    steps: usize,
+    memory: MemoryProfile,
+}
+
+impl VMConfig for Profiler {}
+
+impl Profiler {
+    pub fn new(program: &Program, memory_size: usize) -> Self {
+        // Map every opcode to the index of its first uncompressed instruction
+        let ip_map = program.code.iter().fold(vec![0], |mut acc, opcode| {
+            acc.push(acc[acc.len() - 1] + opcode.count());
+            acc
+        });
+        let code_len = ip_map[ip_map.len() - 1];
+        Profiler {
+            profile_data: vec![0; code_len],
+            ip_map,
+            loops: HashMap::new(),
+            active: Vec::new(),
+            steps: 0,
+            memory: MemoryProfile::new(memory_size),
+        }
+    }
+
+    /// Record the execution of an opcode.
+    ///
+    /// # Parameters
+    ///     ip       - index of the opcode
+    ///     opcode   - the executed opcode
+    ///     cell     - value of the current cell before the opcode was executed
+    ///     data_ptr - data pointer before the opcode was executed
+    ///
+    pub fn record(&mut self, ip: usize, opcode: OpCode, cell: u8, data_ptr: usize) {
+        self.steps += 1;
+        let uncompressed_ip = self.ip_map[ip];
+        (0..opcode.count()).for_each(|index| self.profile_data[uncompressed_ip + index] += 1);
+
+        match opcode {
+            OpCode::LoopStart => {
+                let profile = self.loops.entry(ip).or_default();
+                profile.entries += 1;
+                if cell == 0 {
+                    profile.steps += 1;
+                } else {
+                    profile.iterations += 1;
+                    self.active.push(ActiveLoop {
+                        start: ip,
+                        entry_step: self.steps,
+                        iteration_data_ptr: data_ptr,
+                    });
+                }
+            }
+            OpCode::LoopEnd => {
+                let Some(active) = self.active.last_mut() else {
+                    return;
+                };
+                let profile = self.loops.entry(active.start).or_default();
+                profile.drift += data_ptr as isize - active.iteration_data_ptr as isize;
+                if cell == 0 {
+                    profile.steps += self.steps - active.entry_step + 1;
+                    self.active.pop();
+                } else {
+                    profile.iterations += 1;
+                    active.iteration_data_ptr = data_ptr;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Record the memory accesses of an executed opcode. See `MemoryProfile::record`.
+    pub fn record_memory(&mut self, opcode: OpCode, address: usize, value: u8, data_ptr: usize) {
+        self.memory.record(opcode, address, value, data_ptr);
+    }
+
+    /// Execution count per uncompressed instruction.
+    pub fn profile_data(&self) -> &Vec<usize> {
+        &self.profile_data
+    }
+
+    /// Profiles of all executed loops, indexed by the opcode index of their `[`.
+    pub fn loops(&self) -> &HashMap<usize, LoopProfile> {
+        &self.loops
+    }
+
+    /// Memory access statistics.
+    pub fn memory(&self) -> &MemoryProfile {
+        &self.memory
+    }
+
+    /// Total number of executed steps.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Generate a report of the {limit} loops with the most steps.
+    pub fn loop_report(&self, program: &Program, limit: usize) -> String {
+        let mut loops = self.loops().iter().collect::<Vec<_>>();
+        loops.sort_by_key(|(ip, _)| **ip);
+        let rows = loops
+            .into_iter()
+            .map(|(ip, profile)| LoopRow {
+                location: program.location(*ip),
+                entries: profile.entries as u64,
+                iterations: profile.iterations as u64,
+                details: Some(LoopDetails {
+                    ip: *ip,
+                    steps: profile.steps as u64,
+                    drift: profile.drift,
+                }),
+            })
+            .collect();
+        loop_report(rows, LoopWeight::Steps(self.steps() as u64), limit)
+    }
+}
+
+/// A loop of a hot-loop report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopRow {
+    pub location: Option<SourceLocation>,
+    /// Number of times the loop was entered
+    pub entries: u64,
+    /// Number of times the loop body was executed
+    pub iterations: u64,
+    /// Data that only the interpreter collects
+    pub details: Option<LoopDetails>,
+}
+
+/// Data of a loop that only the interpreter collects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopDetails {
+    /// Opcode index of the `[`
+    pub ip: usize,
+    /// Number of steps executed inside of the loop
+    pub steps: u64,
+    /// Sum of the data pointer changes over all iterations
+    pub drift: isize,
+}
+
+/// What the loops of a hot-loop report are ranked by, with the total their share is relative to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopWeight {
+    /// Steps inside of the loop, requires the details of every loop
+    Steps(u64),
+    /// Iterations of the loop
+    Iterations(u64),
+}
+
+/// Generate a report of the {limit} loops with the highest {weight}. Loops of the same weight keep
+/// the order of {rows}. The IP, steps and drift columns are only included when ranking by steps.
+pub fn loop_report(mut rows: Vec<LoopRow>, weight: LoopWeight, limit: usize) -> String {
+    let (detailed, total) = match weight {
+        LoopWeight::Steps(total) => (true, total),
+        LoopWeight::Iterations(total) => (false, total),
+    };
+    let steps = |row: &LoopRow| row.details.as_ref().map_or(0, |details| details.steps);
+    let value = |row: &LoopRow| if detailed { steps(row) } else { row.iterations };
+    rows.sort_by_key(|row| Reverse(value(row)));
+    let ratio = |value: f64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            value / total as f64
+        }
+    };
+
+    let mut report = format!("{:>4}  {:>9}  ", "RANK", "LOCATION");
+    if detailed {
+        report.push_str(&format!("{:>6}  ", "IP"));
+    }
+    report.push_str(&format!(
+        "{:>10}  {:>12}  {:>10}  ",
+        "ENTRIES", "ITERATIONS", "ITER/ENTRY"
+    ));
+    if detailed {
+        report.push_str(&format!(
+            "{:>14}  {:>7}  {:>10}\n",
+            "STEPS", "STEPS%", "DRIFT/ITER"
+        ));
+    } else {
+        report.push_str(&format!("{:>7}\n", "ITER%"));
+    }
+    rows.iter().take(limit).enumerate().for_each(|(rank, row)| {
+        let location = row
+            .location
+            .map(|location| format!("{}:{}", location.line, location.column))
+            .unwrap_or_default();
+        report.push_str(&format!("{:>4}  {:>9}  ", rank + 1, location));
+        let details = row.details.as_ref().filter(|_| detailed);
+        if let Some(details) = details {
+            report.push_str(&format!("{:>6}  ", details.ip));
+        }
+        report.push_str(&format!(
+            "{:>10}  {:>12}  {:>10.1}  ",
+            row.entries,
+            row.iterations,
+            ratio(row.iterations as f64, row.entries),
+        ));
+        match details {
+            Some(details) => report.push_str(&format!(
+                "{:>14}  {:>6.2}%  {:>+10.2}\n",
+                details.steps,
+                ratio(100.0 * details.steps as f64, total),
+                ratio(details.drift as f64, row.iterations),
+            )),
+            None => report.push_str(&format!(
+                "{:>6.2}%\n",
+                ratio(100.0 * row.iterations as f64, total)
+            )),
+        }
+    });
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::io::string::{StdinString, StdoutString};
+    use super::super::program::SourceLocation;
+    use super::super::vm::{EvalError, Normal, VM};
+    use super::{LoopRow, LoopWeight, loop_report};
+
+    #[test]
+    fn test_loop_profile() -> Result<(), EvalError> {
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let code = "++[>+++[>+<-]<-]>>[>]";
+        let vm = VM::<Normal>::new(code, Box::new(stdin), Box::new(stdout), true, 16)?;
+        let mut vm = vm.enable_profiler();
+        vm.run()?;
+
+        let profiler = vm.profiler();
+        assert_eq!(profiler.profile_data().len(), code.len());
+        assert_eq!(profiler.profile_data()[0], 1);
+        assert_eq!(profiler.profile_data()[1], 1);
+        assert_eq!(profiler.profile_data()[4], 2);
+        assert_eq!(profiler.profile_data()[9], 6);
+
+        let program = vm.program();
+        let outer = profiler.loops()[&1].clone();
+        assert_eq!(outer.entries, 1);
+        assert_eq!(outer.iterations, 2);
+        assert_eq!(outer.drift, 0);
+        assert_eq!(outer.steps, profiler.steps() - 1 - 4);
+
+        let inner = profiler.loops()[&4].clone();
+        assert_eq!(inner.entries, 2);
+        assert_eq!(inner.iterations, 6);
+        assert_eq!(inner.drift, 0);
+        assert_eq!(inner.steps, 2 * (1 + 3 * 5));
+
+        let drifting = profiler.loops()[&14].clone();
+        assert_eq!(drifting.entries, 1);
+        assert_eq!(drifting.iterations, 1);
+        assert_eq!(drifting.drift, 1);
+
+        let report = profiler.loop_report(program, 2);
+        assert_eq!(report.lines().count(), 3);
+        assert!(report.lines().nth(1).unwrap().contains("1:3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_iteration_report() {
+        let row = |column, iterations| LoopRow {
+            location: Some(SourceLocation {
+                offset: column - 1,
+                line: 1,
+                column,
+            }),
+            entries: 1,
+            iterations,
+            details: None,
+        };
+        let rows = vec![row(1, 10), row(5, 30), row(9, 10)];
+        let report = loop_report(rows, LoopWeight::Iterations(50), 2);
+        let lines = report.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("ITER%"));
+        assert!(!lines[0].contains("STEPS"));
+        assert!(lines[1].contains("1:5"));
+        assert!(lines[1].ends_with("60.00%"));
+        // Loops with the same number of iterations keep their order
+        assert!(lines[2].contains("1:1"));
+    }
+}
@@ -32,18 +32,57 @@ use std::{cmp::max, collections::HashMap};
 use thiserror::Error;
 
 use super::super::vm::vm::EvalError;
+use super::diagnostic;
 use super::opcode::OpCode;
 
 #[derive(Debug)]
 pub struct Program {
     pub code: Vec<OpCode>,
     pub jump_table: HashMap<usize, usize>,
+    // The position in the source code of every opcode
+    pub locations: Vec<SourceLocation>,
+}
+
+/// Position of an opcode in the original source code. Line and column are 1-based.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceLocation {
+    // Offset in characters from the start of the source
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
 #[derive(Debug, Error)]
 pub enum ProgramError {
-    #[error("Found unmatched bracket at: {0}")]
-    MissingBracket(usize),
+    #[error("Unmatched ']' at line {}, column {}", .location.line, .location.column)]
+    UnmatchedClose {
+        location: SourceLocation,
+        hint: Option<String>,
+    },
+    #[error("Unclosed '[' at line {}, column {}", .location.line, .location.column)]
+    UnclosedLoop {
+        location: SourceLocation,
+        hint: Option<String>,
+    },
+}
+
+impl ProgramError {
+    /// Render the error with an excerpt of the {source} read from {filename}.
+    pub fn render(&self, filename: &str, source: &str) -> String {
+        let (message, label, location, hint) = match self {
+            ProgramError::UnmatchedClose { location, hint } => {
+                ("unmatched ']'", "there is no loop to close", location, hint)
+            }
+            ProgramError::UnclosedLoop { location, hint } => {
+                ("unclosed '['", "this loop is never closed", location, hint)
+            }
+        };
+        let notes = hint
+            .iter()
+            .map(|hint| format!("hint: {}", hint))
+            .collect::<Vec<_>>();
+        diagnostic::render(filename, source, *location, message, label, &notes)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -66,6 +105,28 @@ impl Step {
     }
 }
 
+impl SourceLocation {
+    /// Pair every character of the source with its location.
+    pub fn annotate(source: &str) -> impl Iterator<Item = (char, SourceLocation)> + '_ {
+        let mut line = 1;
+        let mut column = 1;
+        source.chars().enumerate().map(move |(offset, value)| {
+            let location = SourceLocation {
+                offset,
+                line,
+                column,
+            };
+            if value == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+            (value, location)
+        })
+    }
+}
+
 impl Program {
     /// CReate a new program based on the provided brainfuck code.
     ///
@@ -77,68 +138,103 @@ impl Program {
     /// # Errors
     ///
     pub fn new(program_string: &str, rle: bool) -> Result<Self, ProgramError> {
-        let mut code: Vec<OpCode> = program_string
-            .chars()
-            .filter_map(OpCode::convert)
-            .collect();
+        Program::parse(program_string, rle, OpCode::convert)
+    }
+
+    /// Create a program using the extended syntax, where `#` dumps the state of the VM. The
+    /// input after a `!` has to be removed with `split_input` first.
+    pub fn new_extended(program_string: &str, rle: bool) -> Result<Self, ProgramError> {
+        Program::parse(program_string, rle, OpCode::convert_extended)
+    }
+
+    fn parse(
+        program_string: &str,
+        rle: bool,
+        convert: fn(char) -> Option<OpCode>,
+    ) -> Result<Self, ProgramError> {
+        let mut code_with_locations: Vec<(OpCode, SourceLocation)> =
+            SourceLocation::annotate(program_string)
+                .filter_map(|(value, location)| Some((convert(value)?, location)))
+                .collect();
 
         if rle {
-            code = code.iter().fold(vec![], |mut acc, current| -> Vec<OpCode> {
-                // check if we can merge with the top
-                if let Some(top) = acc.pop() {
-                    match (top, current) {
-                        (OpCode::DecDataPtr(count), OpCode::DecDataPtr(1)) => {
-                            acc.push(OpCode::DecDataPtr(count + 1))
-                        }
-                        (OpCode::IncDataPtr(count), OpCode::IncDataPtr(1)) => {
-                            acc.push(OpCode::IncDataPtr(count + 1))
-                        }
-                        (OpCode::DecValue(count), OpCode::DecValue(1)) => {
-                            acc.push(OpCode::DecValue(count + 1))
-                        }
-                        (OpCode::IncValue(count), OpCode::IncValue(1)) => {
-                            acc.push(OpCode::IncValue(count + 1))
-                        }
-                        (t, c) => {
-                            acc.push(t);
-                            acc.push(*c);
+            code_with_locations = code_with_locations.iter().fold(
+                vec![],
+                |mut acc, (current, location)| -> Vec<(OpCode, SourceLocation)> {
+                    // check if we can merge with the top. The merged opcode keeps the location of
+                    // the first character.
+                    if let Some((top, top_location)) = acc.pop() {
+                        match (top, current) {
+                            (OpCode::DecDataPtr(count), OpCode::DecDataPtr(1)) => {
+                                acc.push((OpCode::DecDataPtr(count + 1), top_location))
+                            }
+                            (OpCode::IncDataPtr(count), OpCode::IncDataPtr(1)) => {
+                                acc.push((OpCode::IncDataPtr(count + 1), top_location))
+                            }
+                            (OpCode::DecValue(count), OpCode::DecValue(1)) => {
+                                acc.push((OpCode::DecValue(count + 1), top_location))
+                            }
+                            (OpCode::IncValue(count), OpCode::IncValue(1)) => {
+                                acc.push((OpCode::IncValue(count + 1), top_location))
+                            }
+                            (t, c) => {
+                                acc.push((t, top_location));
+                                acc.push((*c, *location));
+                            }
                         }
+                    } else {
+                        acc.push((*current, *location));
                     }
-                } else {
-                    acc.push(*current);
-                }
-                acc
-            });
+                    acc
+                },
+            );
         }
+        let (code, locations): (Vec<OpCode>, Vec<SourceLocation>) =
+            code_with_locations.into_iter().unzip();
         let mut jump_table = HashMap::new();
 
         // compute jump table
         let mut stack = vec![];
-        let result_jump_table: Result<(), ProgramError> = code
-            .iter()
-            .enumerate().try_for_each(|(index, opcode)| match opcode {
-                OpCode::LoopStart => {
-                    stack.push(index);
-                    Ok(())
-                }
+        for (index, opcode) in code.iter().enumerate() {
+            match opcode {
+                OpCode::LoopStart => stack.push(index),
                 OpCode::LoopEnd => {
                     // Check if we have unmatched closed bracket
-                    if stack.is_empty() {
-                        return Err(ProgramError::MissingBracket(index));
-                    }
-                    let start = stack.pop().unwrap();
+                    let Some(start) = stack.pop() else {
+                        return Err(ProgramError::UnmatchedClose {
+                            location: locations[index],
+                            hint: imbalance_hint(program_string, &locations, &jump_table),
+                        });
+                    };
                     jump_table.insert(start, index);
                     jump_table.insert(index, start);
-                    Ok(())
                 }
-                _ => Ok(()),
-            });
-        result_jump_table?;
+                _ => (),
+            }
+        }
         // Check if we have unmatch open bracket
-        if let Some(index) = stack.pop() {
-            return Err(ProgramError::MissingBracket(index));
+        if let Some(&index) = stack.last() {
+            let hint = imbalance_hint(program_string, &locations, &jump_table).or_else(|| {
+                let outermost = locations[stack[0]];
+                (1 < stack.len()).then(|| {
+                    format!(
+                        "{} loops are not closed, the outermost starts at line {}, column {}",
+                        stack.len(),
+                        outermost.line,
+                        outermost.column
+                    )
+                })
+            });
+            return Err(ProgramError::UnclosedLoop {
+                location: locations[index],
+                hint,
+            });
         }
-        Ok(Program { code, jump_table })
+        Ok(Program {
+            code,
+            jump_table,
+            locations,
+        })
     }
 
     /// Return the step at the provided position of the program.
@@ -182,11 +278,19 @@ impl Program {
         })
     }
 
-    pub fn listing(
-        &self,
-        start: Option<usize>,
-        end: Option<usize>,
-    ) -> Result<String, EvalError> {
+    /// Return the source location of the opcode at the provided position.
+    pub fn location(&self, ip: usize) -> Option<SourceLocation> {
+        self.locations.get(ip).copied()
+    }
+
+    /// Return the index of the first opcode on the provided (1-based) source line.
+    pub fn ip_for_line(&self, line: usize) -> Option<usize> {
+        self.locations
+            .iter()
+            .position(|location| location.line == line)
+    }
+
+    pub fn listing(&self, start: Option<usize>, end: Option<usize>) -> Result<String, EvalError> {
         // compute jump table
         let mut stack = vec![];
         let mut max_indent = 0;
@@ -263,9 +367,56 @@ impl Program {
     }
 }
 
+/// Split the {source} of the extended syntax at the first `!` into the program and the input of
+/// the program.
+pub fn split_input(source: &str) -> (&str, Option<&str>) {
+    match source.split_once('!') {
+        Some((program, input)) => (program, Some(input)),
+        None => (source, None),
+    }
+}
+
+/// Look for the first loop whose brackets both start a line but have a different indentation.
+/// In formatted code this is likely where the brackets got unbalanced.
+fn imbalance_hint(
+    source: &str,
+    locations: &[SourceLocation],
+    jump_table: &HashMap<usize, usize>,
+) -> Option<String> {
+    let lines = source.lines().collect::<Vec<_>>();
+    // Indentation of a bracket that is the first character on its line
+    let indentation = |location: SourceLocation| {
+        let line = lines.get(location.line - 1)?;
+        let indentation = line.chars().take_while(|c| c.is_whitespace()).count();
+        (indentation + 1 == location.column).then_some(indentation)
+    };
+    let mut loops = jump_table
+        .iter()
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| (locations[*start], locations[*end]))
+        .collect::<Vec<_>>();
+    loops.sort_by_key(|(start, _)| start.offset);
+    loops
+        .into_iter()
+        .find(|(start, end)| {
+            start.line != end.line
+                && matches!(
+                    (indentation(*start), indentation(*end)),
+                    (Some(a), Some(b)) if a != b
+                )
+        })
+        .map(|(start, end)| {
+            format!(
+                "the '[' at line {}, column {} is closed by the ']' at line {}, column {}, \
+                 which is indented differently; the imbalance likely starts there",
+                start.line, start.column, end.line, end.column
+            )
+        })
+}
+
 impl Display for Program {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let code_string: String = self.code.iter().map(OpCode::to_char).collect();
+        let code_string: String = self.code.iter().map(|opcode| opcode.to_char()).collect();
         formatter.write_str(&code_string)?;
         Ok(())
     }
@@ -274,13 +425,46 @@ impl Display for Program {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::vec;
 
     #[test]
     fn test_missmatched_loops_open() {
         let code = "[[[]]";
         let program = Program::new(code, false);
         assert!(program.is_err());
+
+        let code = "+\n[\n  [-\n  >+<\n]\n";
+        let Err(ProgramError::UnclosedLoop { location, hint }) = Program::new(code, true) else {
+            panic!("Expected an unclosed loop");
+        };
+        assert_eq!((location.line, location.column), (2, 1));
+        assert_eq!(
+            hint.as_deref(),
+            Some(
+                "the '[' at line 3, column 3 is closed by the ']' at line 5, column 1, which is \
+                 indented differently; the imbalance likely starts there"
+            )
+        );
+        let rendered = ProgramError::UnclosedLoop {
+            location,
+            hint: None,
+        }
+        .render("a.bf", code);
+        assert_eq!(
+            rendered,
+            "error: unclosed '['\n --> a.bf:2:1\n  |\n2 | [\n  | ^ this loop is never closed\n"
+        );
+    }
+
+    #[test]
+    fn test_extended_syntax() {
+        // Debug instructions are never merged
+        let program = Program::new_extended("+#+#>>", true).unwrap();
+        assert_eq!(program.to_string(), "+#+#>");
+        assert_eq!(program.code[4], OpCode::IncDataPtr(2));
+        assert_eq!(Program::new("+#+", true).unwrap().to_string(), "+");
+
+        assert_eq!(split_input(",.!a!b"), (",.", Some("a!b")));
+        assert_eq!(split_input(",."), (",.", None));
     }
 
     #[test]
@@ -288,6 +472,15 @@ mod tests {
         let code = "[[[]]]]";
         let program = Program::new(code, false);
         assert!(program.is_err());
+
+        let code = "[-]\n\t+]";
+        let Err(error) = Program::new(code, true) else {
+            panic!("Expected an unmatched bracket");
+        };
+        assert_eq!(
+            error.render("b.bf", code),
+            "error: unmatched ']'\n --> b.bf:2:3\n  |\n2 | \t+]\n  | \t ^ there is no loop to close\n"
+        );
     }
 
     #[test]
@@ -301,7 +494,7 @@ mod tests {
         assert_eq!(code, program.to_string());
 
         // Check random
-        for (ip, expected) in vec![
+        for (ip, expected) in [
             (0, Step::new(OpCode::IncValue(1), Some(1), None)),
             (9, Step::new(OpCode::IncDataPtr(1), Some(10), None)),
             (30, Step::new(OpCode::DecDataPtr(1), Some(31), None)),
@@ -311,7 +504,7 @@ mod tests {
         }
 
         // Check loops
-        for (start, end) in vec![(8, 48), (14, 33), (43, 45)] {
+        for (start, end) in [(8, 48), (14, 33), (43, 45)] {
             assert_eq!(
                 program.get_step(start),
                 Some(Step::new(OpCode::LoopStart, Some(start + 1), Some(end + 1)))
@@ -323,6 +516,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_locations() {
+        // The merged RLE opcode keeps the location of its first character
+        let code = "+ comment\n ++[\n-]";
+        let program = Program::new(code, true).unwrap();
+        assert_eq!(
+            program.locations,
+            vec![
+                SourceLocation {
+                    offset: 0,
+                    line: 1,
+                    column: 1
+                },
+                SourceLocation {
+                    offset: 13,
+                    line: 2,
+                    column: 4
+                },
+                SourceLocation {
+                    offset: 15,
+                    line: 3,
+                    column: 1
+                },
+                SourceLocation {
+                    offset: 16,
+                    line: 3,
+                    column: 2
+                },
+            ]
+        );
+        assert_eq!(program.ip_for_line(2), Some(1));
+        assert_eq!(program.ip_for_line(4), None);
+    }
+
     #[test]
     fn test_optimization() {
         let code = "++++++++++[----------]";
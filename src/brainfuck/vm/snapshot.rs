@@ -0,0 +1,198 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::program::Program;
+use super::vm::EvalError;
+
+// Version 2 added the step count
+const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Cannot access snapshot file")]
+    IOError(#[from] io::Error),
+    #[error("Invalid snapshot file: {0}")]
+    FormatError(#[from] serde_json::Error),
+    #[error("Snapshot version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error("Snapshot was taken for a different program or RLE setting")]
+    ProgramMismatch,
+    #[error("Snapshot contains an invalid state: {0}")]
+    EvalError(#[from] EvalError),
+}
+
+/// The complete state of a VM and the debugger breakpoints.
+///
+/// Snapshots are stored as JSON. The memory is stored as hex string to keep the file small.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    // Fingerprint of the program the snapshot belongs to
+    pub program: String,
+    pub ip: usize,
+    pub data_ptr: usize,
+    // Number of executed instructions
+    pub steps: usize,
+    #[serde(with = "hex_bytes")]
+    pub memory: Vec<u8>,
+    // The input consumed so far
+    pub input: String,
+    // The output generated so far
+    pub output: String,
+    pub breakpoints: Vec<usize>,
+}
+
+impl Snapshot {
+    pub fn new(
+        program: &Program,
+        ip: usize,
+        data_ptr: usize,
+        steps: usize,
+        memory: Vec<u8>,
+        input: String,
+        output: String,
+    ) -> Self {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            program: Snapshot::fingerprint(program),
+            ip,
+            data_ptr,
+            steps,
+            memory,
+            input,
+            output,
+            breakpoints: vec![],
+        }
+    }
+
+    /// FNV-1a hash over the opcodes. Instruction pointers are only meaningful for the same
+    /// opcodes, so the RLE setting is part of the fingerprint as well. Every opcode is encoded as
+    /// its character and count, which does not depend on the names of the `OpCode` variants.
+    pub fn fingerprint(program: &Program) -> String {
+        let hash = program
+            .code
+            .iter()
+            .map(|opcode| format!("{}{}", opcode.to_char(), opcode.count()))
+            .collect::<String>()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        format!("{:016x}", hash)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if !hex.len().is_multiple_of(2) {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|index| {
+                hex.get(index..index + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or(D::Error::custom("invalid hex digits"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = |rle| Snapshot::fingerprint(&Program::new("++[>,.<]", rle).unwrap());
+        // The fingerprint of "+2[1>1,1.1<1]1" must stay the same for the saved snapshots
+        assert_eq!(fingerprint(true), "8baca4a250e956d2");
+        assert_ne!(fingerprint(true), fingerprint(false));
+    }
+
+    #[test]
+    fn test_save_load() -> Result<(), SnapshotError> {
+        let program = Program::new("+[>,.<]", true).unwrap();
+        let mut snapshot = Snapshot::new(
+            &program,
+            3,
+            1,
+            7,
+            vec![1, 0xab, 0, 0],
+            "x".to_string(),
+            "x".to_string(),
+        );
+        snapshot.breakpoints = vec![2, 5];
+        let path = std::env::temp_dir().join(format!("snapshot_{}.json", std::process::id()));
+        snapshot.save(&path)?;
+        let content = std::fs::read_to_string(&path)?;
+        assert!(content.contains("\"01ab0000\""));
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path)?;
+        assert_eq!(loaded?, snapshot);
+
+        // Snapshots without the step count are rejected
+        let content = content.replace("\"version\": 2", "\"version\": 1");
+        std::fs::write(&path, content)?;
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path)?;
+        assert!(matches!(loaded, Err(SnapshotError::UnsupportedVersion(1))));
+        Ok(())
+    }
+}
@@ -34,23 +34,22 @@ use thiserror::Error;
 use super::super::io::base::Stdin;
 use super::super::io::base::Stdout;
 
+use super::fault::Fault;
 use super::opcode::OpCode;
+use super::profiler::Profiler;
 use super::program::Program;
 use super::program::ProgramError;
+use super::snapshot::{Snapshot, SnapshotError};
+
+// Number of cells in front of and behind the data pointer in a state dump
+const DUMP_RADIUS: usize = 8;
 
 pub trait VMConfig {}
 
 #[derive(Debug)]
 pub struct Normal;
 
-#[derive(Debug)]
-pub struct Profiler {
-    profile_data: Vec<usize>,
-    ip_map: Vec<usize>,
-}
-
 impl VMConfig for Normal {}
-impl VMConfig for Profiler {}
 
 #[derive(Debug)]
 pub struct VM<S: VMConfig> {
@@ -60,6 +59,10 @@ pub struct VM<S: VMConfig> {
     program: Program,
     stdin: Box<dyn Stdin>,
     stdout: Box<dyn Stdout>,
+    // Number of executed (uncompressed) instructions
+    steps: usize,
+    // Print the state to stderr at `#` instructions of the extended syntax
+    print_dumps: bool,
     config: S,
 }
 
@@ -67,10 +70,19 @@ pub struct VM<S: VMConfig> {
 pub enum EvalError {
     #[error("Program Error")]
     ProgramError(#[from] ProgramError),
-    #[error("Memory Out of Bounds error")]
-    MemoryOutOfBounds,
-    #[error("Invalid Instruction Pointer")]
-    InvalidInstructionPointer,
+    /// An access to the memory at {address} outside of the {memory_size} cells
+    #[error(
+        "Memory Out of Bounds error: cell {address} is outside of the memory of {memory_size} cells"
+    )]
+    MemoryOutOfBounds { address: usize, memory_size: usize },
+    /// The instruction pointer {ip} is outside of the program of {length} instructions
+    #[error(
+        "Invalid Instruction Pointer: instruction {ip} is outside of the program of {length} instructions"
+    )]
+    InvalidInstructionPointer { ip: usize, length: usize },
+    /// The program moved the data pointer out of the memory
+    #[error("Data Pointer Out of Bounds error")]
+    DataPointerOutOfBounds(Box<Fault>),
     #[error("I/O error")]
     IOError(#[from] io::Error),
 }
@@ -84,16 +96,66 @@ impl<S: VMConfig> VM<S> {
         memory_size: usize,
     ) -> Result<VM<Normal>, EvalError> {
         let program = Program::new(program_code, rle)?;
+        Ok(VM::<Normal>::with_program(
+            program,
+            stdin,
+            stdout,
+            memory_size,
+        ))
+    }
 
-        Ok(VM {
+    /// Create a VM for an already parsed program, e.g., one using the extended syntax.
+    pub fn with_program(
+        program: Program,
+        stdin: Box<dyn Stdin>,
+        stdout: Box<dyn Stdout>,
+        memory_size: usize,
+    ) -> VM<Normal> {
+        VM {
             ip: 0,
             data_ptr: 0,
             memory: vec![0; memory_size],
             program,
             stdin,
             stdout,
+            steps: 0,
+            print_dumps: false,
             config: Normal {},
-        })
+        }
+    }
+
+    /// Print the state of the VM to stderr whenever a `#` instruction is executed.
+    pub fn enable_dumps(&mut self) {
+        self.print_dumps = true;
+    }
+
+    /// Describe the instruction pointer, the data pointer and the cells around the data pointer.
+    /// Trailing zero cells behind the data pointer are left out.
+    pub fn state_dump(&self) -> String {
+        let start = self.data_ptr.saturating_sub(DUMP_RADIUS);
+        let last = self
+            .memory
+            .iter()
+            .rposition(|value| *value != 0)
+            .unwrap_or(0);
+        let end = last
+            .max(self.data_ptr)
+            .min(self.data_ptr + DUMP_RADIUS)
+            .min(self.memory.len() - 1);
+        let cells = (start..=end)
+            .map(|address| {
+                if address == self.data_ptr {
+                    format!("[{}]", self.memory[address])
+                } else {
+                    self.memory[address].to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            "# ip={} data_ptr={} cells {}..={}: {}\n",
+            self.ip, self.data_ptr, start, end, cells
+        )
     }
 
     /// Access the IP address of the VM
@@ -105,8 +167,8 @@ impl<S: VMConfig> VM<S> {
         self.data_ptr
     }
 
-    pub fn stdout(&self) -> &Box<dyn Stdout> {
-        &self.stdout
+    pub fn stdout(&self) -> &dyn Stdout {
+        self.stdout.as_ref()
     }
 
     pub fn program(&self) -> &Program {
@@ -117,33 +179,162 @@ impl<S: VMConfig> VM<S> {
         &self.memory
     }
 
+    /// Returns the input consumed so far.
+    pub fn input(&self) -> String {
+        self.stdin.consumed()
+    }
+
+    /// Number of characters consumed so far.
+    pub fn input_len(&self) -> usize {
+        self.stdin.consumed_len()
+    }
+
+    /// Reset the input so that exactly {consumed} has been read, see `Stdin::rewind`.
+    pub fn rewind_input(&mut self, consumed: &str) {
+        self.stdin.rewind(consumed)
+    }
+
+    /// Number of instructions executed so far. RLE opcodes count as their uncompressed
+    /// instructions.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Reset the number of executed instructions, e.g., after a step was undone.
+    pub fn set_steps(&mut self, steps: usize) {
+        self.steps = steps;
+    }
+
+    /// Error for the current instruction moving the data pointer by {movement} cells out of the
+    /// memory.
+    fn out_of_bounds(&self, opcode: OpCode, movement: isize) -> EvalError {
+        EvalError::DataPointerOutOfBounds(Box::new(Fault::new(
+            self.ip,
+            opcode,
+            self.program.location(self.ip),
+            self.data_ptr,
+            movement,
+            Some(self.steps),
+            &self.memory,
+        )))
+    }
+
+    fn invalid_ip(&self, ip: usize) -> EvalError {
+        EvalError::InvalidInstructionPointer {
+            ip,
+            length: self.program.code.len(),
+        }
+    }
+
+    fn outside_memory(&self, address: usize) -> EvalError {
+        EvalError::MemoryOutOfBounds {
+            address,
+            memory_size: self.memory.len(),
+        }
+    }
+
+    /// Move the instruction pointer to the given opcode index.
+    pub fn set_ip(&mut self, ip: usize) -> Result<(), EvalError> {
+        if self.program.code.len() <= ip {
+            return Err(self.invalid_ip(ip));
+        }
+        self.ip = ip;
+        Ok(())
+    }
+
+    /// Move the data pointer to the given memory cell.
+    pub fn set_data_ptr(&mut self, data_ptr: usize) -> Result<(), EvalError> {
+        if self.memory.len() <= data_ptr {
+            return Err(self.outside_memory(data_ptr));
+        }
+        self.data_ptr = data_ptr;
+        Ok(())
+    }
+
+    /// Overwrite the memory starting at {start} with the given values.
+    pub fn write_memory(&mut self, start: usize, values: &[u8]) -> Result<(), EvalError> {
+        let end = start.saturating_add(values.len());
+        if self.memory.len() < end {
+            return Err(self.outside_memory(start.max(self.memory.len())));
+        }
+        self.memory[start..end].copy_from_slice(values);
+        Ok(())
+    }
+
+    /// Set {len} memory cells starting at {start} to {value}.
+    pub fn fill_memory(&mut self, start: usize, len: usize, value: u8) -> Result<(), EvalError> {
+        let end = start.saturating_add(len);
+        if self.memory.len() < end {
+            return Err(self.outside_memory(start.max(self.memory.len())));
+        }
+        self.memory[start..end].fill(value);
+        Ok(())
+    }
+
+    /// Capture the complete state of the VM.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(
+            &self.program,
+            self.ip,
+            self.data_ptr,
+            self.steps,
+            self.memory.clone(),
+            self.stdin.consumed(),
+            self.stdout.to_string(),
+        )
+    }
+
+    /// Restore a state captured by `snapshot`. The memory size of the snapshot replaces the
+    /// current memory size.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if snapshot.program != Snapshot::fingerprint(&self.program) {
+            return Err(SnapshotError::ProgramMismatch);
+        }
+        if self.program.code.len() <= snapshot.ip && !self.program.code.is_empty() {
+            return Err(self.invalid_ip(snapshot.ip).into());
+        }
+        if snapshot.memory.len() <= snapshot.data_ptr {
+            return Err(EvalError::MemoryOutOfBounds {
+                address: snapshot.data_ptr,
+                memory_size: snapshot.memory.len(),
+            }
+            .into());
+        }
+        self.ip = snapshot.ip;
+        self.data_ptr = snapshot.data_ptr;
+        self.steps = snapshot.steps;
+        self.memory = snapshot.memory.clone();
+        self.stdin.rewind(&snapshot.input);
+        self.stdout.reset(&snapshot.output);
+        Ok(())
+    }
+
     /// Function to execute exactly one step if there is a step left.
     pub fn execute_step(&mut self) -> Result<bool, EvalError> {
         let step_option = self.program.get_step(self.ip);
         debug!(
             "Stepping ip={} => {:?}",
             self.ip,
-            if step_option.is_some() {
-                format!("{:?}", step_option.unwrap())
+            if let Some(step) = step_option {
+                format!("{:?}", step)
             } else {
                 "NONE".to_string()
             }
         );
-        if step_option.is_none() {
+        let Some(step) = step_option else {
             return Ok(true);
-        }
-        let step = step_option.unwrap();
+        };
         let next_ip_option = match step.opcode {
             OpCode::DecDataPtr(count) => {
                 if self.data_ptr < count {
-                    return Err(EvalError::MemoryOutOfBounds);
+                    return Err(self.out_of_bounds(step.opcode, -(count as isize)));
                 }
                 self.data_ptr -= count;
                 step.then_ip
             }
             OpCode::IncDataPtr(count) => {
                 if self.data_ptr + count >= self.memory.len() {
-                    return Err(EvalError::InvalidInstructionPointer);
+                    return Err(self.out_of_bounds(step.opcode, count as isize));
                 }
                 self.data_ptr += count;
                 step.then_ip
@@ -166,6 +357,12 @@ impl<S: VMConfig> VM<S> {
                 self.stdout.write(self.memory[self.data_ptr] as char);
                 step.then_ip
             }
+            OpCode::Dump => {
+                if self.print_dumps {
+                    eprint!("{}", self.state_dump());
+                }
+                step.then_ip
+            }
             OpCode::LoopStart | OpCode::LoopEnd => {
                 if self.memory[self.data_ptr] == 0 {
                     step.else_ip
@@ -174,6 +371,7 @@ impl<S: VMConfig> VM<S> {
                 }
             }
         };
+        self.steps += step.opcode.count();
         if let Some(next_ip) = next_ip_option {
             self.ip = next_ip
         } else {
@@ -194,12 +392,7 @@ impl VM<Normal> {
     }
 
     pub fn enable_profiler(self) -> VM<Profiler> {
-        let ip_map = self.program().code.iter().fold(vec![0], |mut acc, opcode| {
-            acc.push(acc[acc.len() - 1] + opcode.count());
-            acc
-        });
-        let code_len = ip_map[ip_map.len() - 1];
-        let profile_data = vec![0, code_len];
+        let profiler = Profiler::new(&self.program, self.memory.len());
 
         VM {
             ip: self.ip,
@@ -208,30 +401,32 @@ impl VM<Normal> {
             program: self.program,
             stdin: self.stdin,
             stdout: self.stdout,
-            config: Profiler {
-                profile_data,
-                ip_map,
-            },
+            steps: self.steps,
+            print_dumps: self.print_dumps,
+            config: profiler,
         }
     }
 }
 
 impl VM<Profiler> {
     pub fn run(&mut self) -> Result<(), EvalError> {
-        loop {
-            let step_option = self.program.get_step(self.ip).unwrap();
-            let count = step_option.opcode.count();
-            let uncompressed_ip = self.config.ip_map[self.ip];
-            (0..count).for_each(|index| self.config.profile_data[uncompressed_ip + index] += 1);
-            if self.execute_step()? {
+        while let Some(step) = self.program.get_step(self.ip) {
+            let data_ptr = self.data_ptr;
+            let cell = self.memory[data_ptr];
+            self.config.record(self.ip, step.opcode, cell, data_ptr);
+            let finished = self.execute_step()?;
+            let value = self.memory[data_ptr];
+            self.config
+                .record_memory(step.opcode, data_ptr, value, self.data_ptr);
+            if finished {
                 break;
             }
         }
         Ok(())
     }
 
-    pub fn profile_data(&self) -> &Vec<usize> {
-        &self.config.profile_data
+    pub fn profiler(&self) -> &Profiler {
+        &self.config
     }
 }
 
@@ -239,6 +434,7 @@ impl VM<Profiler> {
 mod test {
     use super::super::super::io::string::StdinString;
     use super::super::super::io::string::StdoutString;
+    use super::super::program::split_input;
     use super::*;
     use ctor::ctor;
 
@@ -254,7 +450,149 @@ mod test {
         let stdout = StdoutString::new();
         let mut vm = VM::<Normal>::new(hw, Box::new(stdin), Box::new(stdout), false, 1 << 10)?;
         vm.run()?;
-        assert_eq!("Hello World!\n", format!("{}", vm.stdout().to_string()));
+        assert_eq!("Hello World!\n", vm.stdout().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> Result<(), SnapshotError> {
+        // Echo two characters
+        let code = ",.,.";
+        let new_vm = || {
+            VM::<Normal>::new(
+                code,
+                Box::new(StdinString::new("ab")),
+                Box::new(StdoutString::new()),
+                false,
+                8,
+            )
+        };
+        let mut vm = new_vm()?;
+        vm.execute_step()?;
+        vm.execute_step()?;
+        let snapshot = vm.snapshot();
+        assert_eq!(snapshot.input, "a");
+        assert_eq!(snapshot.output, "a");
+        vm.run()?;
+        assert_eq!("ab", vm.stdout().to_string());
+
+        // Rewinding replays the input
+        vm.restore(&snapshot)?;
+        assert_eq!(vm.ip(), 2);
+        assert_eq!(vm.steps(), 2);
+        assert_eq!("a", vm.stdout().to_string());
+        vm.run()?;
+        assert_eq!("ab", vm.stdout().to_string());
+
+        let mut other = new_vm()?;
+        other.restore(&snapshot)?;
+        assert_eq!(other.memory()[0], b'a');
+        other.run()?;
+        assert_eq!("ab", other.stdout().to_string());
+
+        let mut different = VM::<Normal>::new(
+            ",.",
+            Box::new(StdinString::new("")),
+            Box::new(StdoutString::new()),
+            false,
+            8,
+        )?;
+        assert!(matches!(
+            different.restore(&snapshot),
+            Err(SnapshotError::ProgramMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutators() -> Result<(), EvalError> {
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let mut vm = VM::<Normal>::new(".>.", Box::new(stdin), Box::new(stdout), false, 4)?;
+        vm.write_memory(0, b"Hi")?;
+        vm.fill_memory(2, 2, b'!')?;
+        assert_eq!(vm.memory(), b"Hi!!");
+        assert!(matches!(
+            vm.write_memory(3, b"ab"),
+            Err(EvalError::MemoryOutOfBounds {
+                address: 4,
+                memory_size: 4
+            })
+        ));
+        assert!(matches!(
+            vm.fill_memory(usize::MAX, 4, 0),
+            Err(EvalError::MemoryOutOfBounds {
+                address: usize::MAX,
+                ..
+            })
+        ));
+
+        vm.set_data_ptr(1)?;
+        vm.set_ip(2)?;
+        assert!(vm.set_data_ptr(4).is_err());
+        assert!(matches!(
+            vm.set_ip(3),
+            Err(EvalError::InvalidInstructionPointer { ip: 3, length: 3 })
+        ));
+        vm.run()?;
+        assert_eq!("i", vm.stdout().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_bounds() -> Result<(), EvalError> {
+        let code = "+>+>+\n>>>";
+        for (rle, data_ptr, movement, column) in [(false, 3, 1, 2), (true, 2, 3, 1)] {
+            let stdin = StdinString::new("");
+            let stdout = StdoutString::new();
+            let mut vm = VM::<Normal>::new(code, Box::new(stdin), Box::new(stdout), rle, 4)?;
+            let Err(EvalError::DataPointerOutOfBounds(fault)) = vm.run() else {
+                panic!("Expected the data pointer to leave the memory");
+            };
+            assert_eq!(fault.data_ptr, data_ptr);
+            assert_eq!(fault.movement, movement);
+            assert_eq!(fault.steps, Some(5 + data_ptr - 2));
+            assert_eq!(
+                fault
+                    .location
+                    .map(|location| (location.line, location.column)),
+                Some((2, column))
+            );
+            assert_eq!(fault.tape_start, 0);
+            assert_eq!(fault.tape, vec![1, 1, 1, 0]);
+        }
+
+        let stdin = StdinString::new("");
+        let stdout = StdoutString::new();
+        let mut vm = VM::<Normal>::new("+<", Box::new(stdin), Box::new(stdout), false, 4)?;
+        let Err(EvalError::DataPointerOutOfBounds(fault)) = vm.run() else {
+            panic!("Expected the data pointer to leave the memory");
+        };
+        assert_eq!(
+            fault.render("a.bf", "+<"),
+            "error: data pointer out of bounds\n --> a.bf:1:2\n  |\n1 | +<\n  |  ^ moves the data \
+             pointer from 0 by -1\n  |\n  = instruction 1 ('<' x1) after 1 steps\n  = tape 0x0000: \
+             [01] 00 00 00 (memory size 4)\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_dump() -> Result<(), EvalError> {
+        let (code, input) = split_input("+#>,>#!ab");
+        assert_eq!(input, Some("ab"));
+        let program = Program::new_extended(code, true)?;
+        let stdin = StdinString::new(input.unwrap());
+        let stdout = StdoutString::new();
+        let mut vm = VM::<Normal>::with_program(program, Box::new(stdin), Box::new(stdout), 32);
+        vm.run()?;
+        assert_eq!(vm.steps(), 4);
+        assert_eq!(vm.state_dump(), "# ip=5 data_ptr=2 cells 0..=2: 1 97 [0]\n");
+        vm.set_data_ptr(20)?;
+        assert_eq!(
+            vm.state_dump(),
+            "# ip=5 data_ptr=20 cells 12..=20: 0 0 0 0 0 0 0 0 [0]\n"
+        );
         Ok(())
     }
 }
@@ -28,32 +28,98 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
 mod args;
-mod brainfuck;
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use args::{CLIArgs, Commands};
 use clap::Parser;
 use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
 
-#[cfg(target_arch = "aarch64")]
-use brainfuck::commands::jit::{jit_disassemble, jit_dump, jit_run};
+use brainfuck::commands::analyze::analyze;
+use brainfuck::commands::bench::bench;
+use brainfuck::commands::debug::dap::dap;
 use brainfuck::commands::debug::debug;
+use brainfuck::commands::debug::gdb::gdb;
+use brainfuck::commands::debug::script::script;
+use brainfuck::commands::debug::tui::tui;
 use brainfuck::commands::disassemble::disassemble;
+use brainfuck::commands::fmt::{FmtOptions, fmt};
+use brainfuck::commands::generate::{GenerateOptions, generate};
+#[cfg(target_arch = "aarch64")]
+use brainfuck::commands::jit::{jit_disassemble, jit_dump, jit_run};
+use brainfuck::commands::lint::lint;
+use brainfuck::commands::minify::minify;
+use brainfuck::commands::profile::ProfileOptions;
 use brainfuck::commands::run::run;
+use brainfuck::commands::test::test;
+use brainfuck::commands::verify::verify;
+#[cfg(target_arch = "aarch64")]
+use brainfuck::jit::perf::PerfOptions;
+use brainfuck::vm::preprocessor::PreprocessorError;
+use brainfuck::vm::program::ProgramError;
+use brainfuck::vm::vm::EvalError;
 
+use args::IntCommand;
 #[cfg(target_arch = "aarch64")]
 use args::JITCommand;
-use args::IntCommand;
 
 fn handle_cli_args(cli_args: CLIArgs) -> Result<()> {
     let config = cli_args.config()?;
     match cli_args.command {
-        Commands::INT { command } => match command {
-            IntCommand::Debug { filename: _ } => Ok(debug(config)?),
+        Commands::Int { command } => match command {
+            IntCommand::Debug {
+                dap: true,
+                gdb: _,
+                tui: _,
+                script: _,
+                filename,
+            } => Ok(dap(config, &filename)?),
+            IntCommand::Debug {
+                dap: false,
+                gdb: Some(port),
+                tui: _,
+                script: _,
+                filename: _,
+            } => Ok(gdb(config, port)?),
+            IntCommand::Debug {
+                dap: false,
+                gdb: None,
+                tui: true,
+                script: _,
+                filename: _,
+            } => Ok(tui(config)?),
+            IntCommand::Debug {
+                dap: false,
+                gdb: None,
+                tui: false,
+                script: Some(path),
+                filename: _,
+            } => Ok(script(config, &path)?),
+            IntCommand::Debug {
+                dap: false,
+                gdb: None,
+                tui: false,
+                script: None,
+                filename: _,
+            } => Ok(debug(config)?),
             IntCommand::Run {
                 filename: _,
                 profile,
-            } => Ok(run(config, profile)?),
+                profile_format,
+                memory_profile,
+                heatmap,
+            } => {
+                let options = ProfileOptions {
+                    profile,
+                    format: profile_format,
+                    memory_report: memory_profile,
+                    heatmap,
+                };
+                Ok(run(config, &options)?)
+            }
             IntCommand::Disassemble {
                 filename: _,
                 start,
@@ -64,19 +130,170 @@ fn handle_cli_args(cli_args: CLIArgs) -> Result<()> {
             }
         },
         #[cfg(target_arch = "aarch64")]
-        Commands::JIT { command } => match command {
-            JITCommand::Ast { filename: _ } => Ok(jit_disassemble(config)?),
+        Commands::Jit { opt_level, command } => match command {
+            JITCommand::Ast { filename: _ } => Ok(jit_disassemble(config, opt_level)?),
             JITCommand::Dump {
                 filename: _,
                 output,
-            } => Ok(jit_dump(config, output)?),
-            JITCommand::Run { filename: _ } => Ok(jit_run(config)?),
+            } => Ok(jit_dump(config, opt_level, output)?),
+            JITCommand::Run {
+                profile,
+                perf_map,
+                jitdump,
+                filename: _,
+            } => {
+                let perf = PerfOptions {
+                    map: perf_map,
+                    jitdump,
+                };
+                Ok(jit_run(config, opt_level, profile, perf)?)
+            }
         },
+        Commands::Generate {
+            text,
+            file,
+            cells,
+            no_wrapping,
+        } => {
+            let text = match (text, file) {
+                (_, Some(path)) => fs::read_to_string(path)?,
+                (text, None) => text.unwrap_or_default(),
+            };
+            let options = GenerateOptions {
+                cells: cells as usize,
+                wrapping: !no_wrapping,
+            };
+            println!("{}", generate(&text, &options)?);
+            Ok(())
+        }
+        Commands::Bench {
+            runs,
+            input,
+            filename: _,
+        } => {
+            let input = match input {
+                Some(path) => fs::read_to_string(path)?,
+                None => config.split_input().1.unwrap_or_default().to_string(),
+            };
+            print!("{}", bench(config, runs, &input)?);
+            Ok(())
+        }
+        Commands::Test {
+            engine,
+            step_limit,
+            paths,
+        } => {
+            let report = test(config, &paths, engine, step_limit)?;
+            print!("{}", report);
+            Ok(report.check()?)
+        }
+        Commands::Fmt {
+            indent,
+            width,
+            group,
+            strip_comments,
+            write,
+            filename,
+        } => {
+            if config.source_map.is_some() {
+                bail!("fmt works on the macro source and cannot preprocess it");
+            }
+            let options = FmtOptions {
+                indent,
+                width,
+                group,
+                comments: !strip_comments,
+            };
+            let formatted = fmt(config, &options)?;
+            if write {
+                fs::write(filename, formatted)?;
+            } else {
+                print!("{}", formatted);
+            }
+            Ok(())
+        }
+        Commands::Expand { filename: _ } => {
+            print!("{}", config.code);
+            Ok(())
+        }
+        Commands::Minify { filename: _ } => {
+            println!("{}", minify(config)?);
+            Ok(())
+        }
+        Commands::Analyze { filename: _ } => {
+            print!("{}", analyze(config)?);
+            Ok(())
+        }
+        Commands::Lint { format, filename } => {
+            if config.source_map.is_some() {
+                bail!("lint works on the macro source and cannot preprocess it");
+            }
+            let (output, findings) = lint(config, &filename.display().to_string(), format)?;
+            print!("{}", output);
+            if 0 < findings {
+                bail!("{} findings", findings);
+            }
+            Ok(())
+        }
+        Commands::Verify {
+            input,
+            step_limit,
+            filename: _,
+        } => {
+            let input = match input {
+                Some(path) => fs::read_to_string(path)?,
+                None => config.split_input().1.unwrap_or_default().to_string(),
+            };
+            print!("{}", verify(config, &input, step_limit)?);
+            Ok(())
+        }
     }
 }
 
+// Exit codes of errors in the program. Other errors exit with 1, usage errors with 2.
+const EXIT_PROGRAM_ERROR: u8 = 3;
+const EXIT_RUNTIME_ERROR: u8 = 4;
+
+/// Print errors in the program with an excerpt of the source code.
+///
+/// # Returns
+///     the exit code of the error if it was reported
+fn report_program_error(error: &anyhow::Error, filename: &Path) -> Option<ExitCode> {
+    // Preprocessor errors can occur in included files
+    if let Some(error) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<PreprocessorError>())
+    {
+        let source = fs::read_to_string(&error.position().file).ok()?;
+        eprint!("{}", error.render(&source));
+        return Some(ExitCode::from(EXIT_PROGRAM_ERROR));
+    }
+    let source = fs::read_to_string(filename).ok()?;
+    let filename = filename.display().to_string();
+    let (rendered, exit_code) = error.chain().find_map(|cause| {
+        if let Some(error) = cause.downcast_ref::<ProgramError>() {
+            Some((error.render(&filename, &source), EXIT_PROGRAM_ERROR))
+        } else if let Some(error) = cause.downcast_ref::<EvalError>() {
+            match error {
+                EvalError::DataPointerOutOfBounds(fault) => {
+                    Some((fault.render(&filename, &source), EXIT_RUNTIME_ERROR))
+                }
+                EvalError::MemoryOutOfBounds { .. }
+                | EvalError::InvalidInstructionPointer { .. } => {
+                    Some((format!("error: {}\n", error), EXIT_RUNTIME_ERROR))
+                }
+                EvalError::ProgramError(_) | EvalError::IOError(_) => None,
+            }
+        } else {
+            None
+        }
+    })?;
+    eprint!("{}", rendered);
+    Some(ExitCode::from(exit_code))
+}
+
 /// Main function
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     env_logger::try_init()?;
 
     let cli = CLIArgs::parse();
@@ -88,5 +305,18 @@ fn main() -> Result<()> {
             env::set_var("RUST_LIB_BACKTRACE", "1");
         }
     }
-    handle_cli_args(cli)
+    let print_timing = cli.print_timing;
+    let filename = cli.filename().cloned();
+    let now = Instant::now();
+    let result = handle_cli_args(cli);
+    if print_timing {
+        eprintln!("Execution time: {:.2?}", now.elapsed());
+    }
+    match result {
+        Err(error) => match filename.and_then(|filename| report_program_error(&error, &filename)) {
+            Some(exit_code) => Ok(exit_code),
+            None => Err(error),
+        },
+        Ok(()) => Ok(ExitCode::SUCCESS),
+    }
 }
@@ -0,0 +1,210 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Drives `brainfuck int debug --dap` with a scripted DAP client.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::{Value, json};
+
+// Tests run in parallel within the same process, so each client needs its own program file
+static CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+struct Client {
+    path: PathBuf,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    seq: u64,
+}
+
+impl Client {
+    fn start(program: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "dap_test_{}_{}.bf",
+            std::process::id(),
+            CLIENTS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, program).unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["int", "debug", "--dap"])
+            .arg(&path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Client {
+            path,
+            child,
+            stdin,
+            stdout,
+            seq: 0,
+        }
+    }
+
+    fn send(&mut self, command: &str, arguments: Value) {
+        self.seq += 1;
+        let request = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(
+            self.stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            request.len(),
+            request
+        )
+        .unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                content_length = value.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Send a request and return the body of its response.
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.send(command, arguments);
+        let response = self.receive();
+        assert_eq!(response["type"], "response");
+        assert_eq!(response["command"], command);
+        assert_eq!(response["success"], true, "{}", response);
+        response["body"].clone()
+    }
+
+    fn expect_event(&mut self, event: &str) -> Value {
+        let message = self.receive();
+        assert_eq!(message["type"], "event");
+        assert_eq!(message["event"], event, "{}", message);
+        message["body"].clone()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[test]
+fn test_dap_session() {
+    // Prints 'A' on the first line and 'B' on the second line
+    let mut client = Client::start("++++++++[>++++++++<-]>+.\n+.\n");
+
+    let capabilities = client.request("initialize", json!({ "adapterID": "brainfuck" }));
+    assert_eq!(capabilities["supportsReadMemoryRequest"], true);
+    client.expect_event("initialized");
+
+    client.request("launch", json!({ "stopOnEntry": true }));
+    let breakpoints = client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": "test.bf" }, "breakpoints": [{ "line": 2 }, { "line": 5 }] }),
+    );
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], false);
+
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.expect_event("stopped")["reason"], "entry");
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("stopped")["reason"], "step");
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(frames["stackFrames"][0]["line"], 1);
+    assert_eq!(frames["stackFrames"][0]["column"], 2);
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("output")["output"], "A");
+    assert_eq!(client.expect_event("stopped")["reason"], "breakpoint");
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(frames["stackFrames"][0]["line"], 2);
+
+    let variables = client.request("variables", json!({ "variablesReference": 1 }));
+    assert_eq!(variables["variables"][1]["name"], "dp");
+    assert_eq!(variables["variables"][1]["memoryReference"], "0x1");
+    let memory = client.request(
+        "readMemory",
+        json!({ "memoryReference": "0x0", "count": 2 }),
+    );
+    // [0, 65]
+    assert_eq!(memory["data"], "AEE=");
+
+    client.request(
+        "writeMemory",
+        json!({ "memoryReference": "0x1", "data": "Ww==" }),
+    );
+    client.request("continue", json!({ "threadId": 1 }));
+    // '[' + 1
+    assert_eq!(client.expect_event("output")["output"], "\\");
+    client.expect_event("exited");
+    client.expect_event("terminated");
+
+    client.request("disconnect", json!({}));
+}
+
+#[test]
+fn test_dap_pause() {
+    // Never terminates
+    let mut client = Client::start("+[]");
+    client.request("initialize", json!({ "adapterID": "brainfuck" }));
+    client.expect_event("initialized");
+    client.request("launch", json!({ "stopOnEntry": true }));
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.expect_event("stopped")["reason"], "entry");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    client.request("pause", json!({ "threadId": 1 }));
+    assert_eq!(client.expect_event("stopped")["reason"], "pause");
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(frames["stackFrames"][0]["line"], 1);
+
+    client.request("disconnect", json!({}));
+}
@@ -0,0 +1,142 @@
+// BSD 3-Clause License
+//
+// Copyright (c) 2025, Sven Stork
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this
+//    list of conditions and the following disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice,
+//    this list of conditions and the following disclaimer in the documentation
+//    and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its
+//    contributors may be used to endorse or promote products derived from
+//    this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//
+//! Drives `brainfuck int debug --gdb <port>` with a minimal remote serial protocol client.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+struct Client {
+    path: PathBuf,
+    child: Child,
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Client {
+    fn start(program: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("gdb_test_{}.bf", std::process::id()));
+        std::fs::write(&path, program).unwrap();
+        // Let the OS pick a free port
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["int", "debug", "--gdb", &port.to_string()])
+            .arg(&path)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stream = (0..100)
+            .find_map(|_| {
+                TcpStream::connect(("127.0.0.1", port))
+                    .inspect_err(|_| thread::sleep(Duration::from_millis(50)))
+                    .ok()
+            })
+            .expect("Cannot connect to gdb stub");
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Client {
+            path,
+            child,
+            stream,
+            reader,
+        }
+    }
+
+    /// Send a packet and return the reply.
+    fn request(&mut self, packet: &str) -> String {
+        let checksum = packet
+            .bytes()
+            .fold(0u8, |acc, value| acc.wrapping_add(value));
+        write!(self.stream, "${}#{:02x}", packet, checksum).unwrap();
+        let mut ack = [0];
+        self.reader.read_exact(&mut ack).unwrap();
+        assert_eq!(ack[0], b'+');
+        let mut start = [0];
+        self.reader.read_exact(&mut start).unwrap();
+        assert_eq!(start[0], b'$');
+        let mut reply = vec![];
+        self.reader.read_until(b'#', &mut reply).unwrap();
+        reply.pop();
+        let mut checksum = [0; 2];
+        self.reader.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[test]
+fn test_gdb_session() {
+    // Prints 'A'. The output instruction is at index 23.
+    let mut client = Client::start("++++++++[>++++++++<-]>+.+");
+
+    assert!(
+        client
+            .request("qSupported:swbreak+")
+            .contains("qXfer:features:read+")
+    );
+    assert!(
+        client
+            .request("qXfer:features:read:target.xml:0,fff")
+            .starts_with("l<?xml")
+    );
+    assert_eq!(client.request("?"), "S05");
+
+    assert_eq!(client.request("Z0,17,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    // ip = 23, dp = 1
+    assert_eq!(client.request("g"), "17000000000000000100000000000000");
+    assert_eq!(client.request("p1"), "0100000000000000");
+    assert_eq!(client.request("m0,2"), "0041");
+    assert_eq!(client.request("m0fff,2"), "E01");
+
+    assert_eq!(client.request("M0,1:2a"), "OK");
+    assert_eq!(client.request("m0,1"), "2a");
+    assert_eq!(client.request("P1=0000000000000000"), "OK");
+    assert_eq!(client.request("p1"), "0000000000000000");
+    assert_eq!(client.request("P1=0100000000000000"), "OK");
+
+    assert_eq!(client.request("z0,17,1"), "OK");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("c"), "W00");
+    assert_eq!(client.request("k"), "OK");
+}